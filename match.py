@@ -10,6 +10,8 @@ Round-robin tournament (3+ engines, separated by --):
 
 Options:
   -j N / --jobs N   Run up to N games in parallel (default: 1)
+  -t MS / --movetime MS
+                    Give each engine MS milliseconds per move (sent with every FEN)
 
 Example:
   python match.py 20 ./kid_shogi --num-tries 100 \
@@ -19,6 +21,7 @@ Example:
 Engine protocol (--engine mode):
   - Each engine runs as a persistent subprocess per game.
   - The match runner sends the initial FEN to the Sente engine.
+  - With --movetime, every FEN line is followed by " movetime <MS>".
   - Each engine reads a FEN, makes its move, and prints either:
       • The new FEN (game continues — fed directly to the other engine), or
       • A result string: "1-0" (Sente wins), "0-1" (Gote wins), "1/2-1/2" (draw).
@@ -59,7 +62,12 @@ def recv_line(proc: subprocess.Popen) -> str:
     return line.rstrip("\n")
 
 
-def play_game(cmd_a: list[str], cmd_b: list[str], a_is_sente: bool) -> tuple[str, float, float]:
+def fen_line(fen: str, movetime: int | None) -> str:
+    return fen if movetime is None else f"{fen} movetime {movetime}"
+
+
+def play_game(cmd_a: list[str], cmd_b: list[str], a_is_sente: bool,
+              movetime: int | None = None) -> tuple[str, float, float]:
     """Play one game. Returns (result, time_a_secs, time_b_secs)."""
     eng_a = start_engine(cmd_a)
     eng_b = start_engine(cmd_b)
@@ -75,7 +83,7 @@ def play_game(cmd_a: list[str], cmd_b: list[str], a_is_sente: bool) -> tuple[str
     # time_by_label["A"] / ["B"] = total seconds spent waiting for that engine
     time_by_label: dict[str, float] = {"A": 0.0, "B": 0.0}
     try:
-        send_line(sente_eng, INITIAL_FEN)
+        send_line(sente_eng, fen_line(INITIAL_FEN, movetime))
         movers = [(sente_eng, sente_label), (gote_eng, gote_label)]
         mover_idx = 0
         while True:
@@ -92,7 +100,7 @@ def play_game(cmd_a: list[str], cmd_b: list[str], a_is_sente: bool) -> tuple[str
                     result = gote_label
                 break
             mover_idx = 1 - mover_idx
-            send_line(movers[mover_idx][0], response)
+            send_line(movers[mover_idx][0], fen_line(response, movetime))
     finally:
         for eng in (eng_a, eng_b):
             try:
@@ -105,13 +113,14 @@ def play_game(cmd_a: list[str], cmd_b: list[str], a_is_sente: bool) -> tuple[str
 
 
 def run_match(games: int, cmd_a: list[str], cmd_b: list[str],
-              label_a: str, label_b: str, jobs: int = 1) -> tuple[int, int, int, float, float]:
+              label_a: str, label_b: str, jobs: int = 1,
+              movetime: int | None = None) -> tuple[int, int, int, float, float]:
     """Run `games` games. Returns (wins_a, draws, wins_b, total_time_a, total_time_b)."""
     from concurrent.futures import ThreadPoolExecutor, as_completed
 
     def run_one(i):
         a_is_sente = (i % 2 == 0)
-        result, ta, tb = play_game(cmd_a, cmd_b, a_is_sente)
+        result, ta, tb = play_game(cmd_a, cmd_b, a_is_sente, movetime)
         return i, a_is_sente, result, ta, tb
 
     wins_a = draws = wins_b = 0
@@ -146,8 +155,9 @@ def run_match(games: int, cmd_a: list[str], cmd_b: list[str],
 
 
 def parse_args(argv):
-    # Extract -j/--jobs before splitting on --
+    # Extract -j/--jobs and -t/--movetime before splitting on --
     jobs = 1
+    movetime = None
     filtered = []
     i = 0
     while i < len(argv):
@@ -157,6 +167,12 @@ def parse_args(argv):
         elif argv[i].startswith('--jobs='):
             jobs = int(argv[i].split('=', 1)[1])
             i += 1
+        elif argv[i] in ('-t', '--movetime') and i + 1 < len(argv):
+            movetime = int(argv[i + 1])
+            i += 2
+        elif argv[i].startswith('--movetime='):
+            movetime = int(argv[i].split('=', 1)[1])
+            i += 1
         else:
             filtered.append(argv[i])
             i += 1
@@ -180,7 +196,7 @@ def parse_args(argv):
     if len(engines) < 2:
         print("Need at least two engine specs separated by '--'")
         sys.exit(1)
-    return games, engines, jobs
+    return games, engines, jobs, movetime
 
 
 def engine_label(cmd: list[str], idx: int) -> str:
@@ -190,14 +206,14 @@ def engine_label(cmd: list[str], idx: int) -> str:
 
 
 def main():
-    games, engines, jobs = parse_args(sys.argv[1:])
+    games, engines, jobs, movetime = parse_args(sys.argv[1:])
     labels = [engine_label(cmd, i) for i, cmd in enumerate(engines)]
     if jobs > 1:
         print(f"Running up to {jobs} games in parallel.")
 
     if len(engines) == 2:
         # Simple two-engine match
-        w, d, l, ta, tb = run_match(games, engines[0], engines[1], labels[0], labels[1], jobs)
+        w, d, l, ta, tb = run_match(games, engines[0], engines[1], labels[0], labels[1], jobs, movetime)
         print()
         print(f"Results after {games} games ({labels[0]} vs {labels[1]}):")
         print(f"  {labels[0]} wins : {w}")
@@ -219,7 +235,7 @@ def main():
     total_pairs = len(pairs)
     for match_num, (i, j) in enumerate(pairs, 1):
         print(f"\n=== Match {match_num}/{total_pairs}: {labels[i]} vs {labels[j]} ===")
-        w, d, l, ta, tb = run_match(games, engines[i], engines[j], labels[i], labels[j], jobs)
+        w, d, l, ta, tb = run_match(games, engines[i], engines[j], labels[i], labels[j], jobs, movetime)
         wins[i][j] = w
         wins[j][i] = l
         draws[i][j] = draws[j][i] = d
@@ -1,11 +1,11 @@
 // Traits describing abstract game
 
-pub trait AbstractGame : Sized + Clone {
+pub trait AbstractGame : Sized + Clone + Send + Sync {
     /// Compact integer type used as a collision-free map key for positions.
     /// Choose the smallest type that fits all reachable positions for the game
     /// (e.g. u32 for tiny games, u64 for kid_shogi).
     /// There is intentionally no `from_hash` — hashes are write-only keys.
-    type PositionHash: Eq + std::hash::Hash + Copy;
+    type PositionHash: Eq + std::hash::Hash + Copy + Send + Sync;
 
     fn possible_moves(self: &Self) -> Vec<String>;
     fn make_move(self: &Self, mv: &str) -> Option<Self>;
@@ -16,6 +16,9 @@ pub trait AbstractGame : Sized + Clone {
     fn is_lost(self: &Self) -> bool;
     fn current_player(self: &Self) -> i32;  // actually 0 or 1
     fn pretty_print(self: &Self) -> String;
+    /// True if `mv` takes an opponent's piece. Used only as a search hint
+    /// (move ordering, quiescence), so games without captures keep the default.
+    fn is_capture(self: &Self, _mv: &str) -> bool { false }
 
     fn initial() -> Self;
     fn from_str(s: &str) -> Option<Self>;
@@ -26,8 +29,43 @@ pub trait NeuroPosition : AbstractGame {
     fn encode(self: &Self) -> Vec<f64>;
 }
 
+/// Positions described by a few active binary features, so that a network's first
+/// layer can be updated move by move instead of recomputed (NNUE).
+/// Features are seen from one player's side: both sides get the same indices for
+/// mirrored positions.
+pub trait SparsePosition : AbstractGame {
+    /// Number of distinct features; indices are below this
+    const FEATURE_COUNT: usize;
+    fn active_features(self: &Self, player: i32) -> Vec<usize>;
+    /// Features that `mv` turns off and on, as seen by `player`; `None` for illegal moves.
+    /// The default compares the feature sets before and after the move.
+    fn feature_changes(self: &Self, mv: &str, player: i32) -> Option<(Vec<usize>, Vec<usize>)> {
+        let next = self.make_move(mv)?;
+        let before = self.active_features(player);
+        let after = next.active_features(player);
+        let removed = before.iter().filter(|f| !after.contains(f)).copied().collect();
+        let added = after.iter().filter(|f| !before.contains(f)).copied().collect();
+        Some((removed, added))
+    }
+}
+
 pub trait StrategyFactory<PosT: AbstractGame + Send + 'static>: Send + Sync {
-    fn create(&self) -> Box<dyn crate::strategy::StrategyEngine<PosT>>;
+    /// New engine for one game; engines that play randomly draw from `seed`, so
+    /// equal seeds give equal games.
+    fn create(&self, seed: u64) -> Box<dyn crate::strategy::StrategyEngine<PosT> + Send>;
+    /// Engine for ranking candidate moves, if this kind of strategy supports it.
+    fn create_analysis(&self, _seed: u64) -> Option<Box<dyn crate::analysis::AnalysisEngine<PosT> + Send>> {
+        None
+    }
+}
+
+impl<PosT: AbstractGame + Send + 'static, F: StrategyFactory<PosT> + ?Sized> StrategyFactory<PosT> for Box<F> {
+    fn create(&self, seed: u64) -> Box<dyn crate::strategy::StrategyEngine<PosT> + Send> {
+        (**self).create(seed)
+    }
+    fn create_analysis(&self, seed: u64) -> Option<Box<dyn crate::analysis::AnalysisEngine<PosT> + Send>> {
+        (**self).create_analysis(seed)
+    }
 }
 
 pub trait Evaluator<PosT: AbstractGame> {
@@ -35,6 +73,27 @@ pub trait Evaluator<PosT: AbstractGame> {
     // Return saturation value for this evaluator; if ±saturation is returned,
     // evaluator believes that the position is won/lost
     fn saturation(&self) -> f64;
+    /// `evaluate_position` after each of the legal `moves` of `pos`; evaluators
+    /// that can score a move from its parent position override this.
+    fn evaluate_children(&self, pos: &PosT, moves: &[String]) -> Vec<f64> {
+        moves.iter().map(|mv| self.evaluate_position(&pos.make_move(mv).unwrap())).collect()
+    }
+}
+
+/// Prior probabilities of the legal `moves` of `pos`, in the same order; they are
+/// expected to be non-negative and to sum to 1.
+pub trait PolicyEvaluator<PosT: AbstractGame> {
+    fn policy(&self, pos: &PosT, moves: &[String]) -> Vec<f64>;
+}
+
+/// The same prior for every move
+#[derive(Clone, Copy, Default)]
+pub struct UniformPolicy;
+
+impl<PosT: AbstractGame> PolicyEvaluator<PosT> for UniformPolicy {
+    fn policy(&self, _pos: &PosT, moves: &[String]) -> Vec<f64> {
+        vec![1.0 / moves.len().max(1) as f64; moves.len()]
+    }
 }
 
 #[cfg(test)]
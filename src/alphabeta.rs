@@ -0,0 +1,590 @@
+// Negamax search with alpha-beta pruning, iterative deepening and a transposition table
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use crate::abstract_game::{self as ag};
+use crate::analysis::{self, AnalysisEngine, MoveAnalysis};
+use crate::strategy::{self, SearchLimits, StrategyEngine};
+use crate::transposition::{move_fingerprint, table_key, Bound, TTEntry, TranspositionTable};
+
+/// Score of a won position at the root; a win found `n` plies deep scores `WIN - n`,
+/// so shorter wins and longer losses are preferred. Evaluator scores are scaled to [-1, 1].
+pub const WIN: f64 = 1000.0;
+const MAX_PLY: usize = 128;
+const MAX_QUIESCENCE_PLY: usize = 8;
+const DEFAULT_TT_SIZE: usize = 1 << 18;
+/// Depth cap when the search is bounded by time or nodes instead of depth
+const MAX_BUDGETED_DEPTH: i32 = 64;
+
+pub fn is_win_score(score: f64) -> bool {
+    score.abs() >= WIN - MAX_PLY as f64
+}
+
+// Mate scores are stored relative to the node, not to the root, so that they stay
+// correct when the same position is reached at a different ply.
+fn score_to_tt(score: f64, ply: usize) -> f64 {
+    if is_win_score(score) { score + score.signum() * ply as f64 } else { score }
+}
+
+fn score_from_tt(score: f64, ply: usize) -> f64 {
+    if is_win_score(score) { score - score.signum() * ply as f64 } else { score }
+}
+
+/// Alpha-beta engine. With `threads > 1` it runs Lazy SMP: helper threads search
+/// the same root at staggered depths and share only the transposition table,
+/// which fills it with results the main thread then picks up.
+pub struct AlphaBetaStrategy<'a, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> {
+    eval: &'a EvalT,
+    max_depth: i32,
+    threads: usize,
+    tt: TranspositionTable,
+    history: HashMap<(i32, String), u64>,
+    nodes: usize,
+    phantom_data: PhantomData<PosT>,
+}
+
+impl<'a, PosT, EvalT> AlphaBetaStrategy<'a, PosT, EvalT>
+where
+    PosT: ag::AbstractGame + Send,
+    EvalT: ag::Evaluator<PosT> + Sync,
+{
+    pub fn new(eval: &'a EvalT, max_depth: i32, threads: usize) -> Self {
+        AlphaBetaStrategy {
+            eval,
+            max_depth,
+            threads: threads.max(1),
+            tt: TranspositionTable::new(DEFAULT_TT_SIZE),
+            history: HashMap::new(),
+            nodes: 0,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Number of nodes visited by all threads during the last `search`
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Iteratively deepen up to `max_depth`; returns the best move with its score
+    /// from the side to move's point of view.
+    pub fn search(&mut self, pos: &PosT) -> Option<(String, f64)> {
+        self.search_with_limits(pos, &SearchLimits::default())
+    }
+
+    /// Like `search`, but stops when `limits` run out and returns the result of
+    /// the last completed iteration. The first iteration always completes.
+    pub fn search_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<(String, f64)> {
+        self.tt.new_search();
+        let mut history = std::mem::take(&mut self.history);
+        history.values_mut().for_each(|h| *h /= 8);
+        let stop = AtomicBool::new(false);
+        let max_depth = self.max_depth_for(pos, limits);
+        let start = Instant::now();
+        let budget = Budget { limits, start, deadline: limits.deadline(start) };
+        let (eval, tt) = (self.eval, &self.tt);
+        let (best, nodes, history) = std::thread::scope(|scope| {
+            let helpers = (1..self.threads).map(|i| {
+                let pos = pos.clone();
+                let stop = &stop;
+                scope.spawn(move || {
+                    let mut helper = Searcher::new(eval, tt, stop, HashMap::new());
+                    helper.iterate(&pos, max_depth, (i % 2) as i32);
+                    helper.nodes
+                })
+            }).collect::<Vec<_>>();
+            let mut main = Searcher::new(eval, tt, &stop, history);
+            main.budget = Some(budget);
+            let best = main.iterate(pos, max_depth, 0);
+            stop.store(true, Ordering::Relaxed);
+            let helper_nodes: usize = helpers.into_iter().map(|h| h.join().unwrap()).sum();
+            (best, main.nodes + helper_nodes, main.history)
+        });
+        self.history = history;
+        self.nodes = nodes;
+        best
+    }
+
+    fn max_depth_for(&self, pos: &PosT, limits: &SearchLimits) -> i32 {
+        if pos.possible_moves().len() == 1 {
+            1  // nothing to think about
+        } else {
+            limits.depth.unwrap_or(if limits.is_budgeted() { MAX_BUDGETED_DEPTH } else { self.max_depth })
+        }
+    }
+}
+
+/// Limits checked by the main thread; helper threads only follow its stop flag.
+struct Budget<'s> {
+    limits: &'s SearchLimits,
+    start: Instant,
+    deadline: Option<Instant>,
+}
+
+/// Per-thread search state; everything but the transposition table is private.
+struct Searcher<'s, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> {
+    eval: &'s EvalT,
+    tt: &'s TranspositionTable,
+    stop: &'s AtomicBool,
+    budget: Option<Budget<'s>>,
+    completed_depth: i32,
+    killers: Vec<[Option<String>; 2]>,
+    history: HashMap<(i32, String), u64>,
+    path: Vec<PosT::PositionHash>,
+    root_best: Option<String>,
+    nodes: usize,
+}
+
+impl<'s, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> Searcher<'s, PosT, EvalT> {
+    fn new(eval: &'s EvalT, tt: &'s TranspositionTable, stop: &'s AtomicBool,
+           history: HashMap<(i32, String), u64>) -> Self {
+        Searcher {
+            eval,
+            tt,
+            stop,
+            budget: None,
+            completed_depth: 0,
+            killers: vec![[None, None]; MAX_PLY],
+            history,
+            path: Vec::new(),
+            root_best: None,
+            nodes: 0,
+        }
+    }
+
+    /// Iterative deepening; helper threads pass `depth_offset` 1 to stay one ply
+    /// ahead of the main thread.
+    fn iterate(&mut self, pos: &PosT, max_depth: i32, depth_offset: i32) -> Option<(String, f64)> {
+        let mut best = None;
+        for depth in (1 + depth_offset)..=max_depth {
+            let score = self.negamax(pos, depth, 0, -WIN, WIN);
+            if self.stop.load(Ordering::Relaxed) {
+                break  // interrupted iteration, result is meaningless
+            }
+            best = self.root_best.take().map(|mv| (mv, score));
+            self.completed_depth = depth;
+            if is_win_score(score) || self.out_of_budget() || !self.has_time_for_next_iteration() {
+                break
+            }
+        }
+        best
+    }
+
+    /// The best `num_pv` root moves at `depth`, best first. Each move is searched
+    /// with alpha at the current `num_pv`-th best score, so moves outside the list
+    /// only have to prove that they are worse.
+    fn multi_pv_root(&mut self, pos: &PosT, depth: i32, num_pv: usize, previous: &[String]) -> Vec<(String, f64)> {
+        let mut moves = pos.possible_moves();
+        moves.sort_by_key(|mv| previous.iter().position(|p| p == mv).unwrap_or(usize::MAX));
+        let mut scored: Vec<(String, f64)> = Vec::new();
+        self.path.push(pos.to_hash());
+        for mv in moves {
+            let alpha = if scored.len() >= num_pv { scored[num_pv - 1].1 } else { -WIN };
+            let child = pos.make_move(&mv).unwrap();
+            let score = -self.negamax(&child, depth - 1, 1, -WIN, -alpha);
+            if self.stop.load(Ordering::Relaxed) {
+                break
+            }
+            if scored.len() < num_pv || score > alpha {
+                scored.push((mv, score));
+                scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+                scored.truncate(num_pv);
+            }
+        }
+        self.path.pop();
+        scored
+    }
+
+    // Expected continuation after `pos`, read from the best moves stored in the table.
+    fn tt_line(&self, pos: &PosT, max_len: usize) -> Vec<String> {
+        analysis::follow_line(pos, max_len, |p| {
+            let fp = self.tt.probe(table_key(&p.to_hash()))?.best_move;
+            p.possible_moves().into_iter().find(|mv| fp != 0 && move_fingerprint(mv) == fp)
+        })
+    }
+
+    fn out_of_budget(&self) -> bool {
+        let Some(budget) = &self.budget else { return false };
+        self.completed_depth > 0 && (
+            budget.limits.is_stopped()
+            || budget.deadline.is_some_and(|d| Instant::now() >= d)
+            || budget.limits.nodes.is_some_and(|n| self.nodes >= n))
+    }
+
+    // Each iteration takes several times longer than the previous one, so past half
+    // the move time the next one would most likely be interrupted and wasted.
+    fn has_time_for_next_iteration(&self) -> bool {
+        match &self.budget {
+            Some(Budget { limits: SearchLimits { movetime: Some(t), .. }, start, .. }) =>
+                start.elapsed() < *t / 2,
+            _ => true,
+        }
+    }
+
+    fn evaluate(&self, pos: &PosT) -> f64 {
+        (self.eval.evaluate_position(pos) / self.eval.saturation()).clamp(-1.0, 1.0)
+    }
+
+    fn ordered_moves(&self, pos: &PosT, ply: usize, tt_move: u16) -> Vec<String> {
+        let player = pos.current_player();
+        let mut keyed = pos.possible_moves().into_iter().map(|mv| {
+            let key = if tt_move != 0 && move_fingerprint(&mv) == tt_move {
+                u64::MAX
+            } else if pos.is_capture(&mv) {
+                u64::MAX - 1
+            } else if self.killers[ply].contains(&Some(mv.clone())) {
+                u64::MAX - 2
+            } else {
+                self.history.get(&(player, mv.clone())).copied().unwrap_or(0)
+            };
+            (key, mv)
+        }).collect::<Vec<_>>();
+        keyed.sort_by_key(|k| std::cmp::Reverse(k.0));
+        keyed.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    fn record_cutoff(&mut self, pos: &PosT, mv: &str, depth: i32, ply: usize) {
+        if pos.is_capture(mv) { return }
+        let killers = &mut self.killers[ply];
+        if killers[0].as_deref() != Some(mv) {
+            killers[1] = killers[0].take();
+            killers[0] = Some(mv.to_string());
+        }
+        *self.history.entry((pos.current_player(), mv.to_string())).or_insert(0) += (depth * depth) as u64;
+    }
+
+    fn negamax(&mut self, pos: &PosT, depth: i32, ply: usize, mut alpha: f64, mut beta: f64) -> f64 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.out_of_budget() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        if self.stop.load(Ordering::Relaxed) {
+            return 0.0
+        }
+        if pos.is_lost() {
+            return -(WIN - ply as f64)
+        }
+        let hash = pos.to_hash();
+        if ply > 0 && self.path.contains(&hash) {
+            return 0.0  // repetition: treat as a draw
+        }
+        if depth <= 0 || ply + 1 >= MAX_PLY {
+            return self.quiescence(pos, ply, 0, alpha, beta)
+        }
+
+        // Mate distance pruning: no line from here can beat a win at the next ply
+        alpha = alpha.max(-(WIN - ply as f64));
+        beta = beta.min(WIN - (ply + 1) as f64);
+        if alpha >= beta {
+            return alpha
+        }
+
+        let key = table_key(&hash);
+        let mut tt_move = 0;
+        if let Some(entry) = self.tt.probe(key) {
+            tt_move = entry.best_move;
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        let moves = self.ordered_moves(pos, ply, tt_move);
+        if moves.is_empty() {
+            return self.evaluate(pos)
+        }
+        let alpha_orig = alpha;
+        let mut best_score = -WIN;
+        let mut best_move = None;
+        self.path.push(hash);
+        for mv in moves {
+            let child = pos.make_move(&mv).unwrap();
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha);
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv.clone());
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                self.record_cutoff(pos, &mv, depth, ply);
+                break
+            }
+        }
+        self.path.pop();
+        if self.stop.load(Ordering::Relaxed) {
+            return 0.0
+        }
+
+        let bound = if best_score <= alpha_orig {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.tt.store(key, TTEntry {
+            depth,
+            score: score_to_tt(best_score, ply),
+            bound,
+            best_move: best_move.as_deref().map_or(0, move_fingerprint),
+        });
+        if ply == 0 {
+            self.root_best = best_move;
+        }
+        best_score
+    }
+
+    // Resolve captures before trusting the static evaluation, so that a piece
+    // hanging at the horizon is not counted as material.
+    fn quiescence(&mut self, pos: &PosT, ply: usize, qply: usize, mut alpha: f64, beta: f64) -> f64 {
+        let stand_pat = self.evaluate(pos);
+        if stand_pat >= beta || qply >= MAX_QUIESCENCE_PLY {
+            return stand_pat
+        }
+        alpha = alpha.max(stand_pat);
+        let captures = pos.possible_moves().into_iter().filter(|mv| pos.is_capture(mv)).collect::<Vec<_>>();
+        for mv in captures {
+            self.nodes += 1;
+            let child = pos.make_move(&mv).unwrap();
+            let score = if child.is_lost() {
+                WIN - (ply + 1) as f64
+            } else {
+                -self.quiescence(&child, ply + 1, qply + 1, -beta, -alpha)
+            };
+            if score >= beta {
+                return score
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+impl<'a, PosT, EvalT> AnalysisEngine<PosT> for AlphaBetaStrategy<'a, PosT, EvalT>
+where
+    PosT: ag::AbstractGame + Send,
+    EvalT: ag::Evaluator<PosT> + Sync,
+{
+    /// Single-threaded iterative deepening over the root moves; every listed move
+    /// gets an exact score and a line read back from the transposition table.
+    fn analyze(&mut self, pos: &PosT, num_pv: usize, limits: &SearchLimits) -> Vec<MoveAnalysis> {
+        self.tt.new_search();
+        let stop = AtomicBool::new(false);
+        let max_depth = self.max_depth_for(pos, limits);
+        let start = Instant::now();
+        let mut searcher = Searcher::new(self.eval, &self.tt, &stop, std::mem::take(&mut self.history));
+        searcher.budget = Some(Budget { limits, start, deadline: limits.deadline(start) });
+        let mut best: Vec<(String, f64)> = Vec::new();
+        for depth in 1..=max_depth {
+            let previous = best.iter().map(|(mv, _)| mv.clone()).collect::<Vec<_>>();
+            let scored = searcher.multi_pv_root(pos, depth, num_pv.max(1), &previous);
+            if stop.load(Ordering::Relaxed) {
+                break  // interrupted iteration, result is meaningless
+            }
+            best = scored;
+            searcher.completed_depth = depth;
+            if best.first().is_some_and(|b| is_win_score(b.1))
+                || searcher.out_of_budget() || !searcher.has_time_for_next_iteration() {
+                break
+            }
+        }
+        let depth = searcher.completed_depth;
+        let lines = best.into_iter().map(|(mv, score)| {
+            let child = pos.make_move(&mv).unwrap();
+            let mut pv = vec![mv.clone()];
+            pv.extend(searcher.tt_line(&child, depth.max(1) as usize - 1));
+            MoveAnalysis { mv, score, visits: None, depth: Some(depth), pv }
+        }).collect();
+        self.nodes = searcher.nodes;
+        self.history = searcher.history;
+        lines
+    }
+}
+
+impl<'a, PosT, EvalT> strategy::StrategyEngine<PosT> for AlphaBetaStrategy<'a, PosT, EvalT>
+where
+    PosT: ag::AbstractGame + Send,
+    EvalT: ag::Evaluator<PosT> + Sync,
+{
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.search(pos).map(|(mv, _)| mv)
+    }
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        self.search_with_limits(pos, limits).map(|(mv, _)| mv)
+    }
+    /// Searches every reply to full depth, one ply deeper than a move search,
+    /// so that the next search finds its root and most of its tree in the table.
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        let limits = SearchLimits { depth: limits.depth.or(Some(self.max_depth + 1)), ..limits.clone() };
+        self.search_with_limits(pos, &limits);
+    }
+}
+
+/// Creates a fresh `AlphaBetaStrategy` (with an empty transposition table) for each game.
+pub struct AlphaBetaFactory<PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT> + Sync + 'static> {
+    pub eval: &'static EvalT,
+    pub max_depth: i32,
+    pub threads: usize,
+    _pos: PhantomData<PosT>,
+}
+
+impl<PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT> + Sync + 'static> AlphaBetaFactory<PosT, EvalT> {
+    pub fn new(eval: &'static EvalT, max_depth: i32, threads: usize) -> Self {
+        AlphaBetaFactory { eval, max_depth, threads, _pos: PhantomData }
+    }
+}
+
+impl<PosT, EvalT> ag::StrategyFactory<PosT> for AlphaBetaFactory<PosT, EvalT>
+where
+    PosT: ag::AbstractGame + Send + Sync + 'static,
+    EvalT: ag::Evaluator<PosT> + Sync + Send + 'static,
+{
+    fn create(&self, _seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(AlphaBetaStrategy::new(self.eval, self.max_depth, self.threads))
+    }
+    fn create_analysis(&self, _seed: u64) -> Option<Box<dyn AnalysisEngine<PosT> + Send>> {
+        Some(Box::new(AlphaBetaStrategy::new(self.eval, self.max_depth, self.threads)))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::{tests as agt, AbstractGame};
+    use crate::kids_shogi::{KidsShogiGame, SimpleEvaluator};
+    use crate::strategy::{self, StrategyEngine};
+
+    use super::*;
+
+    #[test]
+    fn solves_one_two_game() {
+        let eval = strategy::OneStepEvaluator::<agt::OneTwoGame>::new();
+        let mut strat = AlphaBetaStrategy::new(&eval, 12, 1);
+        // Multiples of 3 are lost for the side to move
+        for (heap, expected) in [("8 0", "2"), ("7 1", "1"), ("5 0", "2"), ("4 0", "1")] {
+            let pos = agt::OneTwoGame::from_str(heap).unwrap();
+            let (mv, score) = strat.search(&pos).unwrap();
+            assert_eq!(mv, expected, "heap {}", heap);
+            assert!(is_win_score(score) && score > 0.0, "heap {} score {}", heap, score);
+        }
+        let (_, score) = strat.search(&agt::OneTwoGame::from_str("9 0").unwrap()).unwrap();
+        assert!(is_win_score(score) && score < 0.0);
+    }
+
+    #[test]
+    fn captures_lion() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let mut strat = AlphaBetaStrategy::new(&SimpleEvaluator{}, 4, 1);
+        let (mv, score) = strat.search(&pos).unwrap();
+        assert_eq!(mv, "a3a4");
+        assert_eq!(score, WIN - 1.0);
+    }
+
+    #[test]
+    fn prefers_shortest_win() {
+        // Lion can win by try (c3c4) right away, or dawdle first
+        let pos = KidsShogiGame::from_fen("l2/2L/3/3 b -").unwrap();
+        let mut strat = AlphaBetaStrategy::new(&SimpleEvaluator{}, 5, 1);
+        let (mv, score) = strat.search(&pos).unwrap();
+        assert_eq!(mv, "c3c4");
+        assert_eq!(score, WIN - 1.0);
+    }
+
+    #[test]
+    fn avoids_hanging_lion() {
+        // Gote's elephant on b2 attacks a1 and c1; moving the lion there loses at once
+        let pos = KidsShogiGame::from_fen("1l1/3/1e1/1L1 b -").unwrap();
+        let mut strat = AlphaBetaStrategy::new(&SimpleEvaluator{}, 3, 1);
+        let mv = strat.choose_move(&pos).unwrap();
+        assert_ne!(mv, "b1a1");
+        assert_ne!(mv, "b1c1");
+    }
+
+    #[test]
+    fn lazy_smp_agrees_with_single_thread() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let mut strat = AlphaBetaStrategy::new(&SimpleEvaluator{}, 6, 4);
+        let (mv, score) = strat.search(&pos).unwrap();
+        assert_eq!(mv, "a3a4");
+        assert_eq!(score, WIN - 1.0);
+
+        let initial = KidsShogiGame::initial();
+        let mut multi = AlphaBetaStrategy::new(&SimpleEvaluator{}, 5, 3);
+        let mv = multi.choose_move(&initial).unwrap();
+        assert!(initial.possible_moves().contains(&mv));
+        assert!(multi.nodes() > 0);
+    }
+
+    #[test]
+    fn search_limits() {
+        let pos = KidsShogiGame::initial();
+        let mut strat = AlphaBetaStrategy::new(&SimpleEvaluator{}, 64, 2);
+
+        let limits = SearchLimits::with_movetime(Some(std::time::Duration::from_millis(100)));
+        let t0 = Instant::now();
+        assert!(strat.search_with_limits(&pos, &limits).is_some());
+        assert!(t0.elapsed() < std::time::Duration::from_secs(5));
+
+        let stopped = SearchLimits::default();
+        stopped.stop.store(true, Ordering::Relaxed);
+        assert!(strat.search_with_limits(&pos, &stopped).is_some());  // depth 1 still completes
+
+        let few_nodes = SearchLimits { nodes: Some(2000), ..Default::default() };
+        assert!(strat.search_with_limits(&pos, &few_nodes).is_some());
+        assert!(strat.nodes() < 200000);
+
+        let shallow = SearchLimits { depth: Some(2), ..Default::default() };
+        assert!(strat.search_with_limits(&pos, &shallow).is_some());
+    }
+
+    #[test]
+    fn pondering_fills_the_table() {
+        let pos = KidsShogiGame::initial();
+        let reply = pos.make_move("b2b3").unwrap();
+        let mut fresh = AlphaBetaStrategy::new(&SimpleEvaluator{}, 5, 1);
+        fresh.search(&reply).unwrap();
+        let mut pondered = AlphaBetaStrategy::new(&SimpleEvaluator{}, 5, 1);
+        pondered.ponder(&pos, &SearchLimits::default());
+        pondered.search(&reply).unwrap();
+        assert!(pondered.nodes() < fresh.nodes(), "{} vs {}", pondered.nodes(), fresh.nodes());
+
+        // A raised stop flag ends pondering after the first iteration
+        let stopped = SearchLimits::default();
+        stopped.stop.store(true, Ordering::Relaxed);
+        let mut deep = AlphaBetaStrategy::new(&SimpleEvaluator{}, 64, 1);
+        deep.ponder(&pos, &stopped);
+        assert!(deep.nodes() < 1000);
+    }
+
+    #[test]
+    fn multi_pv_analysis() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let mut strat = AlphaBetaStrategy::new(&SimpleEvaluator{}, 4, 1);
+        let lines = strat.analyze(&pos, 3, &SearchLimits::default());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].mv, "a3a4");
+        assert_eq!(lines[0].score, WIN - 1.0);
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+        for line in &lines {
+            assert_eq!(line.pv[0], line.mv);
+            let mut p = pos.clone();
+            for mv in &line.pv {
+                p = p.make_move(mv).expect("pv must be legal");
+            }
+        }
+        // Scores of the listed moves are exact: they agree with a search after each move
+        for line in &lines[1..] {
+            let child = pos.make_move(&line.mv).unwrap();
+            let (_, child_score) = AlphaBetaStrategy::new(&SimpleEvaluator{}, 3, 1).search(&child).unwrap();
+            assert!((line.score + child_score).abs() < 1e-6, "{} {} {}", line.mv, line.score, child_score);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// Multi-PV analysis: ranked candidate moves with scores and principal variations
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::SearchLimits;
+
+/// One candidate move of an analysed position.
+/// `score` is from the side to move's point of view: the average reward in [-1, 1]
+/// for MCTS, the negamax score for minimax engines (wins are close to ±`alphabeta::WIN`).
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct MoveAnalysis {
+    #[serde(rename = "move")]
+    pub mv: String,
+    pub score: f64,
+    /// Visits of the move's subtree (MCTS)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub visits: Option<usize>,
+    /// Completed search depth (minimax engines)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub depth: Option<i32>,
+    /// Expected line, starting with `mv`
+    pub pv: Vec<String>,
+}
+
+pub trait AnalysisEngine<PosT: ag::AbstractGame> {
+    /// Up to `num_pv` moves, best first.
+    fn analyze(&mut self, pos: &PosT, num_pv: usize, limits: &SearchLimits) -> Vec<MoveAnalysis>;
+}
+
+impl<PosT: ag::AbstractGame, A: AnalysisEngine<PosT> + ?Sized> AnalysisEngine<PosT> for Box<A> {
+    fn analyze(&mut self, pos: &PosT, num_pv: usize, limits: &SearchLimits) -> Vec<MoveAnalysis> {
+        (**self).analyze(pos, num_pv, limits)
+    }
+}
+
+/// Human-readable table, one line per move.
+pub fn format_analysis(lines: &[MoveAnalysis]) -> String {
+    lines.iter().enumerate().map(|(i, line)| {
+        let effort = match (line.visits, line.depth) {
+            (Some(v), _) => format!("visits {}", v),
+            (None, Some(d)) => format!("depth {}", d),
+            (None, None) => String::new(),
+        };
+        format!("{}. {:<6} score {:>8.3}  {:<12} pv {}\n", i + 1, line.mv, line.score, effort, line.pv.join(" "))
+    }).collect()
+}
+
+/// Follow `next_move` from `pos` for at most `max_len` moves, stopping at the end
+/// of the game or on a repeated position.
+pub fn follow_line<PosT: ag::AbstractGame>(
+    pos: &PosT, max_len: usize, mut next_move: impl FnMut(&PosT) -> Option<String>,
+) -> Vec<String> {
+    let mut line = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut pos = pos.clone();
+    while line.len() < max_len && !pos.is_lost() && seen.insert(pos.to_hash()) {
+        let Some(mv) = next_move(&pos) else { break };
+        let Some(next) = pos.make_move(&mv) else { break };
+        line.push(mv);
+        pos = next;
+    }
+    line
+}
@@ -0,0 +1,363 @@
+// Opening book: weighted moves per position, built from self-play or imported games
+//
+// Binary format (all integers little-endian):
+//   magic     8 bytes  "KSBOOK\0\x01"
+//   positions u32
+//   then for each position, in increasing hash order:
+//     hash    u64      `AbstractGame::to_hash` of the position
+//     moves   u16
+//     then for each move:
+//       len    u8      length of the move string
+//       move   len bytes, UTF-8
+//       weight u32
+//
+// A book with more than u32::MAX positions, more than u16::MAX moves in a position or
+// a move longer than 255 bytes cannot be written (`io::ErrorKind::InvalidInput`).
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use rand::distributions::WeightedIndex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::{derive_seed, SearchLimits, StrategyEngine};
+use crate::tree_dump::TreeNode;
+
+const MAGIC: &[u8; 8] = b"KSBOOK\0\x01";
+
+/// Weight added to a move for each game its side won or drew; lost games add nothing,
+/// so moves that only ever lost drop out of the book.
+const WIN_WEIGHT: u32 = 2;
+const DRAW_WEIGHT: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookMove {
+    pub mv: String,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpeningBook {
+    entries: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of positions in the book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn moves(&self, hash: u64) -> &[BookMove] {
+        self.entries.get(&hash).map_or(&[], |v| v.as_slice())
+    }
+
+    pub fn add(&mut self, hash: u64, mv: &str, weight: u32) {
+        let moves = self.entries.entry(hash).or_default();
+        match moves.iter_mut().find(|m| m.mv == mv) {
+            Some(m) => m.weight = m.weight.saturating_add(weight),
+            None => moves.push(BookMove { mv: mv.to_string(), weight }),
+        }
+    }
+
+    /// Record the first `max_plies` moves of a game played from the initial position.
+    /// `winner` is the winning player, `None` for a draw. Returns false (and adds
+    /// nothing) if the game contains an illegal move.
+    pub fn add_game<PosT>(&mut self, moves: &[String], winner: Option<i32>, max_plies: usize) -> bool
+    where
+        PosT: ag::AbstractGame,
+        PosT::PositionHash: Into<u64>,
+    {
+        let mut pos = PosT::initial();
+        let mut entries = Vec::new();
+        for mv in moves.iter().take(max_plies) {
+            let Some(next) = pos.make_move(mv) else { return false };
+            let weight = match winner {
+                Some(w) if w == pos.current_player() => WIN_WEIGHT,
+                Some(_) => 0,
+                None => DRAW_WEIGHT,
+            };
+            entries.push((pos.to_hash().into(), mv, weight));
+            pos = next;
+        }
+        for (hash, mv, weight) in entries {
+            self.add(hash, mv, weight);
+        }
+        true
+    }
+
+    /// Import games in text form, one per line: moves separated by spaces from the
+    /// initial position, optionally followed by "1-0", "0-1" or "1/2-1/2" (the
+    /// `--engine` result strings). Games without a result count as draws.
+    /// Returns the number of games imported; illegal games are skipped.
+    pub fn import_games<PosT>(&mut self, text: &str, max_plies: usize) -> usize
+    where
+        PosT: ag::AbstractGame,
+        PosT::PositionHash: Into<u64>,
+    {
+        text.lines().filter(|line| {
+            let mut tokens = line.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+            let winner = match tokens.last().map(String::as_str) {
+                Some("1-0") => Some(0),
+                Some("0-1") => Some(1),
+                _ => None,
+            };
+            if matches!(tokens.last().map(String::as_str), Some("1-0" | "0-1" | "1/2-1/2")) {
+                tokens.pop();
+            }
+            !tokens.is_empty() && self.add_game::<PosT>(&tokens, winner, max_plies)
+        }).count()
+    }
+
+    /// Play `games` games of `strat` against itself and add their openings.
+    /// Games longer than `max_game_plies` are scored as draws.
+    pub fn add_self_play<PosT, S>(&mut self, strat: &mut S, games: usize, max_plies: usize, max_game_plies: usize)
+    where
+        PosT: ag::AbstractGame,
+        PosT::PositionHash: Into<u64>,
+        S: StrategyEngine<PosT> + ?Sized,
+    {
+        for _ in 0..games {
+            let mut pos = PosT::initial();
+            let mut moves = Vec::new();
+            while !pos.is_lost() && moves.len() < max_game_plies {
+                let Some(mv) = strat.choose_move(&pos) else { break };
+                pos = pos.make_move(&mv).expect("strategy must play legal moves");
+                moves.push(mv);
+            }
+            let winner = pos.is_lost().then(|| 1 - pos.current_player());
+            self.add_game::<PosT>(&moves, winner, max_plies);
+        }
+    }
+
+    /// Drop moves lighter than `min_weight`, and positions left without moves.
+    pub fn prune(&mut self, min_weight: u32) {
+        self.entries.values_mut().for_each(|moves| moves.retain(|m| m.weight >= min_weight.max(1)));
+        self.entries.retain(|_, moves| !moves.is_empty());
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let too_large = |what: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("{} does not fit in a book", what));
+        w.write_all(MAGIC)?;
+        let positions = u32::try_from(self.entries.len()).map_err(|_| too_large("the number of positions"))?;
+        w.write_all(&positions.to_le_bytes())?;
+        let mut hashes = self.entries.keys().copied().collect::<Vec<_>>();
+        hashes.sort();
+        for hash in hashes {
+            let moves = &self.entries[&hash];
+            w.write_all(&hash.to_le_bytes())?;
+            let count = u16::try_from(moves.len()).map_err(|_| too_large("the number of moves in a position"))?;
+            w.write_all(&count.to_le_bytes())?;
+            for m in moves {
+                let len = u8::try_from(m.mv.len()).map_err(|_| too_large(&format!("move {:?}", m.mv)))?;
+                w.write_all(&[len])?;
+                w.write_all(m.mv.as_bytes())?;
+                w.write_all(&m.weight.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+            let mut buf = [0u8; N];
+            r.read_exact(&mut buf)?;
+            Ok(buf)
+        }
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        if &read_array::<R, 8>(r)? != MAGIC {
+            return Err(invalid("not an opening book"))
+        }
+        let positions = u32::from_le_bytes(read_array(r)?);
+        let mut book = OpeningBook::new();
+        for _ in 0..positions {
+            let hash = u64::from_le_bytes(read_array(r)?);
+            let count = u16::from_le_bytes(read_array(r)?);
+            for _ in 0..count {
+                let [len] = read_array::<R, 1>(r)?;
+                let mut mv = vec![0u8; len as usize];
+                r.read_exact(&mut mv)?;
+                let mv = String::from_utf8(mv).map_err(|_| invalid("move is not UTF-8"))?;
+                let weight = u32::from_le_bytes(read_array(r)?);
+                book.add(hash, &mv, weight);
+            }
+        }
+        Ok(book)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::read_from(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+/// Plays a weighted-random book move while the position is in the book, and asks
+/// `followup` otherwise. Book moves that are not legal in the position (a corrupt
+/// book or a book for another game) are ignored.
+pub struct BookStrategy<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> {
+    book: Arc<OpeningBook>,
+    followup: F,
+    rng: StdRng,
+    pos_type: PhantomData<PosT>,
+}
+
+impl<PosT, F> BookStrategy<PosT, F>
+where
+    PosT: ag::AbstractGame,
+    PosT::PositionHash: Into<u64>,
+    F: StrategyEngine<PosT>,
+{
+    pub fn new(book: Arc<OpeningBook>, seed: u64, followup: F) -> Self {
+        BookStrategy { book, followup, rng: StdRng::seed_from_u64(seed), pos_type: PhantomData }
+    }
+
+    fn book_move(&mut self, pos: &PosT) -> Option<String> {
+        let legal = pos.possible_moves();
+        let candidates = self.book.moves(pos.to_hash().into()).iter()
+            .filter(|m| m.weight > 0 && legal.contains(&m.mv))
+            .collect::<Vec<_>>();
+        let wi = WeightedIndex::new(candidates.iter().map(|m| m.weight)).ok()?;
+        Some(candidates[self.rng.sample(wi)].mv.clone())
+    }
+}
+
+impl<PosT, F> StrategyEngine<PosT> for BookStrategy<PosT, F>
+where
+    PosT: ag::AbstractGame,
+    PosT::PositionHash: Into<u64>,
+    F: StrategyEngine<PosT>,
+{
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.book_move(pos).or_else(|| self.followup.choose_move(pos))
+    }
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        self.book_move(pos).or_else(|| self.followup.choose_move_with_limits(pos, limits))
+    }
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        self.followup.ponder(pos, limits)
+    }
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        self.followup.dump_tree(pos, depth, min_visits)
+    }
+}
+
+/// Wraps the strategies of another factory in a `BookStrategy` sharing one book.
+pub struct BookFactory<PosT: ag::AbstractGame, F: ag::StrategyFactory<PosT>>
+where
+    PosT: Send + 'static,
+{
+    book: Arc<OpeningBook>,
+    inner: F,
+    _pos: PhantomData<fn() -> PosT>,
+}
+
+impl<PosT: ag::AbstractGame + Send + 'static, F: ag::StrategyFactory<PosT>> BookFactory<PosT, F> {
+    pub fn new(book: Arc<OpeningBook>, inner: F) -> Self {
+        BookFactory { book, inner, _pos: PhantomData }
+    }
+}
+
+impl<PosT, F> ag::StrategyFactory<PosT> for BookFactory<PosT, F>
+where
+    PosT: ag::AbstractGame + Send + 'static,
+    PosT::PositionHash: Into<u64>,
+    F: ag::StrategyFactory<PosT>,
+{
+    fn create(&self, seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(BookStrategy::new(Arc::clone(&self.book), derive_seed(seed, 0), self.inner.create(derive_seed(seed, 1))))
+    }
+    fn create_analysis(&self, seed: u64) -> Option<Box<dyn crate::analysis::AnalysisEngine<PosT> + Send>> {
+        self.inner.create_analysis(seed)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::AbstractGame;
+    use crate::kids_shogi::KidsShogiGame;
+    use crate::strategy::RandomMoveStrategy;
+
+    use super::*;
+
+    fn moves(s: &str) -> Vec<String> {
+        s.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn weights_follow_results() {
+        let mut book = OpeningBook::new();
+        let initial = KidsShogiGame::initial().to_hash();
+        assert!(book.add_game::<KidsShogiGame>(&moves("b2b3 b4b3"), Some(0), 10));
+        assert!(book.add_game::<KidsShogiGame>(&moves("b2b3 b4b3"), None, 10));
+        assert!(book.add_game::<KidsShogiGame>(&moves("c1c2"), Some(1), 10));
+        assert!(!book.add_game::<KidsShogiGame>(&moves("b2b4"), None, 10));
+        assert_eq!(book.moves(initial), &[
+            BookMove { mv: "b2b3".to_string(), weight: WIN_WEIGHT + DRAW_WEIGHT },
+            BookMove { mv: "c1c2".to_string(), weight: 0 },
+        ]);
+        book.prune(1);
+        assert_eq!(book.moves(initial).len(), 1);
+        // max_plies cuts the game short
+        let mut short = OpeningBook::new();
+        short.add_game::<KidsShogiGame>(&moves("b2b3 b4b3"), None, 1);
+        assert_eq!(short.len(), 1);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut book = OpeningBook::new();
+        let imported = book.import_games::<KidsShogiGame>("b2b3 b4b3 1-0\nc1c2 0-1\nb2b4\n\nb1c2 b3b2 1/2-1/2\n", 8);
+        assert_eq!(imported, 3);
+        let mut buf = Vec::new();
+        book.write_to(&mut buf).unwrap();
+        assert_eq!(&buf[..8], MAGIC);
+        assert_eq!(OpeningBook::read_from(&mut buf.as_slice()).unwrap(), book);
+        assert!(OpeningBook::read_from(&mut &buf[..buf.len() - 1]).is_err());
+        assert!(OpeningBook::read_from(&mut &b"not a book at all"[..]).is_err());
+
+        let mut long = OpeningBook::new();
+        long.add(1, &"x".repeat(256), 1);
+        assert_eq!(long.write_to(&mut Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let mut wide = OpeningBook::new();
+        wide.entries.insert(1, (0..=u16::MAX as u32).map(|i| BookMove { mv: i.to_string(), weight: 1 }).collect());
+        assert_eq!(wide.write_to(&mut Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn strategy_plays_book_then_followup() {
+        let mut book = OpeningBook::new();
+        book.add_game::<KidsShogiGame>(&moves("b1c2 b3b2"), Some(0), 10);
+        let initial = KidsShogiGame::initial();
+        let mut strat = BookStrategy::new(Arc::new(book), 1, RandomMoveStrategy::new(7));
+        for _ in 0..10 {
+            assert_eq!(strat.choose_move(&initial).unwrap(), "b1c2");
+        }
+        let out_of_book = initial.make_move("c1c2").unwrap();
+        assert!(out_of_book.possible_moves().contains(&strat.choose_move(&out_of_book).unwrap()));
+    }
+
+    #[test]
+    fn self_play_fills_book() {
+        let mut book = OpeningBook::new();
+        book.add_self_play::<KidsShogiGame, _>(&mut RandomMoveStrategy::new(3), 20, 4, 100);
+        let first_moves = book.moves(KidsShogiGame::initial().to_hash());
+        assert!(!first_moves.is_empty());
+        assert!(first_moves.iter().map(|m| m.weight).sum::<u32>() <= 20 * WIN_WEIGHT);
+    }
+}
@@ -0,0 +1,404 @@
+// Depth-first proof-number search (df-pn) for forced wins
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::{SearchLimits, StrategyEngine};
+use crate::tree_dump::TreeNode;
+
+const INF: u64 = u64::MAX / 4;
+const MAX_LINE: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveResult {
+    /// Side to move wins; the proof line alternates winner and defender moves
+    Win(Vec<String>),
+    /// Side to move cannot force a win (the defender can hold a draw or win)
+    NoWin,
+    /// Node budget, time or a stop request ran out before the search was decided
+    Unknown,
+}
+
+// Proof and disproof numbers, and the repeated positions a disproof rests on
+type Entry<HashT> = (u64, u64, Vec<HashT>);
+
+/// Proves or disproves a forced win for the side to move.
+/// OR nodes are the attacker's turns (the proof number is the minimum over children),
+/// AND nodes the defender's (the proof number is the sum). A repetition on the current
+/// path counts as a failed attack, so cycles cannot be used to "prove" anything; a
+/// disproof resting on one is reused only while the repeated positions are on the path.
+pub struct DfPnSolver<PosT: ag::AbstractGame> {
+    max_nodes: usize,
+    nodes: usize,
+    attacker: i32,
+    tt: HashMap<PosT::PositionHash, Entry<PosT::PositionHash>>,
+    path: HashSet<PosT::PositionHash>,
+    deadline: Option<Instant>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<PosT: ag::AbstractGame> DfPnSolver<PosT> {
+    pub fn new(max_nodes: usize) -> Self {
+        DfPnSolver { max_nodes, nodes: 0, attacker: 0, tt: HashMap::new(), path: HashSet::new(),
+            deadline: None, stop: Arc::default() }
+    }
+
+    /// Nodes expanded by the last `solve`
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    pub fn solve(&mut self, pos: &PosT) -> SolveResult {
+        self.solve_with_limits(pos, &SearchLimits::default())
+    }
+
+    /// Like `solve`, but giving up (`Unknown`) once `limits.movetime` has passed or
+    /// `limits.stop` is raised
+    pub fn solve_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> SolveResult {
+        // Each root starts afresh, so the table does not grow from one move to the next
+        self.tt.clear();
+        self.attacker = pos.current_player();
+        self.nodes = 0;
+        self.path.clear();
+        self.deadline = limits.deadline(Instant::now());
+        self.stop = limits.stop.clone();
+        self.mid(pos, INF, INF);
+        match self.numbers(pos) {
+            (0, _) => SolveResult::Win(self.proof_line(pos)),
+            (_, 0) => SolveResult::NoWin,
+            _ => SolveResult::Unknown,
+        }
+    }
+
+    // Node budget spent, time up or stopped; the clock is only read now and then
+    fn is_out_of_budget(&self) -> bool {
+        self.nodes >= self.max_nodes || self.stop.load(Ordering::Relaxed)
+            || (self.nodes.is_multiple_of(256) && self.deadline.is_some_and(|d| Instant::now() >= d))
+    }
+
+    fn is_or_node(&self, pos: &PosT) -> bool {
+        pos.current_player() == self.attacker
+    }
+
+    fn numbers(&self, pos: &PosT) -> (u64, u64) {
+        if pos.is_lost() {
+            return if self.is_or_node(pos) { (INF, 0) } else { (0, INF) }
+        }
+        let hash = pos.to_hash();
+        if self.path.contains(&hash) {
+            return (INF, 0)
+        }
+        match self.tt.get(&hash) {
+            // Reached by another path, the disproof may not hold: search again
+            Some((_, _, repeats)) if !repeats.iter().all(|h| self.path.contains(h)) => (1, 1),
+            Some(&(pn, dn, _)) => (pn, dn),
+            None => (1, 1),
+        }
+    }
+
+    // Positions of the current path whose repetition the disproof of `pos` rests on
+    fn repeats(&self, pos: &PosT) -> Vec<PosT::PositionHash> {
+        let hash = pos.to_hash();
+        if pos.is_lost() {
+            Vec::new()
+        } else if self.path.contains(&hash) {
+            vec![hash]
+        } else {
+            self.tt.get(&hash).map(|(_, _, repeats)| repeats.clone()).unwrap_or_default()
+        }
+    }
+
+    // Proof and disproof numbers of an interior node, given its children's, and the
+    // repetitions a disproof rests on: those of every child at OR nodes, and those of
+    // the disproved child resting on fewest at AND nodes. A return to `pos` itself
+    // fails wherever `pos` is reached, so it does not count.
+    fn combine(&self, pos: &PosT, children: &[PosT]) -> Entry<PosT::PositionHash> {
+        let numbers = children.iter().map(|c| self.numbers(c));
+        let (min_of, sum_of) = numbers.fold((INF, 0u64), |(min, sum), (pn, dn)| {
+            if self.is_or_node(pos) { (min.min(pn), (sum + dn).min(INF)) } else { (min.min(dn), (sum + pn).min(INF)) }
+        });
+        let disproved = children.iter().filter(|c| self.numbers(c).1 == 0).map(|c| self.repeats(c));
+        let mut repeats = match self.is_or_node(pos) {
+            true if sum_of == 0 => disproved.flatten().collect(),
+            false if min_of == 0 => disproved.min_by_key(|r| r.len()).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let hash = pos.to_hash();
+        let mut seen = HashSet::new();
+        repeats.retain(|&h| h != hash && seen.insert(h));
+        if self.is_or_node(pos) { (min_of, sum_of, repeats) } else { (sum_of, min_of, repeats) }
+    }
+
+    // Multiple iterative deepening: search below `pos` until its proof number reaches
+    // `th_pn` or its disproof number reaches `th_dn`.
+    fn mid(&mut self, pos: &PosT, th_pn: u64, th_dn: u64) {
+        let (pn, dn) = self.numbers(pos);
+        if pn >= th_pn || dn >= th_dn || pn == 0 || dn == 0 {
+            return
+        }
+        let hash = pos.to_hash();
+        let children = pos.possible_moves().iter()
+            .map(|mv| pos.make_move(mv).unwrap())
+            .collect::<Vec<_>>();
+        self.path.insert(hash);
+        loop {
+            let (pn, dn, repeats) = if children.is_empty() { (INF, 0, Vec::new()) } else { self.combine(pos, &children) };
+            self.tt.insert(hash, (pn, dn, repeats));
+            if pn >= th_pn || dn >= th_dn || pn == 0 || dn == 0 || self.is_out_of_budget() {
+                break
+            }
+            self.nodes += 1;
+            // Most-proving child: smallest proof number at OR nodes, smallest disproof
+            // number at AND nodes; its threshold leaves room to switch to the runner-up.
+            let or_node = self.is_or_node(pos);
+            let key = |n: (u64, u64)| if or_node { n.0 } else { n.1 };
+            let mut best = 0;
+            let mut second = INF;
+            for (i, c) in children.iter().enumerate().skip(1) {
+                let k = key(self.numbers(c));
+                let best_k = key(self.numbers(&children[best]));
+                if k < best_k {
+                    second = best_k;
+                    best = i;
+                } else {
+                    second = second.min(k);
+                }
+            }
+            let (c_pn, c_dn) = self.numbers(&children[best]);
+            let (c_th_pn, c_th_dn) = if or_node {
+                (th_pn.min(second.saturating_add(1)), (th_dn - dn).saturating_add(c_dn).min(INF))
+            } else {
+                ((th_pn - pn).saturating_add(c_pn).min(INF), th_dn.min(second.saturating_add(1)))
+            };
+            self.mid(&children[best], c_th_pn, c_th_dn);
+        }
+        self.path.remove(&hash);
+    }
+
+    // Follow proven children from `pos`: an immediate win if the attacker has one,
+    // and for the defender a reply that does not lose at once, when there is one.
+    fn proof_line(&self, pos: &PosT) -> Vec<String> {
+        let wins_at_once = |p: &PosT| p.possible_moves().iter()
+            .any(|mv| p.make_move(mv).is_some_and(|c| c.is_lost()));
+        let mut line = Vec::new();
+        let mut pos = pos.clone();
+        while !pos.is_lost() && line.len() < MAX_LINE {
+            let proven = pos.possible_moves().into_iter().filter_map(|mv| {
+                let child = pos.make_move(&mv).unwrap();
+                match self.numbers(&child) {
+                    (0, dn) => Some((dn, mv, child)),
+                    _ => None,
+                }
+            });
+            let next = if self.is_or_node(&pos) {
+                proven.min_by_key(|(_, mv, child)| (!child.is_lost(), mv.clone()))
+            } else {
+                proven.max_by_key(|(dn, _, child)| (!wins_at_once(child), *dn))
+            };
+            let Some((_, mv, child)) = next else { break };
+            line.push(mv);
+            pos = child;
+        }
+        line
+    }
+}
+
+/// Plays the first move of a proven forced win when the solver finds one within
+/// its node budget, and asks `followup` otherwise.
+pub struct DfPnStrategy<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> {
+    solver: DfPnSolver<PosT>,
+    followup: F,
+}
+
+impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> DfPnStrategy<PosT, F> {
+    pub fn new(max_nodes: usize, followup: F) -> Self {
+        DfPnStrategy { solver: DfPnSolver::new(max_nodes), followup }
+    }
+}
+
+impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> StrategyEngine<PosT> for DfPnStrategy<PosT, F> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        match self.solver.solve(pos) {
+            SolveResult::Win(line) if !line.is_empty() => Some(line[0].clone()),
+            _ => self.followup.choose_move(pos),
+        }
+    }
+    /// The solver gets half of `limits.movetime`, the follow-up engine what is left
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        let start = Instant::now();
+        let solver_limits = SearchLimits { movetime: limits.movetime.map(|t| t / 2), stop: limits.stop.clone(),
+            ..Default::default() };
+        match self.solver.solve_with_limits(pos, &solver_limits) {
+            SolveResult::Win(line) if !line.is_empty() => Some(line[0].clone()),
+            _ => {
+                let left = SearchLimits { movetime: limits.movetime.map(|t| t.saturating_sub(start.elapsed())),
+                    stop: limits.stop.clone(), ..*limits };
+                self.followup.choose_move_with_limits(pos, &left)
+            }
+        }
+    }
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        self.followup.ponder(pos, limits)
+    }
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        self.followup.dump_tree(pos, depth, min_visits)
+    }
+}
+
+/// Wraps the strategies of another factory in a `DfPnStrategy`.
+pub struct DfPnFactory<F> {
+    max_nodes: usize,
+    inner: F,
+}
+
+impl<F> DfPnFactory<F> {
+    pub fn new(max_nodes: usize, inner: F) -> Self {
+        DfPnFactory { max_nodes, inner }
+    }
+}
+
+impl<PosT, F> ag::StrategyFactory<PosT> for DfPnFactory<F>
+where
+    PosT: ag::AbstractGame + Send + 'static,
+    PosT::PositionHash: Send,
+    F: ag::StrategyFactory<PosT>,
+{
+    fn create(&self, seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(DfPnStrategy::new(self.max_nodes, self.inner.create(seed)))
+    }
+    fn create_analysis(&self, seed: u64) -> Option<Box<dyn crate::analysis::AnalysisEngine<PosT> + Send>> {
+        self.inner.create_analysis(seed)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::{tests as agt, AbstractGame};
+    use crate::kids_shogi::KidsShogiGame;
+    use crate::strategy::RandomMoveStrategy;
+
+    use super::*;
+
+    #[test]
+    fn one_two_game() {
+        let mut solver = DfPnSolver::new(10000);
+        assert_eq!(solver.solve(&agt::OneTwoGame::from_str("2 0").unwrap()), SolveResult::Win(vec!["2".to_string()]));
+        match solver.solve(&agt::OneTwoGame::from_str("8 1").unwrap()) {
+            SolveResult::Win(line) => {
+                assert_eq!(line[0], "2");
+                assert_eq!(line.len() % 2, 1);
+            }
+            other => panic!("expected a win, got {:?}", other),
+        }
+        assert_eq!(solver.solve(&agt::OneTwoGame::from_str("9 0").unwrap()), SolveResult::NoWin);
+    }
+
+    #[test]
+    fn lion_capture_and_try() {
+        let mut solver = DfPnSolver::new(10000);
+        let capture = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        assert_eq!(solver.solve(&capture), SolveResult::Win(vec!["a3a4".to_string()]));
+        let try_pos = KidsShogiGame::from_fen("l2/2L/3/3 b -").unwrap();
+        assert_eq!(solver.solve(&try_pos), SolveResult::Win(vec!["c3c4".to_string()]));
+    }
+
+    #[test]
+    fn forced_win_by_drop() {
+        // G*a2 cuts off the gote lion; the proven line is G*a2 b4a4 c2c3 a4a3 a2a3
+        let pos = KidsShogiGame::from_fen("1l1/3/2L/3 b G").unwrap();
+        let mut solver = DfPnSolver::new(100000);
+        match solver.solve(&pos) {
+            SolveResult::Win(line) => {
+                let mut p = pos.clone();
+                for mv in &line {
+                    p = p.make_move(mv).expect("proof line must be legal");
+                }
+                assert_eq!(line[0], "G*a2");
+                assert!(p.is_lost() && p.current_player() == 1);
+            }
+            other => panic!("expected a win, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn budget_exhaustion_is_unknown() {
+        let mut solver = DfPnSolver::new(3);
+        assert_eq!(solver.solve(&KidsShogiGame::initial()), SolveResult::Unknown);
+        assert!(solver.nodes() <= 3);
+    }
+
+    #[test]
+    fn honours_limits() {
+        let mut strat = DfPnStrategy::new(usize::MAX, RandomMoveStrategy::new(7));
+        let pos = KidsShogiGame::initial();
+        let t0 = std::time::Instant::now();
+        let limits = SearchLimits::with_movetime(Some(std::time::Duration::from_millis(10)));
+        assert!(strat.choose_move_with_limits(&pos, &limits).is_some());
+        assert!(t0.elapsed() < std::time::Duration::from_secs(2));
+
+        let stopped = SearchLimits::default();
+        stopped.stop.store(true, Ordering::Relaxed);
+        let mut solver = DfPnSolver::new(usize::MAX);
+        assert_eq!(solver.solve_with_limits(&pos, &stopped), SolveResult::Unknown);
+    }
+
+    // A few named positions: sente at R can go to Y, whose only reply comes back to R,
+    // or to W, from which gote can only go to V, where sente wins at X; sente at Q can
+    // only go to Y. Sente at P can go to G, from which gote can only go to S or T, both
+    // leading to Y, or to D, where gote goes to R or to E, from which sente only reaches dead ends
+    #[derive(Clone)]
+    struct CycleGame(char);
+
+    impl AbstractGame for CycleGame {
+        type PositionHash = char;
+        fn possible_moves(&self) -> Vec<String> {
+            let moves: &[&str] = match self.0 { 'R' => &["Y", "W"], 'Y' => &["R"], 'W' => &["V"], 'V' => &["X"], 'Q' => &["Y"],
+                'P' => &["G", "D"], 'G' => &["S", "T"], 'S' | 'T' => &["Y"], 'D' => &["R", "E"], 'E' => &["F", "H", "I"],
+                _ => &[] };
+            moves.iter().map(|mv| mv.to_string()).collect()
+        }
+        fn make_move(&self, mv: &str) -> Option<Self> {
+            self.possible_moves().contains(&mv.to_string()).then(|| CycleGame(mv.chars().next().unwrap()))
+        }
+        fn to_str(&self) -> String { self.0.to_string() }
+        fn to_hash(&self) -> char { self.0 }
+        fn is_lost(&self) -> bool { self.0 == 'X' }
+        fn current_player(&self) -> i32 { if "RQVPSTE".contains(self.0) { 0 } else { 1 } }
+        fn pretty_print(&self) -> String { self.to_str() }
+        fn initial() -> Self { CycleGame('R') }
+        fn from_str(s: &str) -> Option<Self> { s.chars().next().map(CycleGame) }
+    }
+
+    #[test]
+    fn solves_do_not_depend_on_earlier_roots() {
+        // Solving R disproves Y through the repetition of R, which only holds on that path
+        let mut solver = DfPnSolver::new(1000);
+        let line = |moves: &[&str]| SolveResult::Win(moves.iter().map(|mv| mv.to_string()).collect());
+        assert_eq!(solver.solve(&CycleGame('R')), line(&["W", "V", "X"]));
+        let win = line(&["Y", "R", "W", "V", "X"]);
+        assert_eq!(DfPnSolver::new(1000).solve(&CycleGame('Q')), win);
+        assert_eq!(solver.solve(&CycleGame('Q')), win);
+    }
+
+    #[test]
+    fn repetitions_do_not_disprove_other_paths() {
+        // D is searched first, disproving Y through the repetition of R on P D R Y, but Y
+        // reached through G, off that path, wins
+        let line = |moves: &[&str]| SolveResult::Win(moves.iter().map(|mv| mv.to_string()).collect());
+        assert_eq!(DfPnSolver::new(1000).solve(&CycleGame('P')), line(&["G", "T", "Y", "R", "W", "V", "X"]));
+        assert_eq!(DfPnSolver::new(1000).solve(&CycleGame('D')), SolveResult::NoWin);
+    }
+
+    #[test]
+    fn strategy_plays_proven_move() {
+        let followup = RandomMoveStrategy::new(7);
+        let mut strat = DfPnStrategy::new(10000, followup);
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        assert_eq!(strat.choose_move(&pos).unwrap(), "a3a4");
+        let initial = KidsShogiGame::initial();
+        assert!(initial.possible_moves().contains(&strat.choose_move(&initial).unwrap()));
+    }
+}
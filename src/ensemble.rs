@@ -0,0 +1,253 @@
+// Ensemble of engines combined by weighted vote or score averaging
+
+use std::collections::HashMap;
+
+use crate::abstract_game::{self as ag};
+use crate::analysis::AnalysisEngine;
+use crate::strategy::{derive_seed, SearchLimits, StrategyEngine};
+
+/// How member opinions are turned into one move.
+#[derive(clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Combine {
+    /// Each member gives its weight to its best move
+    Vote,
+    /// Analysis members spread their weight over their ranked moves by score
+    /// (clamped to [-1, 1]); plain strategies still vote
+    #[default]
+    Average,
+}
+
+pub enum Member<'a, PosT: ag::AbstractGame> {
+    Strategy(Box<dyn StrategyEngine<PosT> + Send + 'a>),
+    Analysis(Box<dyn AnalysisEngine<PosT> + Send + 'a>),
+}
+
+/// How the members' opinions combined into one move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnsembleReport {
+    pub chosen: String,
+    /// Combined support per move, best first, summing to at most 1
+    pub support: Vec<(String, f64)>,
+    /// Each member's own best move
+    pub proposals: Vec<Option<String>>,
+    /// Share of the total weight whose best move is not `chosen`
+    pub disagreement: f64,
+}
+
+impl std::fmt::Display for EnsembleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let support = self.support.iter().map(|(mv, s)| format!("{} {:.2}", mv, s)).collect::<Vec<_>>();
+        let proposals = self.proposals.iter().map(|p| p.as_deref().unwrap_or("-")).collect::<Vec<_>>();
+        write!(f, "{} (disagreement {:.2}; support {}; members {})",
+            self.chosen, self.disagreement, support.join(", "), proposals.join(" "))
+    }
+}
+
+/// Asks every member and plays the move with the most combined support; ties go
+/// to the move proposed first. The time budget is split evenly between members.
+pub struct EnsembleStrategy<'a, PosT: ag::AbstractGame> {
+    members: Vec<(Member<'a, PosT>, f64)>,
+    combine: Combine,
+    num_pv: usize,
+    verbose: bool,
+}
+
+impl<'a, PosT: ag::AbstractGame> EnsembleStrategy<'a, PosT> {
+    pub fn new(combine: Combine) -> Self {
+        EnsembleStrategy { members: Vec::new(), combine, num_pv: 3, verbose: false }
+    }
+
+    pub fn add(mut self, member: Member<'a, PosT>, weight: f64) -> Self {
+        self.members.push((member, weight.max(0.0)));
+        self
+    }
+
+    /// Print the report of every move to stderr
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    // Each member's support per move (summing to 1) and its best move
+    fn opinion(member: &mut Member<'a, PosT>, combine: Combine, num_pv: usize, pos: &PosT, limits: &SearchLimits)
+        -> (Vec<(String, f64)>, Option<String>)
+    {
+        match member {
+            Member::Strategy(engine) => {
+                let mv = engine.choose_move_with_limits(pos, limits);
+                (mv.iter().map(|mv| (mv.clone(), 1.0)).collect(), mv)
+            }
+            Member::Analysis(engine) => {
+                let lines = engine.analyze(pos, num_pv, limits);
+                let best = lines.first().map(|l| l.mv.clone());
+                let support = match combine {
+                    Combine::Vote => best.iter().map(|mv| (mv.clone(), 1.0)).collect(),
+                    Combine::Average => {
+                        // Shift scores to [0, 2] so that losing moves still get a little
+                        let shifted = lines.iter().map(|l| (l.mv.clone(), l.score.clamp(-1.0, 1.0) + 1.0)).collect::<Vec<_>>();
+                        let total: f64 = shifted.iter().map(|(_, s)| s).sum();
+                        if total > 0.0 {
+                            shifted.into_iter().map(|(mv, s)| (mv, s / total)).collect()
+                        } else {
+                            best.iter().map(|mv| (mv.clone(), 1.0)).collect()
+                        }
+                    }
+                };
+                (support, best)
+            }
+        }
+    }
+
+    // Asks every member and combines their opinions; `None` only without legal moves
+    fn consult(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<EnsembleReport> {
+        let share = SearchLimits {
+            movetime: limits.movetime.map(|t| t / self.members.len().max(1) as u32),
+            ..limits.clone()
+        };
+        let total_weight: f64 = self.members.iter().map(|(_, w)| w).sum();
+        let mut support: Vec<(String, f64)> = Vec::new();
+        let mut index = HashMap::new();
+        let mut proposals = Vec::new();
+        for (member, weight) in self.members.iter_mut() {
+            let (opinion, best) = Self::opinion(member, self.combine, self.num_pv, pos, &share);
+            for (mv, s) in opinion {
+                let i = *index.entry(mv.clone()).or_insert_with(|| {
+                    support.push((mv, 0.0));
+                    support.len() - 1
+                });
+                support[i].1 += *weight * s / total_weight.max(f64::MIN_POSITIVE);
+            }
+            proposals.push(best);
+        }
+        // Stable sort keeps the first proposer's move ahead on ties
+        support.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let chosen = support.first().map(|(mv, _)| mv.clone())
+            .or_else(|| pos.possible_moves().into_iter().next())?;
+        let against: f64 = self.members.iter().zip(&proposals)
+            .filter(|(_, p)| p.as_deref() != Some(chosen.as_str()))
+            .fold(0.0, |sum, ((_, w), _)| sum + w);
+        Some(EnsembleReport {
+            chosen,
+            support,
+            proposals,
+            disagreement: against / total_weight.max(f64::MIN_POSITIVE),
+        })
+    }
+}
+
+impl<'a, PosT: ag::AbstractGame> StrategyEngine<PosT> for EnsembleStrategy<'a, PosT> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.choose_move_with_limits(pos, &SearchLimits::default())
+    }
+
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        let report = self.consult(pos, limits)?;
+        if self.verbose {
+            eprintln!("Ensemble: {}", report);
+        }
+        Some(report.chosen)
+    }
+}
+
+/// Creates an `EnsembleStrategy` from other factories: members that can analyze
+/// join as analysis engines under `Combine::Average`, the rest as strategies.
+pub struct EnsembleFactory<PosT: ag::AbstractGame + Send + 'static> {
+    members: Vec<(Box<dyn ag::StrategyFactory<PosT>>, f64)>,
+    combine: Combine,
+    verbose: bool,
+}
+
+impl<PosT: ag::AbstractGame + Send + 'static> EnsembleFactory<PosT> {
+    pub fn new(combine: Combine) -> Self {
+        EnsembleFactory { members: Vec::new(), combine, verbose: false }
+    }
+
+    /// Make the ensembles print the report of every move to stderr
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn add<F: ag::StrategyFactory<PosT> + 'static>(mut self, factory: F, weight: f64) -> Self {
+        self.members.push((Box::new(factory), weight));
+        self
+    }
+}
+
+impl<PosT: ag::AbstractGame + Send + 'static> ag::StrategyFactory<PosT> for EnsembleFactory<PosT> {
+    fn create(&self, seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        let ensemble = self.members.iter().enumerate().fold(EnsembleStrategy::new(self.combine), |ensemble, (i, (factory, weight))| {
+            let member_seed = derive_seed(seed, i as u64);
+            let analysis = if self.combine == Combine::Average { factory.create_analysis(member_seed) } else { None };
+            let member = match analysis {
+                Some(engine) => Member::Analysis(engine),
+                None => Member::Strategy(factory.create(member_seed)),
+            };
+            ensemble.add(member, *weight)
+        });
+        Box::new(ensemble.verbose(self.verbose))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::AbstractGame;
+    use crate::alphabeta::AlphaBetaStrategy;
+    use crate::kids_shogi::{KidsShogiGame, SimpleEvaluator};
+    use crate::strategy::RandomMoveStrategy;
+
+    use super::*;
+
+    // Always plays the given move
+    struct Fixed(&'static str);
+    impl StrategyEngine<KidsShogiGame> for Fixed {
+        fn choose_move(&mut self, _pos: &KidsShogiGame) -> Option<String> {
+            Some(self.0.to_string())
+        }
+    }
+
+    #[test]
+    fn weighted_vote() {
+        let pos = KidsShogiGame::initial();
+        let mut ensemble = EnsembleStrategy::new(Combine::Vote)
+            .add(Member::Strategy(Box::new(Fixed("b2b3"))), 1.0)
+            .add(Member::Strategy(Box::new(Fixed("c1c2"))), 1.5)
+            .add(Member::Strategy(Box::new(Fixed("c1c2"))), 0.5);
+        let report = ensemble.consult(&pos, &SearchLimits::default()).unwrap();
+        assert_eq!(report.chosen, "c1c2");
+        assert_eq!(report.proposals, vec![Some("b2b3".to_string()), Some("c1c2".to_string()), Some("c1c2".to_string())]);
+        assert!((report.disagreement - 1.0 / 3.0).abs() < 1e-9);
+        assert!((report.support[0].1 - 2.0 / 3.0).abs() < 1e-9);
+
+        // Ties go to the first proposal
+        let mut tied = EnsembleStrategy::new(Combine::Vote)
+            .add(Member::Strategy(Box::new(Fixed("b2b3"))), 1.0)
+            .add(Member::Strategy(Box::new(Fixed("c1c2"))), 1.0);
+        assert_eq!(tied.choose_move(&pos).unwrap(), "b2b3");
+    }
+
+    #[test]
+    fn averaging_outvotes_a_random_member() {
+        let eval = SimpleEvaluator{};
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let mut ensemble = EnsembleStrategy::new(Combine::Average)
+            .add(Member::Analysis(Box::new(AlphaBetaStrategy::new(&eval, 3, 1))), 1.0)
+            .add(Member::Strategy(Box::new(RandomMoveStrategy::new(1))), 0.5);
+        let report = ensemble.consult(&pos, &SearchLimits::default()).unwrap();
+        assert_eq!(report.chosen, "a3a4");
+        assert!(report.support.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(report.support.iter().map(|(_, s)| s).sum::<f64>() <= 1.0 + 1e-9);
+    }
+
+    #[test]
+    fn factory_creates_members() {
+        static EVAL: SimpleEvaluator = SimpleEvaluator{};
+        let factory = EnsembleFactory::new(Combine::Average)
+            .add(crate::alphabeta::AlphaBetaFactory::new(&EVAL, 2, 1), 1.0)
+            .add(crate::mcts::MctsFactory::new(&EVAL, 100, 3.0, 8), 1.0);
+        let mut strat = ag::StrategyFactory::<KidsShogiGame>::create(&factory, 1);
+        let pos = KidsShogiGame::initial();
+        assert!(pos.possible_moves().contains(&strat.choose_move(&pos).unwrap()));
+    }
+}
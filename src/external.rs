@@ -0,0 +1,243 @@
+// Engines running as another process and speaking the `--engine` line protocol
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::{SearchLimits, StrategyEngine};
+
+/// Extra time an engine gets over its movetime to start up and answer
+const GRACE: Duration = Duration::from_millis(1000);
+
+#[derive(Debug)]
+pub enum EngineError {
+    Spawn(io::Error),
+    /// No reply within the time allowed; the process is killed
+    Timeout,
+    /// The process exited or closed its output
+    Crashed,
+    /// A line that is neither a legal move, a position one move away, nor a result
+    BadReply(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Spawn(e) => write!(f, "cannot start engine: {}", e),
+            EngineError::Timeout => write!(f, "engine did not reply in time"),
+            EngineError::Crashed => write!(f, "engine exited"),
+            EngineError::BadReply(line) => write!(f, "unexpected reply from engine: {:?}", line),
+        }
+    }
+}
+
+// A running engine; lines of its output arrive through `lines` so that reads can time out
+struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl EngineProcess {
+    fn spawn(command: &[String]) -> Result<Self, EngineError> {
+        let (program, args) = command.split_first()
+            .ok_or_else(|| EngineError::Spawn(io::Error::new(io::ErrorKind::InvalidInput, "empty command")))?;
+        let mut child = Command::new(program).args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(EngineError::Spawn)?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(EngineProcess { child, stdin, lines })
+    }
+
+    fn ask(&mut self, request: &str, timeout: Duration) -> Result<String, EngineError> {
+        writeln!(self.stdin, "{}", request)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| EngineError::Crashed)?;
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(EngineError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(EngineError::Crashed),
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Turns an engine's reply to `pos` into a move. The `--engine` protocol answers
+/// with the position after the move, or with the result when the move ends the
+/// game ("1-0", "0-1") or the engine gives up ("1/2-1/2", no move); a plain
+/// move is accepted too.
+fn reply_to_move<PosT: ag::AbstractGame>(pos: &PosT, reply: &str) -> Result<Option<String>, EngineError> {
+    let reply = reply.trim();
+    let bad_reply = || EngineError::BadReply(reply.to_string());
+    if pos.make_move(reply).is_some() {
+        return Ok(Some(reply.to_string()))
+    }
+    let moves = pos.possible_moves();
+    match reply {
+        "1/2-1/2" => Ok(None),
+        "1-0" | "0-1" => {
+            let winner = if reply == "1-0" { 0 } else { 1 };
+            if winner != pos.current_player() {
+                return Err(bad_reply())
+            }
+            let winning = moves.into_iter().find(|mv| pos.make_move(mv).is_some_and(|next| next.is_lost()));
+            winning.map(Some).ok_or_else(bad_reply)
+        }
+        _ => {
+            let target = PosT::from_str(reply).ok_or_else(bad_reply)?.to_hash();
+            let found = moves.into_iter().find(|mv| pos.make_move(mv).is_some_and(|next| next.to_hash() == target));
+            found.map(Some).ok_or_else(bad_reply)
+        }
+    }
+}
+
+/// Plays the moves of an engine in a subprocess, e.g. another build of this
+/// binary run with `--engine`. The process starts on the first move and is
+/// restarted once if it turns out to have exited; a move that times out or
+/// cannot be understood is reported on stderr and returns `None`.
+pub struct ExternalEngineStrategy {
+    command: Vec<String>,
+    timeout: Duration,
+    process: Option<EngineProcess>,
+}
+
+impl ExternalEngineStrategy {
+    /// `command` is the program followed by its arguments; `timeout` bounds
+    /// moves that have no movetime.
+    pub fn new(command: Vec<String>, timeout: Duration) -> Self {
+        ExternalEngineStrategy { command, timeout, process: None }
+    }
+
+    fn request<PosT: ag::AbstractGame>(&mut self, pos: &PosT, limits: &SearchLimits) -> Result<Option<String>, EngineError> {
+        let (request, timeout) = match limits.movetime {
+            Some(movetime) => (format!("{} movetime {}", pos.to_str(), movetime.as_millis()), movetime + GRACE),
+            None => (pos.to_str(), self.timeout),
+        };
+        // A process left over from an earlier game may have exited by now
+        for attempt in 0..2 {
+            if self.process.is_none() {
+                self.process = Some(EngineProcess::spawn(&self.command)?);
+            }
+            match self.process.as_mut().unwrap().ask(&request, timeout) {
+                Ok(reply) => {
+                    // The `--engine` loop exits once it reports a result
+                    if ["1-0", "0-1", "1/2-1/2"].contains(&reply.trim()) {
+                        self.process = None;
+                    }
+                    return reply_to_move(pos, &reply)
+                }
+                Err(EngineError::Crashed) if attempt == 0 => self.process = None,
+                Err(e) => {
+                    self.process = None;
+                    return Err(e)
+                }
+            }
+        }
+        Err(EngineError::Crashed)
+    }
+}
+
+impl<PosT: ag::AbstractGame> StrategyEngine<PosT> for ExternalEngineStrategy {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.choose_move_with_limits(pos, &SearchLimits::default())
+    }
+
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        self.request(pos, limits).unwrap_or_else(|e| {
+            eprintln!("External engine {:?}: {}", self.command.join(" "), e);
+            None
+        })
+    }
+}
+
+/// Starts a separate engine process for every game.
+pub struct ExternalEngineFactory {
+    command: Vec<String>,
+    timeout: Duration,
+}
+
+impl ExternalEngineFactory {
+    pub fn new(command: Vec<String>, timeout: Duration) -> Self {
+        ExternalEngineFactory { command, timeout }
+    }
+}
+
+impl<PosT: ag::AbstractGame + Send + 'static> ag::StrategyFactory<PosT> for ExternalEngineFactory {
+    fn create(&self, _seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(ExternalEngineStrategy::new(self.command.clone(), self.timeout))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::abstract_game::AbstractGame;
+    use crate::kids_shogi::KidsShogiGame;
+
+    // An engine written as a shell loop
+    fn shell(script: &str) -> ExternalEngineStrategy {
+        let command = ["sh", "-c", script].map(String::from).to_vec();
+        ExternalEngineStrategy::new(command, Duration::from_secs(5))
+    }
+
+    #[test]
+    fn understands_positions_moves_and_results() {
+        let pos = KidsShogiGame::initial();
+        let mut by_position = shell(r#"while read line; do echo "gle/1C1/3/ELG w C"; done"#);
+        assert_eq!(by_position.choose_move(&pos), Some("b2b3".to_string()));
+        let mut by_move = shell("while read line; do echo c1c2; done");
+        assert_eq!(by_move.choose_move(&pos), Some("c1c2".to_string()));
+
+        let won = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let mut by_result = shell("while read line; do echo 1-0; done");
+        assert_eq!(by_result.choose_move(&won), Some("a3a4".to_string()));
+        let mut gives_up = shell("read line; echo 1/2-1/2");
+        assert_eq!(gives_up.choose_move(&pos), None);
+        assert_eq!(reply_to_move(&pos, "nonsense").unwrap_err().to_string(), r#"unexpected reply from engine: "nonsense""#);
+    }
+
+    #[test]
+    fn restarts_after_a_crash() {
+        // Answers one request per process, like an engine that exits after each game
+        let mut engine = shell("read line; echo b2b3");
+        let pos = KidsShogiGame::initial();
+        assert_eq!(engine.choose_move(&pos), Some("b2b3".to_string()));
+        assert_eq!(engine.choose_move(&pos), Some("b2b3".to_string()));
+        let mut broken = shell("exit 1");
+        assert_eq!(broken.choose_move(&pos), None);
+        let mut missing = ExternalEngineStrategy::new(vec!["/nonexistent/engine".to_string()], Duration::from_secs(1));
+        assert!(matches!(missing.request(&pos, &SearchLimits::default()), Err(EngineError::Spawn(_))));
+    }
+
+    #[test]
+    fn times_out() {
+        let mut slow = shell("while read line; do sleep 5; echo b2b3; done");
+        let limits = SearchLimits::with_movetime(Some(Duration::from_millis(10)));
+        let t0 = std::time::Instant::now();
+        assert!(matches!(slow.request(&KidsShogiGame::initial(), &limits), Err(EngineError::Timeout)));
+        assert!(t0.elapsed() < Duration::from_secs(3));
+        assert!(slow.process.is_none());
+    }
+}
@@ -0,0 +1,89 @@
+// Registry of the games shipped with the crate, selectable at runtime
+
+use crate::abstract_game::{self as ag};
+use crate::hint;
+use crate::kids_shogi;
+use crate::neuro;
+use crate::tune;
+
+/// Runtime identifier of a game type, used by `--game` and by the `game`
+/// field of the `start_game` RPC.
+#[derive(clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GameKind {
+    #[default]
+    #[value(name = "kids_shogi")]
+    KidsShogi,
+}
+
+impl GameKind {
+    pub const ALL: &'static [GameKind] = &[GameKind::KidsShogi];
+
+    /// Run `visitor` with the concrete position type for this game.
+    pub fn dispatch<V: GameVisitor>(self, visitor: V) -> V::Output {
+        match self {
+            GameKind::KidsShogi => visitor.visit::<kids_shogi::KidsShogiGame>(),
+        }
+    }
+}
+
+/// Everything the binary needs to know about a game to play, serve and train it.
+/// Position hashes must widen to `u64` to key opening books.
+pub trait RegisteredGame: ag::NeuroPosition<PositionHash: Into<u64> + Send> + ag::SparsePosition + hint::Explain + Send + Sync + 'static {
+    const KIND: GameKind;
+    /// Evaluator used when no model file is given
+    type DefaultEval: ag::Evaluator<Self> + Default + Send + Sync + 'static;
+    /// Neural network evaluator sized for this game's encoding
+    type NeuroEval: neuro::NeuroModel<Self> + Send + Sync + 'static;
+    /// Evaluator with weights loaded from a file by `--weights` and fitted by `--tune`
+    type FeatureEval: tune::Tunable<Self> + Default + Send + Sync + 'static;
+    fn load_feature_eval(path: &str) -> std::io::Result<Self::FeatureEval>;
+    fn save_feature_eval(eval: &Self::FeatureEval, path: &str) -> std::io::Result<()>;
+}
+
+/// Generic callback for `GameKind::dispatch`; closures cannot be generic over
+/// the position type, so the work is expressed as a trait method instead.
+pub trait GameVisitor {
+    type Output;
+    fn visit<G: RegisteredGame>(self) -> Self::Output;
+}
+
+impl RegisteredGame for kids_shogi::KidsShogiGame {
+    const KIND: GameKind = GameKind::KidsShogi;
+    type DefaultEval = kids_shogi::SimpleEvaluator;
+    type NeuroEval = neuro::NeuroEvaluator<Self, { <Self as ag::NeuroPosition>::ENCODE_LENGTH }>;
+    type FeatureEval = kids_shogi::FeatureEvaluator;
+    fn load_feature_eval(path: &str) -> std::io::Result<Self::FeatureEval> {
+        kids_shogi::FeatureEvaluator::load(path)
+    }
+    fn save_feature_eval(eval: &Self::FeatureEval, path: &str) -> std::io::Result<()> {
+        eval.save(path)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    struct InitialFen;
+    impl GameVisitor for InitialFen {
+        type Output = String;
+        fn visit<G: RegisteredGame>(self) -> String {
+            G::initial().to_str()
+        }
+    }
+
+    #[test]
+    fn dispatch_kids_shogi() {
+        assert_eq!(GameKind::KidsShogi.dispatch(InitialFen), "gle/1c1/1C1/ELG b -");
+        assert_eq!(<kids_shogi::KidsShogiGame as RegisteredGame>::KIND, GameKind::KidsShogi);
+    }
+
+    #[test]
+    fn game_kind_names() {
+        let kind: GameKind = serde_json::from_str(r#""kids_shogi""#).unwrap();
+        assert_eq!(kind, GameKind::KidsShogi);
+        assert_eq!(<GameKind as clap::ValueEnum>::from_str("kids_shogi", false).unwrap(), GameKind::KidsShogi);
+        assert!(GameKind::ALL.contains(&GameKind::default()));
+    }
+}
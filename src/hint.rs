@@ -0,0 +1,151 @@
+// Hints for learners: a suggested move and the reasons it is good
+
+use std::fmt;
+
+use crate::abstract_game::{self as ag};
+use crate::analysis::AnalysisEngine;
+use crate::dfpn::{DfPnSolver, SolveResult};
+use crate::strategy::SearchLimits;
+
+/// Why a move is worth playing. Pieces are named in lower case ("giraffe").
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Reason {
+    /// The game is over after this move
+    WinsNow,
+    /// Whatever the opponent does, the game is won within `moves` own moves
+    ForcedWin { moves: usize },
+    Captures { piece: String },
+    Promotes { piece: String },
+    /// The opponent's lion is attacked after the move
+    ThreatensLion,
+    /// The own lion may safely step onto the last rank next move
+    ThreatensTry,
+    /// The opponent's lion could safely reach its last rank before the move, and cannot after it
+    PreventsTry,
+    /// An attacked piece with no defender gets one
+    Defends { piece: String },
+    /// The moving piece was attacked and goes to a square that is not
+    Escapes { piece: String },
+    /// Nothing specific: the engine just scores it highest
+    EngineChoice,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::WinsNow => write!(f, "wins the game right away"),
+            Reason::ForcedWin { moves } => write!(f, "leads to a forced win in {} move{}", moves, if *moves == 1 { "" } else { "s" }),
+            Reason::Captures { piece } => write!(f, "captures the {}", piece),
+            Reason::Promotes { piece } => write!(f, "promotes the {}", piece),
+            Reason::ThreatensLion => write!(f, "threatens the lion"),
+            Reason::ThreatensTry => write!(f, "threatens to walk the lion to the last row"),
+            Reason::PreventsTry => write!(f, "stops the opponent's lion from reaching the last row"),
+            Reason::Defends { piece } => write!(f, "defends the hanging {}", piece),
+            Reason::Escapes { piece } => write!(f, "takes the {} out of danger", piece),
+            Reason::EngineChoice => write!(f, "the engine rates it highest"),
+        }
+    }
+}
+
+/// Positions that can say what a move does.
+pub trait Explain: ag::AbstractGame {
+    /// Game-specific reasons for playing the legal move `mv` here, besides winning
+    fn move_reasons(&self, mv: &str) -> Vec<Reason>;
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Hint {
+    #[serde(rename = "move")]
+    pub mv: String,
+    /// Most important first, never empty
+    pub reasons: Vec<Reason>,
+    /// Engine score from the side to move's point of view; absent for proven wins
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub score: Option<f64>,
+    /// Expected line, starting with `mv`
+    pub pv: Vec<String>,
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reasons = self.reasons.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        write!(f, "{}: {}", self.mv, reasons.join(", "))
+    }
+}
+
+/// Reasons for `mv` in `pos`; `forced_win` is the length of a proof line that
+/// starts with `mv`, if one is known.
+pub fn explain<PosT: Explain>(pos: &PosT, mv: &str, forced_win: Option<usize>) -> Vec<Reason> {
+    let mut reasons = Vec::new();
+    if pos.make_move(mv).is_some_and(|next| next.is_lost()) {
+        reasons.push(Reason::WinsNow);
+    } else if let Some(plies) = forced_win {
+        reasons.push(Reason::ForcedWin { moves: plies.div_ceil(2) });
+    }
+    reasons.extend(pos.move_reasons(mv));
+    if reasons.is_empty() {
+        reasons.push(Reason::EngineChoice);
+    }
+    reasons
+}
+
+/// Suggest a move for the side to move: a forced win if df-pn finds one within
+/// `mate_nodes`, otherwise `engine`'s best move. `None` if the game is over.
+pub fn hint<PosT: Explain, A: AnalysisEngine<PosT> + ?Sized>(
+    pos: &PosT, engine: &mut A, limits: &SearchLimits, mate_nodes: usize,
+) -> Option<Hint> {
+    if pos.is_lost() {
+        return None
+    }
+    if let SolveResult::Win(line) = DfPnSolver::new(mate_nodes).solve(pos) {
+        let mv = line.first()?.clone();
+        return Some(Hint { reasons: explain(pos, &mv, Some(line.len())), mv, score: None, pv: line })
+    }
+    let best = engine.analyze(pos, 1, limits).into_iter().next()?;
+    Some(Hint { reasons: explain(pos, &best.mv, None), mv: best.mv, score: Some(best.score), pv: best.pv })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::AbstractGame;
+    use crate::alphabeta::AlphaBetaStrategy;
+    use crate::kids_shogi::{KidsShogiGame, SimpleEvaluator};
+
+    use super::*;
+
+    #[test]
+    fn hint_takes_the_lion() {
+        let eval = SimpleEvaluator{};
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let hint = hint(&pos, &mut AlphaBetaStrategy::new(&eval, 3, 1), &SearchLimits::default(), 10000).unwrap();
+        assert_eq!(hint.mv, "a3a4");
+        assert_eq!(hint.reasons[0], Reason::WinsNow);
+        assert_eq!(hint.to_string(), "a3a4: wins the game right away, captures the lion");
+    }
+
+    #[test]
+    fn hint_from_engine_has_reasons() {
+        let eval = SimpleEvaluator{};
+        let pos = KidsShogiGame::initial();
+        let hint = hint(&pos, &mut AlphaBetaStrategy::new(&eval, 3, 1), &SearchLimits::default(), 1000).unwrap();
+        assert!(pos.possible_moves().contains(&hint.mv));
+        assert!(!hint.reasons.is_empty());
+        assert!(hint.score.is_some());
+        assert!(hint.pv.starts_with(std::slice::from_ref(&hint.mv)));
+        assert!(super::hint(&KidsShogiGame::from_fen("3/1L1/3/3 w L").unwrap(),
+            &mut AlphaBetaStrategy::new(&eval, 3, 1), &SearchLimits::default(), 1000).is_none());
+    }
+
+    #[test]
+    fn reasons_serialize_with_kind() {
+        let json = serde_json::to_string(&Reason::Captures { piece: "giraffe".to_string() }).unwrap();
+        assert_eq!(json, r#"{"kind":"captures","piece":"giraffe"}"#);
+        assert_eq!(explain(&KidsShogiGame::initial(), "b1a2", None), vec![Reason::EngineChoice]);
+        assert_eq!(explain(&KidsShogiGame::initial(), "b2b3", Some(3)), vec![
+            Reason::ForcedWin { moves: 2 },
+            Reason::Captures { piece: "chick".to_string() },
+            Reason::ThreatensLion,
+        ]);
+    }
+}
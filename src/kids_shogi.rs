@@ -6,6 +6,10 @@ use arrayvec::ArrayVec;
 
 use super::abstract_game as ag;
 
+mod features;
+mod hints;
+pub use features::FeatureEvaluator;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Point(pub usize, pub usize);
 
@@ -107,6 +111,16 @@ impl PieceKind {
         }
     }
 
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            PieceKind::Chicken => "chick",
+            PieceKind::Elephant => "elephant",
+            PieceKind::Giraffe => "giraffe",
+            PieceKind::Hen => "hen",
+            PieceKind::Lion => "lion",
+        }
+    }
+
     fn to_fen_char(self: &Self) -> char {
         match self {
             PieceKind::Chicken => 'c',
@@ -367,14 +381,17 @@ impl KidsShogiGame {
                         .filter(|&p| our_pieces_loc.get(&p).is_none())
                         .map(move |p| Move::Step(point, p)))
             .collect::<Vec<Move>>();
-        let uniq_drops = self.sente_hand.iter().collect::<HashSet<_>>();
+        // Sorted rather than hashed, so that the order of moves is the same in every run
+        let mut uniq_drops = self.sente_hand.clone();
+        uniq_drops.sort();
+        uniq_drops.dedup();
         let empty_loc = self.cells.iter().enumerate().filter_map(
             |(xy, &cell)| match cell {
                 Cell::Empty => Some(KidsShogiGame::c_to_p(xy)),
                 _ => None
             }).collect::<Vec<_>>();
         let drops = uniq_drops.into_iter()
-            .flat_map(|&pk| empty_loc.iter()
+            .flat_map(|pk| empty_loc.iter()
                 .map(move |&p| Move::Drop(pk, p)))
             .filter(|mv|
                 match mv {
@@ -523,6 +540,14 @@ impl ag::AbstractGame for KidsShogiGame {
     fn is_lost(self: &Self) -> bool {
         (*self).is_lost()
     }
+    fn is_capture(self: &Self, mvstr: &str) -> bool {
+        match Move::from_fen(mvstr) {
+            Some(Move::Step(_, to)) => matches!(
+                self.cells[KidsShogiGame::p_to_c(&to)],
+                Cell::Piece(_, c) if c != self.current_player),
+            _ => false,
+        }
+    }
     fn current_player(self: &Self) -> i32 {
         match self.current_player {
             Color::Sente => 0,
@@ -615,8 +640,87 @@ impl ag::NeuroPosition for KidsShogiGame {
         KidsShogiGame::CELL_COUNT*PieceKind::COUNT*2 + PieceKind::IN_HAND_COUNT*2*2 + 2;
 }
 
+impl KidsShogiGame {
+    const SPARSE_BOARD_FEATURES: usize = KidsShogiGame::CELL_COUNT*PieceKind::COUNT*2;
+
+    fn color_of(player: i32) -> Color {
+        if player == 0 { Color::Sente } else { Color::Gote }
+    }
+
+    fn hand(self: &Self, color: Color) -> &[PieceKind] {
+        match color {
+            Color::Sente => &self.sente_hand,
+            Color::Gote => &self.gote_hand,
+        }
+    }
+
+    // (square, piece, own/opponent's), with squares counted from `view`'s side
+    fn board_feature(pt: &Point, pk: PieceKind, owner: Color, view: Color) -> usize {
+        let pt = if view == Color::Sente { *pt } else { pt.swap_sides() };
+        let side = if owner == view { 0 } else { 1 };
+        (KidsShogiGame::p_to_c(&pt)*PieceKind::COUNT + pk.index())*2 + side
+    }
+
+    // "At least n+1 pieces of this kind in own/opponent's hand", as in encode()
+    fn hand_feature(pk: PieceKind, n: usize, owner: Color, view: Color) -> usize {
+        let side = if owner == view { 0 } else { 1 };
+        KidsShogiGame::SPARSE_BOARD_FEATURES + (side*PieceKind::IN_HAND_COUNT + pk.index())*2 + n
+    }
+}
+
+impl ag::SparsePosition for KidsShogiGame {
+    const FEATURE_COUNT: usize = KidsShogiGame::SPARSE_BOARD_FEATURES + PieceKind::IN_HAND_COUNT*2*2;
+
+    fn active_features(self: &Self, player: i32) -> Vec<usize> {
+        let view = KidsShogiGame::color_of(player);
+        let mut features = Vec::new();
+        for color in [Color::Sente, Color::Gote] {
+            for (pt, pk) in self.find_all_pieces(color) {
+                features.push(KidsShogiGame::board_feature(&pt, pk, color, view));
+            }
+            for &pk in PieceKind::IN_HAND {
+                let count = self.hand(color).iter().filter(|&&x| x == pk).count();
+                features.extend((0..count).map(|n| KidsShogiGame::hand_feature(pk, n, color, view)));
+            }
+        }
+        features
+    }
+
+    fn feature_changes(self: &Self, mvstr: &str, player: i32) -> Option<(Vec<usize>, Vec<usize>)> {
+        let mv = Move::from_fen(mvstr)?;
+        let next = self.make_move_impl(&mv)?;
+        let view = KidsShogiGame::color_of(player);
+        let me = self.current_player;
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        match mv {
+            Move::Step(from, to) => {
+                let Cell::Piece(pk, _) = self.cells[KidsShogiGame::p_to_c(&from)] else { return None };
+                let Cell::Piece(landed, _) = next.cells[KidsShogiGame::p_to_c(&to)] else { return None };
+                removed.push(KidsShogiGame::board_feature(&from, pk, me, view));
+                added.push(KidsShogiGame::board_feature(&to, landed, me, view));
+                if let Cell::Piece(qk, owner) = self.cells[KidsShogiGame::p_to_c(&to)] {
+                    removed.push(KidsShogiGame::board_feature(&to, qk, owner, view));
+                    // A captured lion ends the game and has no hand feature
+                    if qk != PieceKind::Lion {
+                        let count = self.hand(me).iter().filter(|&&x| x == qk.demote()).count();
+                        added.push(KidsShogiGame::hand_feature(qk.demote(), count, me, view));
+                    }
+                }
+            }
+            Move::Drop(pk, to) => {
+                let count = self.hand(me).iter().filter(|&&x| x == pk).count();
+                removed.push(KidsShogiGame::hand_feature(pk, count - 1, me, view));
+                added.push(KidsShogiGame::board_feature(&to, pk, me, view));
+            }
+        }
+        Some((removed, added))
+    }
+}
+
 // Simple evaluator counts the values of pieces on board and in hand 
 // c=1, g=e=3, h=5
+#[derive(Default)]
 pub struct SimpleEvaluator {}
 
 impl ag::Evaluator<KidsShogiGame> for SimpleEvaluator {
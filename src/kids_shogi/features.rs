@@ -0,0 +1,275 @@
+// Linear evaluator over named positional features, with weights read from JSON
+
+use std::collections::BTreeMap;
+use std::io;
+
+use super::*;
+
+/// Feature names, in the order of `features()` and `FeatureEvaluator::weights`.
+/// Every feature is the side to move's value minus the opponent's.
+pub const FEATURES: [&str; FEATURE_COUNT] = [
+    "board_chick",      // chicks on the board
+    "board_elephant",
+    "board_giraffe",
+    "board_hen",
+    "hand_chick",       // pieces in hand
+    "hand_elephant",
+    "hand_giraffe",
+    "lion_mobility",    // lion steps to squares that are free of own pieces and not attacked
+    "lion_rank",        // lion's rank counted from its own side, 0..=3
+    "lion_try",         // 1 if the lion can safely step onto the last rank
+    "lion_attacked",    // 1 if the opponent attacks the lion
+    "attacked",         // other pieces attacked by the opponent
+    "defended",         // other pieces defended by an own piece
+    "chick_advance",    // sum of chick ranks counted from their own side
+    "drop_squares",     // drops onto empty squares the opponent does not attack
+];
+pub const FEATURE_COUNT: usize = 15;
+
+/// Non-terminal scores are kept strictly inside ±saturation, which is reserved
+/// for positions that are already decided.
+const MAX_STATIC_FRACTION: f64 = 0.95;
+
+// Number of pieces of each color attacking every cell
+pub(super) fn attack_counts(pos: &KidsShogiGame) -> [[u8; KidsShogiGame::CELL_COUNT]; 2] {
+    let mut counts = [[0; KidsShogiGame::CELL_COUNT]; 2];
+    for color in [Color::Sente, Color::Gote] {
+        for (pt, pk) in pos.find_all_pieces(color) {
+            let targets = match color {
+                Color::Sente => pk.list_moves(&pt),
+                Color::Gote => pk.list_moves(&pt.swap_sides()).into_iter().map(|p| p.swap_sides()).collect(),
+            };
+            for target in targets {
+                counts[color.index()][KidsShogiGame::p_to_c(&target)] += 1;
+            }
+        }
+    }
+    counts
+}
+
+// Rank of a point counted from `color`'s own side
+fn relative_rank(pt: &Point, color: Color) -> usize {
+    match color {
+        Color::Sente => pt.1,
+        Color::Gote => 3 - pt.1,
+    }
+}
+
+pub(super) fn side_features(pos: &KidsShogiGame, color: Color, attacks: &[[u8; KidsShogiGame::CELL_COUNT]; 2]) -> [f64; FEATURE_COUNT] {
+    let mut f = [0.0; FEATURE_COUNT];
+    let own_attacks = &attacks[color.index()];
+    let opp_attacks = &attacks[color.opponent().index()];
+    let is_own = |pt: &Point| matches!(pos.cells[KidsShogiGame::p_to_c(pt)], Cell::Piece(_, c) if c == color);
+    for (pt, pk) in pos.find_all_pieces(color) {
+        let xy = KidsShogiGame::p_to_c(&pt);
+        match pk {
+            PieceKind::Lion => {
+                let steps = match color {
+                    Color::Sente => pk.list_moves(&pt),
+                    Color::Gote => pk.list_moves(&pt.swap_sides()).into_iter().map(|p| p.swap_sides()).collect(),
+                };
+                let safe = steps.into_iter()
+                    .filter(|p| !is_own(p) && opp_attacks[KidsShogiGame::p_to_c(p)] == 0)
+                    .collect::<Vec<_>>();
+                f[7] = safe.len() as f64;
+                f[8] = relative_rank(&pt, color) as f64;
+                f[9] = if safe.iter().any(|p| relative_rank(p, color) == 3) { 1.0 } else { 0.0 };
+                f[10] = if opp_attacks[xy] > 0 { 1.0 } else { 0.0 };
+                continue;
+            }
+            PieceKind::Chicken => {
+                f[0] += 1.0;
+                f[13] += relative_rank(&pt, color) as f64;
+            }
+            PieceKind::Elephant => f[1] += 1.0,
+            PieceKind::Giraffe => f[2] += 1.0,
+            PieceKind::Hen => f[3] += 1.0,
+        }
+        if opp_attacks[xy] > 0 { f[11] += 1.0 }
+        if own_attacks[xy] > 0 { f[12] += 1.0 }
+    }
+    let hand = match color {
+        Color::Sente => &pos.sente_hand,
+        Color::Gote => &pos.gote_hand,
+    };
+    for pk in hand {
+        match pk {
+            PieceKind::Chicken => f[4] += 1.0,
+            PieceKind::Elephant => f[5] += 1.0,
+            PieceKind::Giraffe => f[6] += 1.0,
+            _ => {}
+        }
+    }
+    let kinds_in_hand = hand.iter().collect::<HashSet<_>>().len();
+    let safe_empty = pos.cells.iter().enumerate()
+        .filter(|&(xy, cell)| *cell == Cell::Empty && opp_attacks[xy] == 0)
+        .count();
+    f[14] = (kinds_in_hand * safe_empty) as f64;
+    f
+}
+
+/// Feature values of `pos` from the side to move's point of view.
+pub fn features(pos: &KidsShogiGame) -> [f64; FEATURE_COUNT] {
+    let attacks = attack_counts(pos);
+    let ours = side_features(pos, pos.current_player, &attacks);
+    let theirs = side_features(pos, pos.current_player.opponent(), &attacks);
+    std::array::from_fn(|i| ours[i] - theirs[i])
+}
+
+// On-disk form: saturation plus weights by feature name
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WeightsFile {
+    saturation: f64,
+    weights: BTreeMap<String, f64>,
+}
+
+/// Weighted sum of `features()`. A lost position scores -saturation; anything
+/// else stays strictly inside ±saturation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureEvaluator {
+    pub weights: [f64; FEATURE_COUNT],
+    pub saturation: f64,
+}
+
+impl Default for FeatureEvaluator {
+    // Material as in SimpleEvaluator (halved piece values) plus small positional terms
+    fn default() -> Self {
+        FeatureEvaluator {
+            weights: [
+                0.5, 1.5, 1.5, 2.5,     // board
+                0.5, 1.5, 1.5,          // hand
+                0.1, 0.2, 1.0, -2.0,    // lion
+                -0.3, 0.2, 0.2, 0.02,   // attacked, defended, chick_advance, drop_squares
+            ],
+            saturation: 20.0,
+        }
+    }
+}
+
+impl FeatureEvaluator {
+    /// Weighted sum of the features, without the terminal check or clamping
+    pub fn raw_score(&self, features: &[f64; FEATURE_COUNT]) -> f64 {
+        self.weights.iter().zip(features).fold(0.0, |sum, (w, f)| sum + w * f)
+    }
+
+    /// Parse weights from JSON: `{"saturation": 20.0, "weights": {"board_chick": 0.5, ...}}`.
+    /// Features missing from `weights` keep their default weight.
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let file: WeightsFile = serde_json::from_str(json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if file.saturation.is_nan() || file.saturation <= 0.0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "saturation must be positive"))
+        }
+        let mut eval = FeatureEvaluator { saturation: file.saturation, ..FeatureEvaluator::default() };
+        for (name, weight) in file.weights {
+            let Some(i) = FEATURES.iter().position(|&f| f == name) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown feature {}", name)))
+            };
+            eval.weights[i] = weight;
+        }
+        Ok(eval)
+    }
+
+    pub fn to_json(&self) -> String {
+        let file = WeightsFile {
+            saturation: self.saturation,
+            weights: FEATURES.iter().map(|f| f.to_string()).zip(self.weights).collect(),
+        };
+        serde_json::to_string_pretty(&file).unwrap()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+}
+
+impl ag::Evaluator<KidsShogiGame> for FeatureEvaluator {
+    fn saturation(&self) -> f64 {
+        self.saturation
+    }
+    fn evaluate_position(&self, pos: &KidsShogiGame) -> f64 {
+        if pos.is_lost() {
+            return -self.saturation
+        }
+        let limit = self.saturation * MAX_STATIC_FRACTION;
+        self.raw_score(&features(pos)).clamp(-limit, limit)
+    }
+}
+
+impl crate::tune::Tunable<KidsShogiGame> for FeatureEvaluator {
+    fn param_names(&self) -> Vec<String> {
+        FEATURES.iter().map(|f| f.to_string()).collect()
+    }
+    fn params(&self) -> Vec<f64> {
+        self.weights.to_vec()
+    }
+    fn set_params(&mut self, params: &[f64]) {
+        self.weights.copy_from_slice(params);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::{AbstractGame, Evaluator};
+
+    use super::*;
+
+    fn feature(pos: &KidsShogiGame, name: &str) -> f64 {
+        features(pos)[FEATURES.iter().position(|&f| f == name).unwrap()]
+    }
+
+    #[test]
+    fn material_only_matches_simple_evaluator() {
+        let mut eval = FeatureEvaluator::default();
+        eval.weights[7..].iter_mut().for_each(|w| *w = 0.0);
+        let simple = SimpleEvaluator::default();
+        for fen in ["gle/1c1/1C1/ELG b -", "gl1/1c1/1C1/ELG w E", "1l1/3/1h1/1L1 b GCe"] {
+            let pos = KidsShogiGame::from_fen(fen).unwrap();
+            assert_eq!(eval.evaluate_position(&pos), simple.evaluate_position(&pos), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn symmetric_and_saturated() {
+        let eval = FeatureEvaluator::default();
+        let mut pos = KidsShogiGame::initial();
+        assert_eq!(eval.evaluate_position(&pos), 0.0);
+        for mv in ["b2b3", "c4b3", "a1b2"] {
+            pos = pos.make_move(mv).unwrap();
+            assert_eq!(eval.evaluate_position(&pos), eval.evaluate_position(&pos.swap_sides()));
+            assert!(eval.evaluate_position(&pos).abs() < eval.saturation());
+        }
+        // Sente's lion has captured Gote's
+        let lost = KidsShogiGame::from_fen("3/1L1/3/3 w L").unwrap();
+        assert_eq!(eval.evaluate_position(&lost), -eval.saturation());
+    }
+
+    #[test]
+    fn lion_features() {
+        // Sente's lion on c3 may step onto c4, which Gote's lion on a4 does not cover
+        let pos = KidsShogiGame::from_fen("l2/2L/3/3 b -").unwrap();
+        assert_eq!(feature(&pos, "lion_try"), 1.0);
+        assert_eq!(feature(&pos, "lion_rank"), 2.0);
+        // Gote's giraffe attacks the sente lion, which attacks it back
+        let pos = KidsShogiGame::from_fen("3/3/g2/L1l b -").unwrap();
+        assert_eq!(feature(&pos, "lion_attacked"), 1.0);
+        assert_eq!(feature(&pos, "attacked"), -1.0);
+    }
+
+    #[test]
+    fn weights_json_round_trip() {
+        let mut eval = FeatureEvaluator::default();
+        eval.weights[9] = 3.5;
+        eval.saturation = 30.0;
+        assert_eq!(FeatureEvaluator::from_json(&eval.to_json()).unwrap(), eval);
+        let partial = FeatureEvaluator::from_json(r#"{"saturation": 10.0, "weights": {"lion_try": 2.0}}"#).unwrap();
+        assert_eq!(partial.weights[9], 2.0);
+        assert_eq!(partial.weights[0], FeatureEvaluator::default().weights[0]);
+        assert!(FeatureEvaluator::from_json(r#"{"saturation": 10.0, "weights": {"queen": 9.0}}"#).is_err());
+        assert!(FeatureEvaluator::from_json(r#"{"saturation": 0.0, "weights": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+// What a move does, for the learner-facing hints in crate::hint
+
+use super::*;
+use super::features::{attack_counts, side_features};
+use crate::hint::{Explain, Reason};
+
+// Index of "lion_try" in FEATURES
+const LION_TRY: usize = 9;
+
+impl KidsShogiGame {
+    fn piece_at(&self, pt: &Point) -> Option<(PieceKind, Color)> {
+        match self.cells[KidsShogiGame::p_to_c(pt)] {
+            Cell::Piece(pk, c) => Some((pk, c)),
+            Cell::Empty => None,
+        }
+    }
+
+    fn can_try(&self, color: Color) -> bool {
+        side_features(self, color, &attack_counts(self))[LION_TRY] > 0.0
+    }
+}
+
+impl Explain for KidsShogiGame {
+    fn move_reasons(&self, mv: &str) -> Vec<Reason> {
+        let (Some(parsed), Some(next)) = (Move::from_fen(mv), ag::AbstractGame::make_move(self, mv)) else {
+            return Vec::new()
+        };
+        let me = self.current_player;
+        let opp = me.opponent();
+        let attacks = attack_counts(self);
+        let next_attacks = attack_counts(&next);
+        let mut reasons = Vec::new();
+
+        if let Move::Step(from, to) = &parsed {
+            if let Some((pk, _)) = self.piece_at(to) {
+                reasons.push(Reason::Captures { piece: pk.name().to_string() });
+            }
+            let (moved, _) = self.piece_at(from).expect("a step starts on a piece");
+            if moved == PieceKind::Chicken && next.piece_at(to).is_some_and(|(pk, _)| pk == PieceKind::Hen) {
+                reasons.push(Reason::Promotes { piece: moved.name().to_string() });
+            }
+        }
+        let opp_lion = next.find_all_pieces(opp).into_iter().find(|&(_, pk)| pk == PieceKind::Lion);
+        if opp_lion.is_some_and(|(pt, _)| next_attacks[me.index()][KidsShogiGame::p_to_c(&pt)] > 0) {
+            reasons.push(Reason::ThreatensLion);
+        }
+        if self.can_try(opp) && !next.can_try(opp) {
+            reasons.push(Reason::PreventsTry);
+        }
+        if !self.can_try(me) && next.can_try(me) {
+            reasons.push(Reason::ThreatensTry);
+        }
+        // Pieces that stay where they are and were attacked without a defender
+        for (pt, pk) in self.find_all_pieces(me) {
+            let xy = KidsShogiGame::p_to_c(&pt);
+            let hanging = pk != PieceKind::Lion && attacks[opp.index()][xy] > 0 && attacks[me.index()][xy] == 0;
+            if hanging && next.piece_at(&pt) == Some((pk, me)) && next_attacks[me.index()][xy] > 0 {
+                reasons.push(Reason::Defends { piece: pk.name().to_string() });
+            }
+        }
+        // A capture already says all there is about where the piece goes
+        if let Move::Step(from, to) = &parsed {
+            let (moved, _) = self.piece_at(from).unwrap();
+            if self.piece_at(to).is_none() && attacks[opp.index()][KidsShogiGame::p_to_c(from)] > 0
+                && next_attacks[opp.index()][KidsShogiGame::p_to_c(to)] == 0 {
+                reasons.push(Reason::Escapes { piece: moved.name().to_string() });
+            }
+        }
+        reasons
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn reasons(fen: &str, mv: &str) -> Vec<Reason> {
+        KidsShogiGame::from_fen(fen).unwrap().move_reasons(mv)
+    }
+
+    #[test]
+    fn captures_and_promotes() {
+        // Taking the chick also puts the new chick next to the lion
+        assert_eq!(reasons("gle/1c1/1C1/ELG b -", "b2b3"),
+            vec![Reason::Captures { piece: "chick".to_string() }, Reason::ThreatensLion]);
+        // The chick becomes a hen on the last rank, taking the giraffe there
+        assert!(reasons("g1l/C2/3/1L1 b -", "a3a4").starts_with(&[
+            Reason::Captures { piece: "giraffe".to_string() },
+            Reason::Promotes { piece: "chick".to_string() },
+        ]));
+    }
+
+    #[test]
+    fn threatens_the_lion() {
+        // The giraffe steps next to the opponent's lion
+        assert!(reasons("3/1l1/G2/1L1 b -", "a2a3").contains(&Reason::ThreatensLion));
+        assert!(!reasons("3/1l1/G2/1L1 b -", "a2a1").contains(&Reason::ThreatensLion));
+    }
+
+    #[test]
+    fn tries() {
+        // Gote's lion could take the giraffe on a1; from b1 the giraffe guards a1 and c1
+        assert!(reasons("3/3/1l1/G1L b -", "a1b1").contains(&Reason::PreventsTry));
+        // Sente's lion reaches the third rank with nothing guarding the fourth
+        assert!(reasons("l2/3/3/1L1 b -", "b1b2").is_empty());
+        assert!(reasons("l2/3/1L1/3 b -", "b2b3").contains(&Reason::ThreatensTry));
+    }
+
+    #[test]
+    fn defends_and_escapes() {
+        // Gote's chick attacks the undefended giraffe on a2
+        assert!(reasons("1l1/c2/G2/2L b -", "c1b1").contains(&Reason::Defends { piece: "giraffe".to_string() }));
+        assert!(reasons("1l1/c2/G2/2L b -", "a2b2").contains(&Reason::Escapes { piece: "giraffe".to_string() }));
+    }
+}
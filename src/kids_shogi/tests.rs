@@ -297,7 +297,7 @@ fn invalid_moves() {
 fn encode_hand() {
     let fen = "gl1/1e1/3/ELG b Cc";
     let pos = KidsShogiGame::from_fen(fen).unwrap();
-    let encode_len = KidsShogiGame::encode_length();
+    let encode_len = KidsShogiGame::ENCODE_LENGTH;
     assert_eq!(encode_len, 12*10 + 6*2 + 2);
     
     let encoded = pos.encode();
@@ -328,4 +328,58 @@ fn simple_evaluator() {
     let mv = Move::from_fen("a3a4").unwrap();
     let pos4 = pos3.make_move_impl(&mv).unwrap();
     assert_eq!(eval.evaluate_position(&pos4), -eval.saturation());  // winning pos
-}
\ No newline at end of file
+}
+
+#[test]
+fn capture_moves() {
+    let pos = KidsShogiGame::initial();
+    assert!(pos.is_capture("b2b3"));
+    assert!(!pos.is_capture("c1c2"));
+    let pos2 = KidsShogiGame::from_fen("gl1/1e1/3/ELG b Cc").unwrap();
+    assert!(!pos2.is_capture("C*b2"));
+    assert!(!pos2.is_capture("garbage"));
+}
+
+#[test]
+fn sparse_features_mirror() {
+    use ag::SparsePosition;
+    let pos = KidsShogiGame::initial();
+    assert_eq!(KidsShogiGame::FEATURE_COUNT, 12*10 + 6*2);
+    // The initial position is symmetric, so both sides see the same features
+    let mut sente = pos.active_features(0);
+    let mut gote = pos.active_features(1);
+    sente.sort();
+    gote.sort();
+    assert_eq!(sente, gote);
+    assert_eq!(sente.len(), 8);
+    let in_hand = KidsShogiGame::from_fen("gl1/1e1/3/ELG b CCc").unwrap().active_features(0);
+    assert_eq!(in_hand.iter().filter(|&&f| f >= 12*10).count(), 3);
+}
+
+#[test]
+fn sparse_feature_changes_match_recomputation() {
+    use ag::SparsePosition;
+    // Steps, captures and drops along the way
+    let mut pos = KidsShogiGame::initial();
+    for mv in ["b2b3", "c4b3", "C*a3", "a4a3", "a1b2", "C*a2", "b2a3"] {
+        for player in [0, 1] {
+            let (removed, added) = pos.feature_changes(mv, player).unwrap();
+            let next = pos.make_move(mv).unwrap();
+            let mut updated = pos.active_features(player).into_iter()
+                .filter(|f| !removed.contains(f))
+                .chain(added)
+                .collect::<Vec<_>>();
+            let mut expected = next.active_features(player);
+            updated.sort();
+            expected.sort();
+            assert_eq!(updated, expected, "after {}", mv);
+        }
+        pos = pos.make_move(mv).unwrap();
+    }
+    assert!(pos.feature_changes("a1a1", 0).is_none());
+    // A chick promoting on the last rank
+    let pos = KidsShogiGame::from_fen("1l1/C2/3/1L1 b -").unwrap();
+    let (removed, added) = pos.feature_changes("a3a4", 0).unwrap();
+    assert_eq!(removed, vec![(6*5 + PieceKind::Chicken.index())*2]);
+    assert_eq!(added, vec![(9*5 + PieceKind::Hen.index())*2]);
+}
@@ -1,6 +1,11 @@
-use crate::strategy::StrategyEngine;
+use crate::strategy::{derive_seed, SearchLimits, StrategyEngine};
 use std::io::{stdin, stdout, Write};
-use abstract_game::{AbstractGame, Evaluator, NeuroPosition};
+use abstract_game::{Evaluator, StrategyFactory};
+use clap::ValueEnum;
+use spec::Spec;
+use games::{GameKind, GameVisitor, RegisteredGame};
+use neuro::NeuroModel;
+use tune::Tunable;
 use clap::Parser;
 
 mod kids_shogi;
@@ -10,37 +15,75 @@ mod neuro;
 mod mcts;
 mod rpc;
 mod static_server;
+mod games;
+mod alphabeta;
+mod transposition;
+mod dfpn;
+mod analysis;
+mod book;
+mod skill;
+mod ensemble;
+mod tune;
+mod hint;
+mod nnue;
+mod external;
+mod remote;
+mod ponder;
+mod spec;
+mod tree_dump;
 
-type GamePosition = kids_shogi::KidsShogiGame;
-const ENCODE_LEN: usize = <GamePosition as NeuroPosition>::ENCODE_LENGTH;
-type NeuroEval = neuro::NeuroEvaluator<GamePosition, ENCODE_LEN>;
+fn read_human_move<G: RegisteredGame>(pos: &G, hint: &mut impl FnMut(&G) -> Option<hint::Hint>) -> Option<String> {
+    loop {
+        print!("Human move> ");
+        stdout().flush().expect("oops flush");
+        let mut buf = String::new();
+        stdin().read_line(&mut buf).expect("failed to read line");
+        let mv = buf.trim();
+        if mv.is_empty() {
+            break None
+        }
+        if mv == "?" {
+            match hint(pos) {
+                Some(hint) => println!("Hint: {}", hint),
+                None => println!("No hint available"),
+            }
+            continue
+        }
+        let new_pos_or = pos.make_move(mv);
+        if new_pos_or.is_some() {
+            break Some(mv.to_string())
+        } else {
+            println!("Possible moves: {} (? for a hint)", pos.possible_moves().join(" "));
+        }
+    }
+}
 
-fn play_cmd_line<EngineT: StrategyEngine<GamePosition>>(human_player: i32, strat: &mut EngineT) {
-    let mut pos = GamePosition::initial();
+/// With `ponder`, the machine keeps thinking while the human chooses a move; with
+/// `dumper`, its search tree is written after each of its moves.
+fn play_cmd_line<G: RegisteredGame, EngineT: StrategyEngine<G> + Send>(
+    human_player: i32, strat: &mut EngineT, mut hint: impl FnMut(&G) -> Option<hint::Hint>, limits: &SearchLimits,
+    ponder: bool, dumper: Option<&tree_dump::TreeDumper>,
+) {
+    let mut pos = G::initial();
+    let mut ply = 0;
     while !pos.is_lost() {
         println!("{}", pos.pretty_print());
         let mv = match pos.current_player() {
             v if v==human_player => {
-                loop {
-                    print!("Human move> ");
-                    stdout().flush().expect("oops flush");
-                    let mut buf = String::new();
-                    stdin().read_line(&mut buf).expect("failed to read line");
-                    let mv = buf.trim();
-                    if mv.is_empty() {
-                        break None
-                    }
-                    let new_pos_or = pos.make_move(mv);
-                    if new_pos_or.is_some() {
-                        break Some(mv.to_string())
-                    } else {
-                        println!("Possible moves: {}", pos.possible_moves().join(" "));
-                    }
+                if ponder {
+                    ponder::ponder_while(strat, &pos, || read_human_move(&pos, &mut hint))
+                } else {
+                    read_human_move(&pos, &mut hint)
                 }
             }
             _ => {
-                let mv = strat.choose_move(&pos);
+                let mv = strat.choose_move_with_limits(&pos, limits);
                 println!("Machine move> {}", mv.clone().unwrap_or("???".to_string()));
+                if let Some(dumper) = dumper {
+                    if !dumper.dump(&*strat, &pos, ply).expect("failed to write the search tree") {
+                        println!("(this strategy keeps no search tree to dump)");
+                    }
+                }
                 mv
             }
         };
@@ -49,6 +92,7 @@ fn play_cmd_line<EngineT: StrategyEngine<GamePosition>>(human_player: i32, strat
             break
         }
         pos = pos.make_move(&mv.unwrap()).expect("must be a valid move");
+        ply += 1;
     }
     if pos.is_lost() {
         let winner = match pos.current_player() {
@@ -62,8 +106,24 @@ fn play_cmd_line<EngineT: StrategyEngine<GamePosition>>(human_player: i32, strat
     }
 }
 
-#[derive(clap::Parser)]
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+enum StrategyKind {
+    Mcts,
+    AlphaBeta,
+    Random,
+    // MCTS and alpha-beta together, see --ensemble-combine
+    Ensemble,
+    // Another program speaking the --engine protocol, see --engine-command
+    External,
+    // Another kid_shogi server, see --remote-url
+    Remote,
+}
+
+#[derive(clap::Parser, Clone)]
 struct Argv {
+    // Game to play, train or serve by default
+    #[arg(short='g', long, value_enum, default_value_t = GameKind::KidsShogi)]
+    game: GameKind,
     // Human player (0=first, 1=second, 2=play with self)
     #[arg(short='p', long, default_value_t = 0)]
     human_player: i32,
@@ -76,9 +136,107 @@ struct Argv {
     // Max tree depth per MCTS rollout
     #[arg(long, default_value_t = 8)]
     max_depth: i32,
+    // Search algorithm used by the machine player
+    #[arg(long, value_enum, default_value_t = StrategyKind::Mcts)]
+    strategy: StrategyKind,
+    // Iterative deepening limit for alpha-beta
+    #[arg(long, default_value_t = 10)]
+    search_depth: i32,
+    // How --strategy ensemble combines its members
+    #[arg(long, value_enum, default_value_t = ensemble::Combine::Average)]
+    ensemble_combine: ensemble::Combine,
+    // Program and arguments of the --strategy external engine, separated by spaces
+    #[arg(long, required_if_eq("strategy", "external"))]
+    engine_command: Option<String>,
+    // Milliseconds an external or remote engine may think about a move when there is no --movetime
+    #[arg(long, default_value_t = 10000)]
+    engine_timeout: u64,
+    // RPC endpoint of the --strategy remote server, e.g. http://127.0.0.1:8080/rpc
+    #[arg(long, required_if_eq("strategy", "remote"))]
+    remote_url: Option<String>,
+    // How many times a failed call to the remote server is repeated
+    #[arg(long, default_value_t = 3)]
+    remote_retries: usize,
+    // Search threads for alpha-beta (Lazy SMP) and MCTS (one shared tree)
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    // Think at most this many milliseconds per move (alpha-beta searches as deep as it fits)
+    #[arg(long)]
+    movetime: Option<u64>,
+    // Keep searching while the human (or the server's client) thinks, reusing the
+    // results for the next move
+    #[arg(long)]
+    ponder: bool,
+    // Benchmark alpha-beta and MCTS with 1..=threads threads, play MCTS with threads
+    // against one thread at equal time per move and exit
+    #[arg(long)]
+    bench: bool,
+    // Weaken the machine player to this skill level, 1 (beginner) to 10 (full strength)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=10))]
+    level: Option<u8>,
+    // Match each skill level against the one below over this many games, print the scores and exit
+    #[arg(long)]
+    calibrate_skill: Option<usize>,
+    // Opening book the machine player follows before searching (applies to --game only)
+    #[arg(long)]
+    book: Option<String>,
+    // Build an opening book into this file from self-play and --import-games, then exit
+    #[arg(long)]
+    build_book: Option<String>,
+    #[arg(long, default_value_t = 100)]
+    book_games: usize,
+    // Plies of each game recorded in the book
+    #[arg(long, default_value_t = 12)]
+    book_depth: usize,
+    // Games to add to the book, one per line: moves from the initial position and a result
+    #[arg(long)]
+    import_games: Option<String>,
+    // Look for a forced win in this FEN with df-pn, print the proof line and exit
+    #[arg(long)]
+    solve: Option<String>,
+    // Node budget for the df-pn mate solver
+    #[arg(long, default_value_t = 200000)]
+    mate_nodes: usize,
+    // Print the best --num-pv moves of this FEN with scores and lines, then exit
+    #[arg(long)]
+    analyze: Option<String>,
+    // Number of candidate moves listed by --analyze
+    #[arg(long, default_value_t = 3)]
+    num_pv: usize,
+    // Write the machine player's search tree (MCTS) after each of its moves into this
+    // directory, as ply-NNN.json and ply-NNN.dot for Graphviz
+    #[arg(long)]
+    dump_tree: Option<std::path::PathBuf>,
+    // Plies below the searched position written by --dump-tree
+    #[arg(long, default_value_t = 2)]
+    dump_depth: usize,
+    // Moves tried fewer times are left out of --dump-tree
+    #[arg(long, default_value_t = 1)]
+    dump_min_visits: usize,
+    // Let the machine player check for forced wins with df-pn before searching
+    #[arg(long)]
+    mate_search: bool,
     // Path to neural network weights; if given, uses neuro evaluator instead of SimpleEvaluator
     #[arg(long)]
     model_file: Option<String>,
+    // JSON weights for the feature evaluator; if given, uses it instead of SimpleEvaluator
+    #[arg(long, conflicts_with = "model_file")]
+    weights: Option<String>,
+    // NNUE network file; if given, uses it instead of SimpleEvaluator, and --train trains it
+    #[arg(long, conflicts_with_all = ["model_file", "weights"])]
+    nnue: Option<String>,
+    // Fit the feature weights (starting from --weights) to the results of the positions
+    // or games in this file, write them to --tune-out and exit
+    #[arg(long)]
+    tune: Option<String>,
+    // Self-play games whose positions are added to the --tune set
+    #[arg(long, default_value_t = 0)]
+    tune_games: usize,
+    #[arg(long, default_value = "weights.json")]
+    tune_out: String,
+    // Maximum passes over all weights while tuning
+    #[arg(long, default_value_t = 100)]
+    tune_iterations: usize,
     #[arg(short='t', long)]
     train: bool,
     // Number of training epochs to run (default 1)
@@ -96,23 +254,283 @@ struct Argv {
     // Directory to serve static web UI files from
     #[arg(long, default_value = "src/web")]
     web_root: std::path::PathBuf,
+    // Strategy as a spec, e.g. "book(file=ks.book,then=alphabeta(depth=8,eval=nnue:ks.nnue))";
+    // replaces --strategy and the flags that configure it (see the README for all engines)
+    #[arg(long)]
+    spec: Option<Spec>,
+    // Play --arena-games games of the machine player against this strategy spec, print the score and exit
+    #[arg(long)]
+    arena: Option<Spec>,
+    #[arg(long, default_value_t = 20)]
+    arena_games: usize,
+    // Seed for all random choices (engines, self-play, training, server game ids),
+    // so that runs can be repeated; random when omitted
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// `--seed`, or a random seed when it is not given
+fn seed(args: &Argv) -> u64 {
+    args.seed.unwrap_or_else(rand::random)
+}
+
+/// Evaluator named by `--model-file`, `--weights` or `--nnue` for `--game`, in spec form
+fn eval_spec(args: &Argv, game: GameKind) -> String {
+    let own_game = game == args.game;
+    match (&args.model_file, &args.weights, &args.nnue) {
+        (Some(path), _, _) if own_game => format!("neuro:{}", path),
+        (_, Some(path), _) if own_game => format!("weights:{}", path),
+        (_, _, Some(path)) if own_game => format!("nnue:{}", path),
+        _ => "simple".to_string(),
+    }
+}
+
+/// The strategy `--spec` names, or the one the separate strategy flags describe.
+/// Only `--game` gets `--spec` and the evaluator, book and mate search flags;
+/// other games the server hosts play with their defaults.
+fn strategy_spec(args: &Argv, game: GameKind) -> Spec {
+    let own_game = game == args.game;
+    if let Some(spec) = args.spec.as_ref().filter(|_| own_game) {
+        return spec.clone()
+    }
+    let eval = eval_spec(args, game);
+    let mcts = Spec::word("mcts").with("tries", args.num_tries).with("softness", args.softness)
+        .with("depth", args.max_depth).with("threads", args.threads).with("eval", &eval);
+    let alphabeta = Spec::word("alphabeta").with("depth", args.search_depth).with("threads", args.threads)
+        .with("eval", &eval);
+    let mut spec = match args.strategy {
+        StrategyKind::Mcts => mcts,
+        StrategyKind::AlphaBeta => alphabeta,
+        StrategyKind::Random => Spec::word("random"),
+        StrategyKind::Ensemble => Spec::word("ensemble").with_spec("member", mcts).with_spec("member", alphabeta)
+            .with("combine", args.ensemble_combine.to_possible_value().unwrap().get_name()).with("verbose", true),
+        StrategyKind::External => Spec::word("external")
+            .with("command", args.engine_command.as_deref().unwrap_or_default()).with("timeout", args.engine_timeout),
+        StrategyKind::Remote => Spec::word("remote").with("url", args.remote_url.as_deref().unwrap_or_default())
+            .with("timeout", args.engine_timeout).with("retries", args.remote_retries),
+    };
+    if let Some(path) = args.book.as_ref().filter(|_| own_game) {
+        spec = Spec::word("book").with("file", path).with_spec("then", spec);
+    }
+    if args.mate_search && own_game {
+        spec = Spec::word("mate").with("nodes", args.mate_nodes).with_spec("then", spec);
+    }
+    spec
+}
+
+/// `strategy_spec` of the machine player, weakened to `--level`
+fn player_spec(args: &Argv) -> Spec {
+    let spec = strategy_spec(args, args.game);
+    match args.level {
+        Some(level) => Spec::word("skill").with("level", level).with_spec("then", spec),
+        None => spec,
+    }
+}
+
+/// `--dump-tree`, if given
+fn tree_dumper(args: &Argv) -> Option<tree_dump::TreeDumper> {
+    args.dump_tree.as_ref().map(|dir| tree_dump::TreeDumper::new(dir, args.dump_depth, args.dump_min_visits)
+        .expect("failed to create the --dump-tree directory"))
+}
+
+fn build_factory<G: RegisteredGame>(spec: &Spec) -> Box<dyn StrategyFactory<G>> {
+    spec::build(spec).unwrap_or_else(|e| panic!("invalid strategy {}: {}", spec, e))
+}
+
+fn run_analyze<G: RegisteredGame>(fen: &str, args: &Argv) {
+    let pos = G::from_str(fen).expect("invalid FEN");
+    println!("{}", pos.pretty_print());
+    let Some(mut engine) = build_factory::<G>(&player_spec(args)).create_analysis(seed(args)) else {
+        println!("This strategy cannot analyze positions");
+        return;
+    };
+    let limits = SearchLimits::with_movetime(args.movetime.map(std::time::Duration::from_millis));
+    print!("{}", analysis::format_analysis(&engine.analyze(&pos, args.num_pv, &limits)));
+}
+
+fn run_build_book<G: RegisteredGame>(path: &str, args: &Argv) {
+    const MAX_GAME_PLIES: usize = 100;
+    let mut book = book::OpeningBook::new();
+    if let Some(ref games_file) = args.import_games {
+        let text = std::fs::read_to_string(games_file).expect("failed to read games");
+        let imported = book.import_games::<G>(&text, args.book_depth);
+        println!("Imported {} games from {}", imported, games_file);
+    }
+    if args.book_games > 0 {
+        println!("Playing {} self-play games...", args.book_games);
+        let mut strat = build_factory::<G>(&player_spec(args)).create(seed(args));
+        book.add_self_play::<G, _>(&mut strat, args.book_games, args.book_depth, MAX_GAME_PLIES);
+    }
+    book.prune(1);
+    if book.is_empty() {
+        println!("Warning: no game contributed a move, the book is empty");
+    }
+    book.save(path).expect("failed to save opening book");
+    println!("Saved {} positions to {}", book.len(), path);
+}
+
+fn run_calibrate_skill<G: RegisteredGame>(games: usize, args: &Argv) {
+    const MAX_GAME_PLIES: usize = 100;
+    println!("Score of each level against the level below ({} games per pair):", games);
+    let factory = build_factory::<G>(&strategy_spec(args, args.game));
+    let scores = skill::calibrate::<G, _>(|level, seed| {
+        let engine = factory.create(args.seed.map_or(seed, |s| derive_seed(s, seed)));
+        skill::SkillLimitedStrategy::new(level, seed, engine)
+    }, games, MAX_GAME_PLIES);
+    for (level, score) in scores {
+        println!("  {:2} vs {:2}: {:.2}", level, level - 1, score);
+    }
+}
+
+fn run_tune<G: RegisteredGame>(path: &str, args: &Argv) {
+    const MAX_GAME_PLIES: usize = 100;
+    let mut eval = match args.weights {
+        Some(ref weights) => G::load_feature_eval(weights).expect("failed to load weights"),
+        None => G::FeatureEval::default(),
+    };
+    let text = std::fs::read_to_string(path).expect("failed to read positions");
+    let mut positions = tune::parse_positions::<G>(&text);
+    println!("Read {} positions from {}", positions.len(), path);
+    if args.tune_games > 0 {
+        println!("Playing {} self-play games...", args.tune_games);
+        let mut strat = build_factory::<G>(&player_spec(args)).create(seed(args));
+        let played = tune::self_play_positions::<G, _>(&mut strat, args.tune_games, MAX_GAME_PLIES);
+        println!("Added {} self-play positions", played.len());
+        positions.extend(played);
+    }
+    if positions.is_empty() {
+        println!("No positions to tune on");
+        return;
+    }
+    let before = eval.params();
+    let params = tune::TuneParameters { max_iterations: args.tune_iterations, ..Default::default() };
+    let report = tune::tune(&mut eval, &positions, &params, |iteration, error, step| {
+        println!("Iteration {}: error {:.6} (step {})", iteration, error, step);
+    });
+    println!("Scale k = {}", report.k);
+    println!("Error before {:.6}, after {:.6} ({} iterations)", report.error_before, report.error_after, report.iterations);
+    print!("{}", tune::format_weights(&before, &eval));
+    G::save_feature_eval(&eval, &args.tune_out).expect("failed to save weights");
+    println!("Saved weights to {}", args.tune_out);
+}
+
+fn run_solve<G: RegisteredGame>(fen: &str, args: &Argv) {
+    let pos = G::from_str(fen).expect("invalid FEN");
+    println!("{}", pos.pretty_print());
+    let mut solver = dfpn::DfPnSolver::new(args.mate_nodes);
+    match solver.solve(&pos) {
+        dfpn::SolveResult::Win(line) =>
+            println!("Forced win in {} plies: {}", line.len(), line.join(" ")),
+        dfpn::SolveResult::NoWin => println!("No forced win"),
+        dfpn::SolveResult::Unknown => println!("Unknown: node budget exhausted"),
+    }
+    println!("Nodes: {}", solver.nodes());
+}
+
+/// Searches the initial position and its successors to `--search-depth` with
+/// 1..=`--threads` threads, printing time and nodes per second for each count;
+/// then the same for `--num-tries` MCTS walks, and a match of MCTS with
+/// `--threads` threads against one thread, both given `--movetime` (50 ms by
+/// default) per move.
+fn run_bench<G: RegisteredGame, EvalT: Evaluator<G> + Sync>(eval: &EvalT, args: &Argv) {
+    let initial = G::initial();
+    let positions = std::iter::once(initial.clone())
+        .chain(initial.possible_moves().iter().map(|mv| initial.make_move(mv).unwrap()))
+        .collect::<Vec<_>>();
+    println!("Alpha-beta, depth {}:", args.search_depth);
+    let mut base_secs = None;
+    for threads in 1..=args.threads.max(1) {
+        let mut strat = alphabeta::AlphaBetaStrategy::new(eval, args.search_depth, threads);
+        let t0 = std::time::Instant::now();
+        let mut nodes = 0;
+        for pos in &positions {
+            strat.search(pos);
+            nodes += strat.nodes();
+        }
+        let secs = t0.elapsed().as_secs_f64();
+        let speedup = base_secs.get_or_insert(secs).max(1e-9) / secs.max(1e-9);
+        println!("threads={:2} time={:8.3}s nodes={:10} nps={:10.0} speedup={:.2}x",
+            threads, secs, nodes, nodes as f64 / secs.max(1e-9), speedup);
+    }
+
+    println!("MCTS, {} walks per position:", args.num_tries);
+    let new_mcts = |threads, seed| mcts::MonteCarloTreeSearchStrategy::new(
+        eval, args.num_tries, args.softness, args.max_depth, seed).threads(threads);
+    let mut base_secs = None;
+    for threads in 1..=args.threads.max(1) {
+        let t0 = std::time::Instant::now();
+        // Largest tree over the positions
+        let mut tree = mcts::MemoryUsage::default();
+        let walks: usize = positions.iter().map(|pos| {
+            let mut strat = new_mcts(threads, seed(args));
+            analysis::AnalysisEngine::analyze(&mut strat, pos, 1, &SearchLimits::default());
+            if strat.memory().bytes > tree.bytes {
+                tree = strat.memory();
+            }
+            strat.walks()
+        }).sum();
+        let secs = t0.elapsed().as_secs_f64();
+        let speedup = base_secs.get_or_insert(secs).max(1e-9) / secs.max(1e-9);
+        println!("threads={:2} time={:8.3}s walks={:10} wps={:10.0} speedup={:.2}x tree={} nodes/{:.1}MiB",
+            threads, secs, walks, walks as f64 / secs.max(1e-9), speedup, tree.nodes, tree.bytes as f64 / (1 << 20) as f64);
+    }
+    // Reuse: the walks the tree already holds for the position after the chosen move
+    let carried: usize = positions.iter().map(|pos| {
+        let mut strat = new_mcts(1, seed(args));
+        match strat.choose_move(pos).and_then(|mv| pos.make_move(&mv)) {
+            Some(next) if !next.is_lost() && strat.choose_move(&next).is_some() => strat.carried_visits(),
+            _ => 0,
+        }
+    }).sum();
+    println!("tree reuse: {:.1}% of the walks carried over to the next move",
+        100.0 * carried as f64 / (positions.len() * args.num_tries).max(1) as f64);
+    let threads = args.threads.max(1);
+    let movetime = std::time::Duration::from_millis(args.movetime.unwrap_or(50));
+    let score = skill::match_score::<G, _>(|player, game| {
+        let strat = new_mcts(if player == 0 { threads } else { 1 }, derive_seed(seed(args), 2 * game + player as u64));
+        strategy::MoveTimeStrategy::new(strat, movetime)
+    }, args.arena_games, 100);
+    println!("MCTS with {} threads vs 1 thread, {} ms per move, {} games: {:.3}",
+        threads, movetime.as_millis(), args.arena_games, score);
 }
 
-fn run_engine_loop<EvalT: Evaluator<GamePosition>>(eval: &EvalT, args: &Argv) {
+fn run_engine_loop<G: RegisteredGame>(args: &Argv) {
     use std::io::BufRead;
     const MAX_HALF_MOVES: usize = 100;
     let mut half_moves: usize = 0;
     let stdin = std::io::stdin();
+    let spec = player_spec(args);
+    eprintln!("Engine: {}", spec);
+    let mut strat = build_factory::<G>(&spec).create(seed(args));
+    let dumper = tree_dumper(args);
     for line in stdin.lock().lines() {
-        let fen = line.expect("read error");
+        let line = line.expect("read error");
         if half_moves >= MAX_HALF_MOVES {
             println!("1/2-1/2");
             break;
         }
-        let pos = GamePosition::from_str(&fen).expect("invalid FEN");
-        let mut strat = mcts::MonteCarloTreeSearchStrategy::new(
-            eval, args.num_tries, args.softness, args.max_depth);
-        let mv = strat.choose_move(&pos).expect("no moves");
+        // "<FEN>" or "<FEN> movetime <ms>"; the per-line budget overrides --movetime
+        let (fen, movetime) = match line.split_once(" movetime ") {
+            Some((fen, ms)) => match ms.trim().parse() {
+                Ok(ms) => (fen, Some(ms)),
+                Err(_) => {
+                    eprintln!("Engine: invalid movetime {:?}, using --movetime", ms.trim());
+                    (fen, args.movetime)
+                }
+            },
+            None => (line.as_str(), args.movetime),
+        };
+        let limits = SearchLimits::with_movetime(movetime.map(std::time::Duration::from_millis));
+        let pos = G::from_str(fen).expect("invalid FEN");
+        // An external or remote engine behind `--spec` may fail; give up the game
+        let Some(mv) = strat.choose_move_with_limits(&pos, &limits) else {
+            eprintln!("Engine: no move for {}", fen);
+            println!("1/2-1/2");
+            break;
+        };
+        if let Some(ref dumper) = dumper {
+            dumper.dump(&strat, &pos, half_moves).expect("failed to write the search tree");
+        }
         let new_pos = pos.make_move(&mv).expect("chosen move must be valid");
         half_moves += 1;
         if new_pos.is_lost() {
@@ -124,80 +542,192 @@ fn run_engine_loop<EvalT: Evaluator<GamePosition>>(eval: &EvalT, args: &Argv) {
     }
 }
 
-fn run_server<EvalT: Evaluator<GamePosition> + Send + Sync + 'static>(
-    eval: EvalT, args: &Argv,
-) {
-    let eval_ref: &'static EvalT = Box::leak(Box::new(eval));
-    let io = rpc::create_io_handler(
-        mcts::MctsFactory::new(eval_ref, args.num_tries, args.softness, args.max_depth));
+/// Registers one host per known game, playing `strategy_spec` for that game.
+struct AddHost<'a> {
+    hosts: &'a mut rpc::GameHosts,
+    args: &'a Argv,
+}
+
+impl GameVisitor for AddHost<'_> {
+    type Output = ();
+    fn visit<G: RegisteredGame>(self) {
+        let spec = strategy_spec(self.args, G::KIND);
+        println!("Server: {:?} plays {}", G::KIND, spec);
+        self.hosts.add(build_factory::<G>(&spec));
+    }
+}
+
+/// Plays `--arena-games` games of the machine player against `opponent` and
+/// prints the player's score.
+fn run_arena<G: RegisteredGame>(opponent: &Spec, args: &Argv) {
+    const MAX_GAME_PLIES: usize = 100;
+    let player = player_spec(args);
+    let factories = [build_factory::<G>(&player), build_factory::<G>(opponent)];
+    println!("{} vs {} ({} games)", player, opponent, args.arena_games);
+    let seed = seed(args);
+    let movetime = args.movetime.map(std::time::Duration::from_millis);
+    let score = skill::match_score::<G, _>(|side, game| {
+        let engine = factories[side].create(derive_seed(seed, 2 * game + side as u64));
+        match movetime {
+            Some(movetime) => Box::new(strategy::MoveTimeStrategy::new(engine, movetime)),
+            None => engine,
+        }
+    }, args.arena_games, MAX_GAME_PLIES);
+    println!("Score: {:.3}", score);
+}
+
+fn run_server(args: &Argv) {
+    let movetime = args.movetime.map(std::time::Duration::from_millis);
+    let mut hosts = rpc::GameHosts::new(args.game, movetime, args.seed).ponder(args.ponder);
+    for game in GameKind::ALL {
+        game.dispatch(AddHost { hosts: &mut hosts, args });
+    }
+    let io = rpc::create_io_handler(hosts);
     let addr = args.listen.parse().expect("invalid listen address");
     println!("Serving at http://{} (GUI: /, RPC: /rpc)", args.listen);
     static_server::serve(io, args.web_root.clone(), addr);
 }
 
-fn main() {
-    let args = Argv::parse();
+/// Runs the selected mode (training, engine loop or CLI game) for one game type.
+struct RunGame<'a> {
+    args: &'a Argv,
+}
+
+impl GameVisitor for RunGame<'_> {
+    type Output = ();
+    fn visit<G: RegisteredGame>(self) {
+        run_game::<G>(self.args)
+    }
+}
+
+/// Binds `$eval` to the evaluator chosen by `--model-file` / `--weights` / `--nnue`
+/// (or the game's default) and evaluates `$body` with it.
+macro_rules! with_eval {
+    ($G:ty, $args:expr, |$eval:ident| $body:expr) => {
+        if let Some(ref model_file) = $args.model_file {
+            let $eval = <$G as RegisteredGame>::NeuroEval::load(model_file)
+                .expect("failed to load model");
+            $body
+        } else if let Some(ref weights) = $args.weights {
+            let $eval = <$G as RegisteredGame>::load_feature_eval(weights)
+                .expect("failed to load weights");
+            $body
+        } else if let Some(ref path) = $args.nnue {
+            let $eval = nnue::NnueEvaluator::<$G>::load(path)
+                .expect("failed to load NNUE");
+            $body
+        } else {
+            let $eval = <$G as RegisteredGame>::DefaultEval::default();
+            $body
+        }
+    };
+}
 
+/// Self-play training of `M` in `model_file`, with parameters from `{model_file}.params`
+fn run_train<G: RegisteredGame, M: NeuroModel<G>>(model_file: &str, args: &Argv) {
+    let params_file = format!("{}.params", model_file);
+    let mut nn =
+        M::load(model_file)
+            .map(|m| { println!("Loaded model from {}", model_file); m })
+            .unwrap_or_else(|_| { println!("No model at {}, starting fresh", model_file); M::fresh() });
+    let mut params = neuro::load_params(&params_file)
+        .map(|p| { println!("Loaded params from {}", params_file); p })
+        .unwrap_or_else(|_| { println!("Using default train parameters"); neuro::TrainParameters::default() });
+    if args.seed.is_some() {
+        params.seed = args.seed;
+    }
+    println!("Parameters: {:?}", params);
+    println!("Max epochs: {}", args.max_epochs);
+    let eval = G::DefaultEval::default();
+    for epoch in 0..args.max_epochs {
+        nn.train_epoch(&eval, &params, epoch, model_file)
+            .expect("training failed");
+    }
+    nn.save_to(model_file).unwrap();
+    neuro::save_params(&params, &params_file).unwrap();
+    println!("Final model saved to {}", model_file);
+}
+
+fn run_game<G: RegisteredGame>(args: &Argv) {
     // ── Training ──────────────────────────────────────────────────────────────
     if args.train {
-        let model_file = args.model_file.as_deref().unwrap_or("ks.model");
-        let params_file = format!("{}.params", model_file);
-        let mut nn: NeuroEval =
-            neuro::load_model(model_file)
-                .map(|m| { println!("Loaded model from {}", model_file); m })
-                .unwrap_or_else(|_| { println!("No model at {}, starting fresh", model_file); NeuroEval::new() });
-        let params = neuro::load_params(&params_file)
-            .map(|p| { println!("Loaded params from {}", params_file); p })
-            .unwrap_or_else(|_| { println!("Using default train parameters"); neuro::TrainParameters::default() });
-        println!("Parameters: {:?}", params);
-        println!("Max epochs: {}", args.max_epochs);
-        let eval = kids_shogi::SimpleEvaluator{};
-        for epoch in 0..args.max_epochs {
-            neuro::train_epoch(&eval, &mut nn, &params, epoch, model_file)
-                .expect("training failed");
+        match args.nnue {
+            Some(ref path) => run_train::<G, nnue::NnueEvaluator<G>>(path, args),
+            None => run_train::<G, G::NeuroEval>(args.model_file.as_deref().unwrap_or("ks.model"), args),
         }
-        neuro::save_model(&nn, model_file).unwrap();
-        neuro::save_params(&params, &params_file).unwrap();
-        println!("Final model saved to {}", model_file);
+        return;
+    }
+
+    // ── Opening book ──────────────────────────────────────────────────────────
+    if let Some(ref path) = args.build_book {
+        run_build_book::<G>(path, args);
+        return;
+    }
+
+    // ── Weight tuning ─────────────────────────────────────────────────────────
+    if let Some(ref path) = args.tune {
+        run_tune::<G>(path, args);
+        return;
+    }
+
+    // ── Skill calibration ─────────────────────────────────────────────────────
+    if let Some(games) = args.calibrate_skill {
+        run_calibrate_skill::<G>(games, args);
+        return;
+    }
+
+    // ── Mate solver ───────────────────────────────────────────────────────────
+    if let Some(ref fen) = args.solve {
+        run_solve::<G>(fen, args);
+        return;
+    }
+
+    // ── Analysis ──────────────────────────────────────────────────────────────
+    if let Some(ref fen) = args.analyze {
+        run_analyze::<G>(fen, args);
+        return;
+    }
+
+    // ── Arena ─────────────────────────────────────────────────────────────────
+    if let Some(ref opponent) = args.arena {
+        run_arena::<G>(opponent, args);
+        return;
+    }
+
+    // ── Benchmark ─────────────────────────────────────────────────────────────
+    if args.bench {
+        with_eval!(G, args, |eval| run_bench::<G, _>(&eval, args));
         return;
     }
 
     // ── Engine loop ───────────────────────────────────────────────────────────
     if args.engine {
-        if let Some(ref model_file) = args.model_file {
-            let nn: NeuroEval = neuro::load_model(model_file)
-                .expect("failed to load model");
-            eprintln!("Engine: using neuro model from {}", model_file);
-            run_engine_loop(&nn, &args);
-        } else {
-            run_engine_loop(&kids_shogi::SimpleEvaluator{}, &args);
-        }
+        run_engine_loop::<G>(args);
         return;
     }
 
+    // ── CLI game ──────────────────────────────────────────────────────────────
+    let limits = SearchLimits::with_movetime(args.movetime.map(std::time::Duration::from_millis));
+    let factory = build_factory::<G>(&player_spec(args));
+    let mut strat = factory.create(seed(args));
+    // Strategies that cannot analyze still get hints, from alpha-beta
+    let mut hinter = factory.create_analysis(seed(args)).unwrap_or_else(|| {
+        let alphabeta = Spec::word("alphabeta").with("depth", args.search_depth).with("threads", args.threads)
+            .with("eval", eval_spec(args, args.game));
+        build_factory::<G>(&alphabeta).create_analysis(seed(args)).expect("alpha-beta can analyze")
+    });
+    let hint = |pos: &G| hint::hint(pos, &mut hinter, &limits, args.mate_nodes);
+    play_cmd_line(args.human_player, &mut strat, hint, &limits, args.ponder, tree_dumper(args).as_ref());
+}
+
+fn main() {
+    let args = Argv::parse();
+
     // ── HTTP server ───────────────────────────────────────────────────────────
     if args.server {
-        if let Some(ref model_file) = args.model_file {
-            let nn: NeuroEval = neuro::load_model(model_file)
-                .expect("failed to load model");
-            println!("Server: using neuro model from {}", model_file);
-            run_server(nn, &args);
-        } else {
-            run_server(kids_shogi::SimpleEvaluator{}, &args);
-        }
+        run_server(&args);
         return;
     }
 
-    // ── CLI game ──────────────────────────────────────────────────────────────
-    if let Some(ref model_file) = args.model_file {
-        let nn: NeuroEval = neuro::load_model(model_file)
-            .expect("failed to load model");
-        let mut strat = mcts::MonteCarloTreeSearchStrategy::new(
-            &nn, args.num_tries, args.softness, args.max_depth);
-        play_cmd_line(args.human_player, &mut strat);
-    } else {
-        let mut strat = mcts::MonteCarloTreeSearchStrategy::new(
-            &kids_shogi::SimpleEvaluator{}, args.num_tries, args.softness, args.max_depth);
-        play_cmd_line(args.human_player, &mut strat);
-    }
+    args.game.dispatch(RunGame { args: &args });
 }
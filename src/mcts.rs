@@ -2,22 +2,222 @@
 
 use std::collections::{HashMap,HashSet};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use crate::abstract_game::{self as ag};
-use crate::strategy::{self, StrategyEngine};
+use crate::analysis::{self, AnalysisEngine, MoveAnalysis};
+use crate::strategy::{self, SearchLimits};
+use crate::transposition::table_key;
+use crate::tree_dump::{Outcome, TreeNode};
+
+// Game-theoretic value for the player to move, once proven (MCTS-Solver): lost
+// positions are proven on creation, and proofs spread from children to parents.
+// A proof comes with the number of plies to the end of the game, the fastest win
+// or the slowest loss, so that playing proven wins makes progress.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Proof {
+    Unknown,
+    Won,
+    Lost,
+}
+
+impl Proof {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => Proof::Won,
+            2 => Proof::Lost,
+            _ => Proof::Unknown,
+        }
+    }
+}
+
+// Visits and total reward, as atomics so that walks on several threads can share them
+#[derive(Default)]
+struct Stats {
+    visits: AtomicU32,  // number of visits so far
+    reward: AtomicU64,    // total reward collected, as f64 bits
+    virtual_loss: AtomicU32,  // walks passing through right now
+}
+
+impl Stats {
+    fn visits(&self) -> usize {
+        self.visits.load(Ordering::Relaxed) as usize
+    }
+
+    fn reward(&self) -> f64 {
+        f64::from_bits(self.reward.load(Ordering::Relaxed))
+    }
+
+    // Visits and total reward counting each walk in progress as a lost visit for
+    // the player choosing this node, which steers other threads elsewhere
+    fn with_virtual_loss(&self) -> (usize, f64) {
+        let pending = self.virtual_loss.load(Ordering::Relaxed);
+        (self.visits() + pending as usize, self.reward() + pending as f64)
+    }
+
+    // A walk is about to pass through
+    fn add_virtual_loss(&self) {
+        self.virtual_loss.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Counts a walk that passed through, replacing its virtual loss if it added one
+    fn add(&self, reward: f64, had_virtual_loss: bool) {
+        self.reward.fetch_update(Ordering::Relaxed, Ordering::Relaxed,
+            |bits| Some((f64::from_bits(bits) + reward).to_bits())).unwrap();
+        self.visits.fetch_add(1, Ordering::Relaxed);
+        if had_virtual_loss {
+            self.virtual_loss.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// Index of a node in the arena
+type NodeId = u32;
+
+// Child of an edge whose node was evicted; the next walk along the edge makes it again
+const EVICTED: NodeId = NodeId::MAX;
+
+// A position, shared by all the lines transposing into it. Rewards are for the
+// player to move there.
+#[derive(Default)]
+struct Node {
+    evaluation: AtomicU64,  // as f64 bits, set when the node is made
+    stats: Stats,  // every walk through the position, whichever way it came
+    proof: AtomicU8,  // a `Proof`
+    proof_depth: AtomicU32,  // plies to the end once proven
+    children: OnceLock<Box<[Edge]>>,  // set once populated, in `possible_moves` order
+}
+
+// A move out of a populated node (UCT on a DAG): how good the move is comes from
+// the child's node, which pools every transposition, but how often it was tried
+// from here is counted on the edge, so parents do not share exploration counts.
+// The move itself is the one at the same index of `possible_moves`.
+struct Edge {
+    child: AtomicU32,  // a `NodeId`, or `EVICTED`
+    prior: f32,  // for PUCT only
+    stats: Stats,  // walks that took the move from this parent
+}
+
+impl Node {
+    fn evaluation(&self) -> f64 {
+        f64::from_bits(self.evaluation.load(Ordering::Relaxed))
+    }
+
+    fn proof(&self) -> Proof {
+        Proof::from_u8(self.proof.load(Ordering::Acquire))
+    }
+
+    fn proof_depth(&self) -> usize {
+        self.proof_depth.load(Ordering::Relaxed) as usize
+    }
+
+    fn prove(&self, proof: Proof, depth: usize) {
+        self.proof_depth.store(depth as u32, Ordering::Relaxed);
+        self.proof.store(proof as u8, Ordering::Release);
+    }
+}
+
+impl Edge {
+    fn child(&self) -> Option<NodeId> {
+        Some(self.child.load(Ordering::Acquire)).filter(|&id| id != EVICTED)
+    }
+}
 
-struct Node<H: Eq + std::hash::Hash + Copy> {
-    parents: HashSet<H>,
-    evaluation: f64,
-    visits: usize,  // number of visits so far
-    reward: f64,    // total reward collected
-    children: HashMap<String, H>,  // move->pos_hash
-    is_populated: bool,
+const CHUNK: usize = 4096;
+const MAX_CHUNKS: usize = 4096;
+const ARENA_NODES: usize = CHUNK * MAX_CHUNKS;
+
+// Node storage: fixed-size chunks that never move, so that threads can add nodes
+// while others read them. Slots are only freed with exclusive access, between walks,
+// and reused before new chunks are allocated.
+struct Arena {
+    chunks: Box<[OnceLock<Box<[Node]>>]>,
+    len: AtomicUsize,  // slots handed out so far, freed ones included
+    free: Mutex<Vec<NodeId>>,
+}
+
+impl Arena {
+    fn new() -> Self {
+        Arena {
+            chunks: (0..MAX_CHUNKS).map(|_| OnceLock::new()).collect(),
+            len: AtomicUsize::new(0),
+            free: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn get(&self, id: NodeId) -> &Node {
+        let id = id as usize;
+        &self.chunks[id / CHUNK].get().expect("node must be allocated")[id % CHUNK]
+    }
+
+    // An empty slot
+    fn alloc(&self) -> NodeId {
+        if let Some(id) = self.free.lock().unwrap().pop() {
+            return id
+        }
+        let id = self.len.fetch_add(1, Ordering::Relaxed);
+        // `MAX_MEMORY_LIMIT` keeps the tree well below this
+        assert!(id < ARENA_NODES, "MCTS arena is full");
+        self.chunks[id / CHUNK].get_or_init(|| (0..CHUNK).map(|_| Node::default()).collect());
+        id as NodeId
+    }
+
+    fn free(&mut self, id: NodeId) {
+        let id = id as usize;
+        self.chunks[id / CHUNK].get_mut().expect("node must be allocated")[id % CHUNK] = Node::default();
+        self.free.get_mut().unwrap().push(id as NodeId);
+    }
+}
+
+const SHARDS: usize = 64;
+
+/// Default bound on the bytes an MCTS tree takes
+pub const DEFAULT_MEMORY_LIMIT: usize = 512 << 20;
+/// Enough for the root and its moves, with room to walk
+pub const MIN_MEMORY_LIMIT: usize = 16 << 10;
+/// Every node takes at least a `Node`, so a tree within this fills at most 7/8 of the
+/// arena, leaving the rest for what walks add before the search notices
+pub const MAX_MEMORY_LIMIT: usize = ARENA_NODES / 8 * 7 * std::mem::size_of::<Node>();
+
+// Where each position's node is
+type Shard<H> = RwLock<HashMap<H, NodeId>>;
+
+/// Size of an MCTS tree
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MemoryUsage {
+    pub nodes: usize,
+    /// Estimated from the nodes, their edges and the position index
+    pub bytes: usize,
+}
+
+// A move of a populated node with what the search knows about it
+struct MoveStats<'a> {
+    mv: String,
+    visits: usize,  // walks that took the move from the node
+    reward: f64,    // their total reward, for the player to move after it
+    child: Option<&'a Node>,  // none if evicted
+}
+
+impl MoveStats<'_> {
+    fn proof(&self) -> Proof {
+        self.child.map_or(Proof::Unknown, Node::proof)
+    }
 }
 
 struct MCTSState<PosT: ag::AbstractGame> {
-    nodes: HashMap<PosT::PositionHash, Node<PosT::PositionHash>>,
-    phantom_data: PhantomData<PosT>,
+    arena: Arena,
+    // Split by hash so that threads seldom wait for the same lock
+    shards: Vec<Shard<PosT::PositionHash>>,
+    nodes: AtomicUsize,
+    edges: AtomicUsize,
+    // Rank moves by visits, as PUCT does, rather than by total reward
+    rank_candidates: bool,
+    phantom_data: PhantomData<fn(PosT)>,
 }
 
 fn clamp(v: f64) -> f64 {
@@ -25,114 +225,475 @@ fn clamp(v: f64) -> f64 {
 }
 
 impl<PosT: ag::AbstractGame> MCTSState<PosT> {
-    fn make_node<EvalT: ag::Evaluator<PosT>>(&mut self, pos: &PosT, parent: Option<&PosT>, evaluator: &EvalT) {
+    fn new(rank_candidates: bool) -> Self {
+        MCTSState {
+            arena: Arena::new(),
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            nodes: AtomicUsize::new(0),
+            edges: AtomicUsize::new(0),
+            rank_candidates,
+            phantom_data: PhantomData,
+        }
+    }
+
+    fn shard(&self, h: &PosT::PositionHash) -> &Shard<PosT::PositionHash> {
+        &self.shards[table_key(h) as usize % SHARDS]
+    }
+
+    fn id(&self, h: &PosT::PositionHash) -> Option<NodeId> {
+        self.shard(h).read().unwrap().get(h).copied()
+    }
+
+    fn node(&self, h: &PosT::PositionHash) -> Option<&Node> {
+        self.id(h).map(|id| self.arena.get(id))
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    // Bytes a node takes with `edges` edges, counting its entry in the index
+    fn node_bytes(edges: usize) -> usize {
+        std::mem::size_of::<Node>() + std::mem::size_of::<(PosT::PositionHash, NodeId)>() + 1
+            + edges * std::mem::size_of::<Edge>()
+    }
+
+    fn memory(&self) -> MemoryUsage {
+        let nodes = self.len();
+        MemoryUsage {
+            nodes,
+            bytes: nodes * Self::node_bytes(0) + self.edges.load(Ordering::Relaxed) * std::mem::size_of::<Edge>(),
+        }
+    }
+
+    // `evaluation` is the evaluator's score divided by its saturation. A position
+    // already in the tree keeps its node.
+    fn make_node(&self, pos: &PosT, evaluation: f64) -> NodeId {
         let pos_hash = pos.to_hash();
-        if let Some(existing_node) = self.nodes.get_mut(&pos_hash) {
-            if let Some(p) = parent {
-                existing_node.parents.insert(p.to_hash());
-            }
-            return
-        }
-        let n = Node{
-            parents: parent.into_iter().map(ag::AbstractGame::to_hash).collect(),
-            evaluation: clamp(evaluator.evaluate_position(pos) / evaluator.saturation()),
-            visits: 0,
-            reward: 0.0,
-            children: HashMap::new(),
-            is_populated: false,
+        let mut shard = self.shard(&pos_hash).write().unwrap();
+        *shard.entry(pos_hash).or_insert_with(|| {
+            let id = self.arena.alloc();
+            let node = self.arena.get(id);
+            node.evaluation.store(clamp(evaluation).to_bits(), Ordering::Relaxed);
+            if pos.is_lost() {
+                node.prove(Proof::Lost, 0);
+            }
+            self.nodes.fetch_add(1, Ordering::Relaxed);
+            id
+        })
+    }
+
+    fn populate_children<EvalT: ag::Evaluator<PosT>>(
+        &self, pos: &PosT, node: &Node, evaluator: &EvalT, policy: Option<&dyn ag::PolicyEvaluator<PosT>>,
+    ) {
+        // Another thread populating the same node makes this one wait for it
+        node.children.get_or_init(|| {
+            let moves = pos.possible_moves();
+            let priors = policy.map(|p| p.policy(pos, &moves)).unwrap_or_else(|| vec![0.0; moves.len()]);
+            let evaluations = evaluator.evaluate_children(pos, &moves);
+            self.edges.fetch_add(moves.len(), Ordering::Relaxed);
+            moves.iter().zip(evaluations).zip(priors).map(|((mv, evaluation), prior)| {
+                let child = self.make_node(&pos.make_move(mv).unwrap(), evaluation / evaluator.saturation());
+                Edge { child: AtomicU32::new(child), prior: prior as f32, stats: Stats::default() }
+            }).collect()
+        });
+    }
+
+    // The node `edge` leads to, made again if it was evicted
+    fn follow<EvalT: ag::Evaluator<PosT>>(&self, edge: &Edge, child_pos: &PosT, evaluator: &EvalT) -> NodeId {
+        edge.child().unwrap_or_else(|| {
+            let id = self.make_node(child_pos, evaluator.evaluate_position(child_pos) / evaluator.saturation());
+            edge.child.store(id, Ordering::Release);
+            id
+        })
+    }
+
+    fn proof(&self, pos: &PosT) -> Proof {
+        self.node(&pos.to_hash()).expect("node must exist").proof()
+    }
+
+    // Proof of the child `edge` leads to; unknown once evicted
+    fn edge_proof(&self, edge: &Edge) -> (Proof, usize) {
+        edge.child().map(|id| self.arena.get(id))
+            .map_or((Proof::Unknown, 0), |child| (child.proof(), child.proof_depth()))
+    }
+
+    // Proves a populated node won if a child is lost for the opponent, or lost if
+    // every child is won for them. Returns the proof, new or old.
+    fn update_proof(&self, node: &Node) -> Proof {
+        let (proof, Some(edges)) = (node.proof(), node.children.get()) else {
+            return node.proof()
         };
-        self.nodes.insert(pos_hash, n);
+        if proof != Proof::Unknown || edges.is_empty() {
+            return proof
+        }
+        let proofs = edges.iter().map(|e| self.edge_proof(e)).collect::<Vec<_>>();
+        let fastest_win = proofs.iter().filter(|(p, _)| *p == Proof::Lost).map(|(_, d)| d).min();
+        let (proof, depth) = if let Some(depth) = fastest_win {
+            (Proof::Won, depth + 1)
+        } else if proofs.iter().all(|(p, _)| *p == Proof::Won) {
+            (Proof::Lost, proofs.iter().map(|(_, d)| d).max().unwrap() + 1)
+        } else {
+            return Proof::Unknown
+        };
+        node.prove(proof, depth);
+        proof
     }
 
-    fn populate_children<EvalT: ag::Evaluator<PosT>>(&mut self, pos: &PosT, evaluator: &EvalT) {
-        let pos_hash = pos.to_hash();
-        let parent_node = self.nodes.get(&pos_hash).expect("parent node must exist");
-        if parent_node.is_populated { return }
-        let moves = pos.possible_moves();
-        let children =
-            moves.into_iter().map(|mv| {
-                let new_pos = pos.make_move(&mv).unwrap();
-                self.make_node(&new_pos, Some(pos), evaluator);
-                (mv, new_pos.to_hash())
-            }).collect();
-        let parent_mut = self.nodes.get_mut(&pos_hash).unwrap();
-        parent_mut.children = children;
-        parent_mut.is_populated = true;
-    }
-
-    fn update_node(&mut self, pos: &PosT, reward: f64) {
-        let node = self.nodes.get_mut(&pos.to_hash()).expect("node must exist");
-        node.visits+=1;
-        node.reward+=reward;
-    }
-
-    #[allow(dead_code)]
-    fn print_move_tree(&self, pos: &PosT, depth: i32, indent: i32) {
-        let indents = String::from_utf8(vec![b' '; indent as usize]).unwrap();
-        pos.possible_moves().into_iter().for_each(|mv| {
-            let new_pos = pos.make_move(&mv).unwrap();
-            if let Some(node) = self.nodes.get(&new_pos.to_hash()) {
-                eprintln!("{}{} {}({}) #{}", &indents, mv, node.reward, node.evaluation, node.visits);
-                if depth>0 {
-                    self.print_move_tree(&new_pos, depth-1, indent+4);
+    // Indices of the moves of a populated node whose outcome is not proven yet
+    fn unproven_edges(&self, node: &Node) -> Vec<usize> {
+        node.children.get().expect("node must be populated").iter().enumerate()
+            .filter(|(_, e)| self.edge_proof(e).0 == Proof::Unknown)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // The edge among `candidates` of a populated, visited node maximizing Q + U, where
+    // Q is the mover's average reward in the child (`fpu` for unvisited children) and
+    // U = c_puct * P * sqrt(N) / (1 + n), n counting the walks along the edge
+    fn puct_choice(&self, node: &Node, candidates: &[usize], c_puct: f64, fpu: f64) -> Option<usize> {
+        let edges = node.children.get().expect("node must be populated");
+        let sqrt_visits = (node.stats.with_virtual_loss().0 as f64).sqrt();
+        let score = |i: usize| {
+            let edge = &edges[i];
+            let (visits, reward) = edge.child().map_or((0, 0.0), |id| self.arena.get(id).stats.with_virtual_loss());
+            let q = if visits > 0 { -reward / visits as f64 } else { fpu };
+            q + c_puct * edge.prior as f64 * sqrt_visits / (1 + edge.stats.with_virtual_loss().0) as f64
+        };
+        // Ties go to the first move, so the walk does not depend on hash order
+        candidates.iter()
+            .map(|&i| (score(i), i))
+            .fold(None, |best: Option<(f64, usize)>, (s, i)| match best {
+                Some((b, _)) if b >= s => best,
+                _ => Some((s, i)),
+            })
+            .map(|(_, i)| i)
+    }
+
+    // Nodes reachable from `root` through nodes that `keep` allows, indexed by id
+    fn reachable(&self, root: NodeId, keep: impl Fn(NodeId) -> bool) -> Vec<bool> {
+        let mut reached = vec![false; self.arena.len.load(Ordering::Relaxed)];
+        let mut todo = vec![root];
+        while let Some(id) = todo.pop() {
+            if !std::mem::replace(&mut reached[id as usize], true) {
+                let edges = self.arena.get(id).children.get();
+                todo.extend(edges.into_iter().flatten().filter_map(Edge::child).filter(|&c| keep(c)));
+            }
+        }
+        reached
+    }
+
+    // Frees every node not in `kept`; edges to them are marked evicted
+    fn sweep(&mut self, kept: &[bool]) {
+        let mut freed = Vec::new();
+        for shard in &mut self.shards {
+            shard.get_mut().unwrap().retain(|_, &mut id| {
+                kept[id as usize] || { freed.push(id); false }
+            });
+        }
+        // Slots are reused in a fixed order, so that equal seeds still give equal trees
+        freed.sort_unstable();
+        let mut edges = 0;
+        for &id in &freed {
+            edges += self.arena.get(id).children.get().map_or(0, |e| e.len());
+            self.arena.free(id);
+        }
+        *self.nodes.get_mut() -= freed.len();
+        *self.edges.get_mut() -= edges;
+        for shard in &self.shards {
+            for &id in shard.read().unwrap().values() {
+                for edge in self.arena.get(id).children.get().into_iter().flatten() {
+                    if edge.child().is_some_and(|c| !kept[c as usize]) {
+                        edge.child.store(EVICTED, Ordering::Relaxed);
+                    }
                 }
-            } else {
-                eprintln!("{}{} not visited", &indents, mv);
             }
+        }
+    }
+
+    // Make `root` the root: keep only the nodes reachable from it. Returns the
+    // visits carried over, or `None` (leaving the tree alone) if `root` is not in it.
+    fn promote(&mut self, root: &PosT) -> Option<usize> {
+        let root_id = self.id(&root.to_hash())?;
+        let visits = self.arena.get(root_id).stats.visits();
+        let reached = self.reachable(root_id, |_| true);
+        self.sweep(&reached);
+        Some(visits)
+    }
+
+    // Frees the least visited nodes, and those no longer reachable from `root`, until
+    // the tree takes at most `max_bytes`. Walks along the edges to them will make them
+    // again. Proven nodes go last: walks avoid them, so they have few visits, but
+    // ranking the moves needs their proofs.
+    fn evict(&mut self, root: &PosT, max_bytes: usize) {
+        let root_id = self.id(&root.to_hash()).expect("root node must exist");
+        let mut excess = self.memory().bytes.saturating_sub(max_bytes);
+        let mut candidates = self.shards.iter()
+            .flat_map(|shard| shard.read().unwrap().values().copied().collect::<Vec<_>>())
+            .filter(|&id| id != root_id)
+            .map(|id| {
+                let node = self.arena.get(id);
+                (node.proof() != Proof::Unknown, node.stats.visits(), id)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        let mut kept = vec![true; self.arena.len.load(Ordering::Relaxed)];
+        for (_, _, id) in candidates {
+            if excess == 0 {
+                break
+            }
+            kept[id as usize] = false;
+            excess = excess.saturating_sub(Self::node_bytes(self.arena.get(id).children.get().map_or(0, |e| e.len())));
+        }
+        let reached = self.reachable(root_id, |id| kept[id as usize]);
+        self.sweep(&reached);
+    }
+
+    // `pos` and the tree below it for `dump_tree`; `mv` and `visits` are those of the
+    // edge leading here. Proven moves are kept however few walks took them. A position
+    // is expanded only where first reached, so transpositions and cycles add one leaf
+    // each rather than another copy of the subtree.
+    fn dump(&self, pos: &PosT, mv: Option<String>, visits: usize, depth: usize, min_visits: usize,
+            expanded: &mut HashSet<PosT::PositionHash>) -> Option<TreeNode> {
+        let hash = pos.to_hash();
+        let node = self.node(&hash)?;
+        let children = if depth == 0 || !expanded.insert(hash) { Vec::new() } else {
+            self.ranked_children(pos).into_iter()
+                .filter(|m| m.visits >= min_visits || m.proof() != Proof::Unknown)
+                .filter_map(|m| self.dump(&pos.make_move(&m.mv).unwrap(), Some(m.mv), m.visits, depth - 1, min_visits, expanded))
+                .collect()
+        };
+        let node_visits = node.stats.visits();
+        Some(TreeNode {
+            mv,
+            position: pos.to_str(),
+            visits,
+            reward: if node_visits > 0 { node.stats.reward() / node_visits as f64 } else { 0.0 },
+            evaluation: node.evaluation(),
+            proof: match node.proof() {
+                Proof::Won => Some(Outcome::Won),
+                Proof::Lost => Some(Outcome::Lost),
+                Proof::Unknown => None,
+            },
+            children,
+        })
+    }
+
+    // What `ranked_children` sorts on, lowest first. Either key changes by at most 1
+    // per walk.
+    fn rank_key(&self, visits: usize, reward: f64) -> f64 {
+        if self.rank_candidates { -(visits as f64) } else { reward }
+    }
+
+    // Ranks moves for the mover: proven wins first, the fastest first, then unproven
+    // moves by `rank_key`, then proven losses, the slowest first
+    fn move_rank(&self, m: &MoveStats) -> (u8, f64) {
+        match (m.proof(), m.child) {
+            (Proof::Lost, Some(child)) => (0, child.proof_depth() as f64),
+            (Proof::Won, Some(child)) => (2, -(child.proof_depth() as f64)),
+            _ => (1, self.rank_key(m.visits, m.reward)),
+        }
+    }
+
+    // True if `pos` is proven, or if `remaining` more walks cannot change
+    // `choose_best_by_reward`: every walk takes one move of `pos` and changes its
+    // rank key by at most 1, so the runner-up would need more than the whole gap to
+    // overtake.
+    fn is_decided(&self, pos: &PosT, remaining: f64) -> bool {
+        if self.proof(pos) != Proof::Unknown {
+            return true
+        }
+        let mut keys = self.ranked_children(pos).iter()
+            .map(|m| self.rank_key(m.visits, m.reward))
+            .collect::<Vec<_>>();
+        keys.sort_by(f64::total_cmp);
+        keys.len() >= 2 && keys[1] - keys[0] > remaining
+    }
+
+    // Moves of `pos` ordered from the mover's best (a proven win, then the lowest
+    // total reward for the opponent, or the most visits) to worst (a proven loss);
+    // empty unless `pos` was populated.
+    fn ranked_children(&self, pos: &PosT) -> Vec<MoveStats<'_>> {
+        let Some(edges) = self.node(&pos.to_hash()).and_then(|n| n.children.get()) else {
+            return vec![]
+        };
+        let mut ranked = pos.possible_moves().into_iter().zip(edges.iter())
+            .map(|(mv, e)| MoveStats {
+                mv,
+                visits: e.stats.visits(),
+                reward: e.stats.reward(),
+                child: e.child().map(|id| self.arena.get(id)),
+            })
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| {
+            let (a, b) = (self.move_rank(a), self.move_rank(b));
+            a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
         });
+        ranked
     }
 
     fn choose_best_by_reward(&self, pos: &PosT) -> Option<String> {
-        let moves = pos.possible_moves();
-        // Only rank moves whose child nodes were actually visited; fall back to
-        // first legal move if the tree search somehow left all children unvisited.
-        let best = moves.iter()
-            .filter_map(|mv| {
-                let new_pos = pos.make_move(mv).unwrap();
-                let reward = self.nodes.get(&new_pos.to_hash())?.reward;
-                Some((mv.clone(), reward))
-            })
-            .min_by(|a, b| a.1.total_cmp(&b.1));
-        best.map(|(mv, _)| mv).or_else(|| moves.into_iter().next())
+        // Only rank moves that were tried or proven; fall back to the first legal
+        // move if the tree search somehow left them all untried.
+        self.ranked_children(pos).into_iter()
+            .find(|m| m.visits > 0 || m.proof() != Proof::Unknown)
+            .map(|m| m.mv)
+            .or_else(|| pos.possible_moves().into_iter().next())
+    }
+
+    fn principal_variation(&self, pos: &PosT, max_len: usize) -> Vec<String> {
+        analysis::follow_line(pos, max_len, |p| {
+            self.ranked_children(p).into_iter()
+                .find(|m| m.visits > 0 || m.proof() != Proof::Unknown)
+                .map(|m| m.mv)
+        })
     }
 }
 
+// Scores moves for the softmax walk, lower being better for the mover as with any
+// evaluator called on the position after the move
 impl<PosT: ag::AbstractGame> ag::Evaluator<PosT> for MCTSState<PosT> {
     fn saturation(self: &Self) -> f64 {
         return 1.0
     }
+    // Without the move leading here, only the average reward and the evaluator's hint
     fn evaluate_position(self: &Self, pos: &PosT) -> f64 {
-        if let Some(node) = self.nodes.get(&pos.to_hash()) {
-            let parent_visits: usize = node.parents.iter().map(
-                |p| self.nodes.get(p).unwrap().visits).sum();
-            let explore_bonus = (parent_visits as f64 + 1.0).ln() / ((node.visits+1) as f64);
-            let eval_bonus = node.evaluation / ((node.visits+1) as f64);
-            let avg_reward = if node.visits>0 {node.reward/(node.visits as f64)} else {0.0};
-            avg_reward - eval_bonus - explore_bonus
+        if let Some(node) = self.node(&pos.to_hash()) {
+            let (visits, reward) = node.stats.with_virtual_loss();
+            let eval_bonus = node.evaluation() / ((visits+1) as f64);
+            let avg_reward = if visits>0 {reward/(visits as f64)} else {0.0};
+            avg_reward - eval_bonus
         } else {
             return 0.0
         }
     }
+    // The child's average reward, with bonuses shrinking as the move is tried from
+    // this parent
+    fn evaluate_children(&self, pos: &PosT, moves: &[String]) -> Vec<f64> {
+        let Some(node) = self.node(&pos.to_hash()) else { return vec![0.0; moves.len()] };
+        let Some(edges) = node.children.get() else {
+            return moves.iter().map(|mv| self.evaluate_position(&pos.make_move(mv).unwrap())).collect()
+        };
+        let all_moves = pos.possible_moves();
+        let parent_visits = node.stats.with_virtual_loss().0;
+        moves.iter().map(|mv| {
+            let edge = &edges[all_moves.iter().position(|m| m == mv).unwrap()];
+            let Some(child) = edge.child().map(|id| self.arena.get(id)) else {
+                return self.evaluate_position(&pos.make_move(mv).unwrap())
+            };
+            let (visits, reward) = child.stats.with_virtual_loss();
+            let tries = edge.stats.with_virtual_loss().0;
+            let explore_bonus = (parent_visits as f64 + 1.0).ln() / ((tries+1) as f64);
+            let eval_bonus = child.evaluation() / ((tries+1) as f64);
+            let avg_reward = if visits>0 {reward/(visits as f64)} else {0.0};
+            avg_reward - eval_bonus - explore_bonus
+        }).collect()
+    }
+}
+
+/// How a walk picks the next move inside the tree
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /// Sample a child by softmax over `MCTSState::evaluate_position`; every walk
+    /// goes down to the depth limit
+    SoftMax,
+    /// AlphaZero's PUCT over the policy's priors; a walk stops at the first node not
+    /// visited yet. `fpu` (first-play urgency) is the value assumed for unvisited
+    /// children, from the mover's point of view, in -1..1.
+    Puct { c_puct: f64, fpu: f64 },
+}
+
+impl Selection {
+    pub const DEFAULT_C_PUCT: f64 = 1.0;
+    pub const DEFAULT_FPU: f64 = 0.0;
+}
+
+/// Priors from a softmax over the evaluator's scores of the children, as
+/// `SoftMaxStrategy` would pick them
+pub struct EvalPolicy<'a, EvalT> {
+    eval: &'a EvalT,
+    softness: f64,
+}
+
+impl<'a, EvalT> EvalPolicy<'a, EvalT> {
+    pub fn new(eval: &'a EvalT, softness: f64) -> Self {
+        EvalPolicy { eval, softness }
+    }
+}
+
+impl<'a, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> ag::PolicyEvaluator<PosT> for EvalPolicy<'a, EvalT> {
+    fn policy(&self, pos: &PosT, moves: &[String]) -> Vec<f64> {
+        let logits = self.eval.evaluate_children(pos, moves).into_iter()
+            .map(|v| -v / self.eval.saturation() * self.softness)
+            .collect::<Vec<_>>();
+        let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let weights = logits.iter().map(|l| (l - max).exp()).collect::<Vec<_>>();
+        let sum: f64 = weights.iter().sum();
+        weights.into_iter().map(|w| w / sum).collect()
+    }
 }
 
 /// Creates a fresh `MonteCarloTreeSearchStrategy` for each game.
 /// `EvalT` must be `'static + Sync` so it can be held behind a `&'static` reference
 /// and shared across threads without a lifetime parameter on the factory.
-pub struct MctsFactory<PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT> + Sync + 'static> {
+pub struct MctsFactory<PosT: ag::AbstractGame + 'static, EvalT: ag::Evaluator<PosT> + Sync + 'static> {
     pub eval: &'static EvalT,
     pub num_tries: usize,
     pub softness: f64,
     pub max_depth: i32,
+    pub selection: Selection,
+    pub policy: &'static (dyn ag::PolicyEvaluator<PosT> + Sync),
+    pub verbose: bool,
+    pub threads: usize,
+    pub memory_limit: usize,
     _pos: PhantomData<PosT>,
 }
 
-impl<PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT> + Sync + 'static> MctsFactory<PosT, EvalT> {
+impl<PosT: ag::AbstractGame + 'static, EvalT: ag::Evaluator<PosT> + Sync + 'static> MctsFactory<PosT, EvalT> {
     pub fn new(eval: &'static EvalT, num_tries: usize, softness: f64, max_depth: i32) -> Self {
-        MctsFactory { eval, num_tries, softness, max_depth, _pos: PhantomData }
+        MctsFactory { eval, num_tries, softness, max_depth, selection: Selection::SoftMax,
+            policy: &ag::UniformPolicy, verbose: false, threads: 1, memory_limit: DEFAULT_MEMORY_LIMIT,
+            _pos: PhantomData }
+    }
+
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Priors for `Selection::Puct`; uniform by default
+    pub fn policy(mut self, policy: &'static (dyn ag::PolicyEvaluator<PosT> + Sync)) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// `bytes` is clamped to `MIN_MEMORY_LIMIT..=MAX_MEMORY_LIMIT`
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes.clamp(MIN_MEMORY_LIMIT, MAX_MEMORY_LIMIT);
+        self
+    }
+
+    fn make(&self, seed: u64) -> MonteCarloTreeSearchStrategy<'static, PosT, EvalT> {
+        MonteCarloTreeSearchStrategy::new(self.eval, self.num_tries, self.softness, self.max_depth, seed)
+            .selection(self.selection)
+            .policy(self.policy)
+            .verbose(self.verbose)
+            .threads(self.threads)
+            .memory_limit(self.memory_limit)
     }
 }
 
-unsafe impl<PosT: ag::AbstractGame + Sync, EvalT: ag::Evaluator<PosT> + Sync + 'static> Sync
+unsafe impl<PosT: ag::AbstractGame + Sync + 'static, EvalT: ag::Evaluator<PosT> + Sync + 'static> Sync
     for MctsFactory<PosT, EvalT> {}
 
 impl<PosT, EvalT> ag::StrategyFactory<PosT> for MctsFactory<PosT, EvalT>
@@ -140,73 +701,368 @@ where
     PosT: ag::AbstractGame + Send + Sync + 'static,
     EvalT: ag::Evaluator<PosT> + Sync + Send + 'static,
 {
-    fn create(&self) -> Box<dyn strategy::StrategyEngine<PosT>> {
-        Box::new(MonteCarloTreeSearchStrategy::new(self.eval, self.num_tries, self.softness, self.max_depth))
+    fn create(&self, seed: u64) -> Box<dyn strategy::StrategyEngine<PosT> + Send> {
+        Box::new(self.make(seed))
+    }
+    fn create_analysis(&self, seed: u64) -> Option<Box<dyn AnalysisEngine<PosT> + Send>> {
+        Some(Box::new(self.make(seed)))
+    }
+}
+
+// What a walk needs; shared by the search threads
+struct Walker<'s, PosT: ag::AbstractGame, EvalT> {
+    eval: &'s EvalT,
+    policy: &'s (dyn ag::PolicyEvaluator<PosT> + Sync),
+    selection: Selection,
+    softness: f64,
+    max_depth: i32,
+    // Only needed with several threads; a lone walk would just perturb itself
+    virtual_loss: bool,
+    state: &'s MCTSState<PosT>,
+}
+
+impl<'s, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> Walker<'s, PosT, EvalT> {
+    fn walk_once(&self, start_pos: &PosT, rng: &mut StdRng) {
+        let state = self.state;
+        let mut pos = start_pos.clone();
+        let mut id = state.id(&pos.to_hash()).expect("root node must exist");
+        let mut node = state.arena.get(id);
+        // Positions left behind, with the index of the edge taken from each
+        let mut track = Vec::new();
+        let mut on_track = HashSet::new();
+        // The edge back to a position of this walk, if the walk ran into a cycle
+        let mut cycle = None;
+        if self.virtual_loss {
+            node.stats.add_virtual_loss();
+        }
+        let policy = matches!(self.selection, Selection::Puct{..})
+            .then_some(self.policy as &dyn ag::PolicyEvaluator<PosT>);
+        while track.len() < usize::try_from(self.max_depth).unwrap_or(0) {
+            // Proven nodes need no more walks; the root is walked anyway, so that
+            // analysis still ranks its other moves
+            let at_root = track.is_empty();
+            if !at_root && node.proof() != Proof::Unknown {
+                break
+            }
+            // PUCT stops at the first node not visited yet and lets the evaluator score it
+            if policy.is_some() && node.stats.visits() == 0 {
+                break
+            }
+            state.populate_children(&pos, node, self.eval, policy);
+            if state.update_proof(node) != Proof::Unknown && !at_root {
+                break
+            }
+            let candidates = state.unproven_edges(node);
+            let choice = match self.selection {
+                Selection::SoftMax => {
+                    let moves = pos.possible_moves();
+                    let unproven = candidates.iter().map(|&i| moves[i].clone()).collect();
+                    strategy::SoftMaxStrategy::new(state, self.softness, &mut *rng).choose_from(&pos, unproven)
+                        .map(|mv| moves.iter().position(|m| *m == mv).unwrap())
+                }
+                Selection::Puct { c_puct, fpu } => state.puct_choice(node, &candidates, c_puct, fpu),
+            };
+            let Some(i) = choice else { break };
+            let edge = &node.children.get().unwrap()[i];
+            if self.virtual_loss {
+                edge.stats.add_virtual_loss();
+            }
+            let pos1 = pos.make_move(&pos.possible_moves()[i]).unwrap();
+            let id1 = state.follow(edge, &pos1, self.eval);
+            // Going around a cycle is scored as a draw, and ends the walk
+            on_track.insert(id);
+            if on_track.contains(&id1) {
+                cycle = Some(i);
+                break
+            }
+            let node1 = state.arena.get(id1);
+            if self.virtual_loss {
+                node1.stats.add_virtual_loss();
+            }
+            track.push((pos, node, i));
+            (pos, id, node) = (pos1, id1, node1);
+        }
+        let player_final = pos.current_player();
+        let ev_final = match (cycle, node.proof()) {
+            (Some(_), _) => 0.0,
+            (None, Proof::Won) => 1.0,
+            (None, Proof::Lost) => -1.0,
+            (None, Proof::Unknown) => self.eval.evaluate_position(&pos)/self.eval.saturation(),
+        };
+        let ev = |p: &PosT| if p.current_player() == player_final {ev_final} else {-ev_final};
+        if let Some(i) = cycle {
+            node.children.get().unwrap()[i].stats.add(0.0, self.virtual_loss);
+        }
+        // Each edge gets the reward of the position it leads to
+        let mut child_ev = ev(&pos);
+        state.update_proof(node);
+        node.stats.add(child_ev, self.virtual_loss);
+        for (p, n, i) in track.into_iter().rev() {
+            n.children.get().unwrap()[i].stats.add(child_ev, self.virtual_loss);
+            child_ev = ev(&p);
+            state.update_proof(n);
+            n.stats.add(child_ev, self.virtual_loss);
+        }
     }
 }
 
+/// Tree-parallel with `threads > 1`: the threads walk the same tree, and a walk in
+/// progress counts as a loss in the nodes it passes (virtual loss) so that the
+/// others spread out.
 pub struct MonteCarloTreeSearchStrategy<'a, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> {
     num_tries: usize,
     softness: f64,
     max_depth: i32,
     eval: &'a EvalT,
+    selection: Selection,
+    policy: &'a (dyn ag::PolicyEvaluator<PosT> + Sync),
+    verbose: bool,
+    threads: usize,
+    memory_limit: usize,
+    // Drives the softmax walks; seeded, so equal seeds give equal trees (with one thread)
+    rng: StdRng,
+    // The tree of the last search, kept for the next one
+    tree: Option<MCTSState<PosT>>,
+    carried_visits: usize,
+    walks: usize,
     phantom_data: PhantomData<PosT>,
 }
 
-impl<'a, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> MonteCarloTreeSearchStrategy<'a, PosT, EvalT> {
-    pub fn new(eval: &'a EvalT, num_tries: usize, softness: f64, max_depth: i32) -> Self {
-        return MonteCarloTreeSearchStrategy{eval: eval, num_tries: num_tries, softness: softness, max_depth, phantom_data: PhantomData}
+impl<'a, PosT: ag::AbstractGame + Sync, EvalT: ag::Evaluator<PosT> + Sync> MonteCarloTreeSearchStrategy<'a, PosT, EvalT> {
+    pub fn new(eval: &'a EvalT, num_tries: usize, softness: f64, max_depth: i32, seed: u64) -> Self {
+        return MonteCarloTreeSearchStrategy{eval: eval, num_tries: num_tries, softness: softness, max_depth,
+            selection: Selection::SoftMax, policy: &ag::UniformPolicy, verbose: false, threads: 1,
+            memory_limit: DEFAULT_MEMORY_LIMIT, rng: StdRng::seed_from_u64(seed), tree: None, carried_visits: 0, walks: 0, phantom_data: PhantomData}
     }
 
-    fn walk_once(&mut self, start_pos: &PosT, state: &mut MCTSState<PosT>) {
-        let mut pos = start_pos.clone();
-        let mut track = Vec::new();
-        let mut track_moves = Vec::new();
-        while track.len() < self.max_depth.try_into().unwrap() {
-            if pos.is_lost() {
-                break
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Priors for `Selection::Puct`; uniform by default
+    pub fn policy(mut self, policy: &'a (dyn ag::PolicyEvaluator<PosT> + Sync)) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Print the visits carried over from the previous search to stderr
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Bytes the tree may take; past them the least visited nodes are evicted.
+    /// Clamped to `MIN_MEMORY_LIMIT..=MAX_MEMORY_LIMIT`.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes.clamp(MIN_MEMORY_LIMIT, MAX_MEMORY_LIMIT);
+        self
+    }
+
+    /// Walks made by all threads during the last search
+    pub fn walks(&self) -> usize {
+        self.walks
+    }
+
+    /// Visits the last search started with, inherited from the search before it
+    pub fn carried_visits(&self) -> usize {
+        self.carried_visits
+    }
+
+    /// Size of the tree kept from the last search
+    pub fn memory(&self) -> MemoryUsage {
+        self.tree.as_ref().map(MCTSState::memory).unwrap_or_default()
+    }
+
+    // The kept tree rerooted at `pos` if `pos` was reached in it, otherwise a new one
+    fn take_tree(&mut self, pos: &PosT) -> MCTSState<PosT> {
+        let mut tree = self.tree.take();
+        self.carried_visits = tree.as_mut().and_then(|t| t.promote(pos)).unwrap_or(0);
+        if self.verbose {
+            eprintln!("MCTS: {} visits carried over", self.carried_visits);
+        }
+        match tree {
+            Some(tree) if self.carried_visits > 0 => tree,
+            _ => {
+                let tree = MCTSState::new(matches!(self.selection, Selection::Puct{..}));
+                tree.make_node(pos, self.eval.evaluate_position(pos) / self.eval.saturation());
+                tree
+            }
+        }
+    }
+
+    // Grow the tree for `pos` within `limits`, starting from what the previous search
+    // left below `pos`. With `stop_when_decided`, also stop as soon as the remaining
+    // walks could not change the chosen move. The caller puts the tree back.
+    fn search(&mut self, pos: &PosT, limits: &SearchLimits, stop_when_decided: bool) -> MCTSState<PosT> {
+        // `nodes` counts walks; with only a time budget, walk until the clock runs out
+        let start = Instant::now();
+        let deadline = limits.deadline(start);
+        let num_tries = limits.nodes.unwrap_or(
+            if limits.is_budgeted() { usize::MAX } else { self.num_tries });
+        let max_depth = limits.depth.unwrap_or(self.max_depth);
+        let mut state = self.take_tree(pos);
+        // Walks are numbered from 1 as threads claim them
+        let claimed = AtomicUsize::new(1);
+        let mut helper_rngs = (1..self.threads).map(|_| StdRng::seed_from_u64(self.rng.gen())).collect::<Vec<_>>();
+        self.walks = 0;
+        // This thread's walks over all rounds, so that it checks the limits as often
+        // however the rounds split them
+        let mut own_walks = 0;
+        // Walk in rounds: a round that fills the memory limit ends early, and the
+        // next one starts once the tree has shed its least visited nodes
+        loop {
+            let walker = Walker { eval: self.eval, policy: self.policy, selection: self.selection,
+                softness: self.softness, max_depth, virtual_loss: self.threads > 1, state: &state };
+            let done = AtomicBool::new(false);
+            let rng = &mut self.rng;
+            let (walks, full) = std::thread::scope(|scope| {
+                let helpers = helper_rngs.drain(..).map(|mut helper_rng| {
+                    let (walker, claimed, done) = (&walker, &claimed, &done);
+                    scope.spawn(move || {
+                        let mut walks = 0;
+                        while !done.load(Ordering::Relaxed) && claimed.fetch_add(1, Ordering::Relaxed) < num_tries {
+                            walker.walk_once(pos, &mut helper_rng);
+                            walks += 1;
+                        }
+                        (walks, helper_rng)
+                    })
+                }).collect::<Vec<_>>();
+                // This thread also watches the limits
+                let mut round_walks = 0;
+                let mut full = false;
+                while claimed.fetch_add(1, Ordering::Relaxed) < num_tries {
+                    walker.walk_once(pos, rng);
+                    own_walks += 1;
+                    round_walks += 1;
+                    if stop_when_decided && state.proof(pos) != Proof::Unknown {
+                        break
+                    }
+                    // A walk ending the round checks the limits too, or a tree filling
+                    // up quickly would never let them stop the search
+                    let filled = state.memory().bytes > self.memory_limit;
+                    if filled || own_walks % 64 == 0 {
+                        if limits.is_stopped() || deadline.is_some_and(|d| Instant::now() >= d) {
+                            break
+                        }
+                        let i = claimed.load(Ordering::Relaxed).min(num_tries);
+                        let remaining_by_time = deadline.map(|d| {
+                            let elapsed = start.elapsed().as_secs_f64().max(1e-9);
+                            i as f64 * d.saturating_duration_since(Instant::now()).as_secs_f64() / elapsed
+                        });
+                        let remaining = remaining_by_time.unwrap_or(f64::INFINITY)
+                            .min((num_tries - i) as f64);
+                        if stop_when_decided && state.is_decided(pos, remaining) {
+                            break
+                        }
+                    }
+                    if filled {
+                        full = true;
+                        break
+                    }
+                }
+                done.store(true, Ordering::Relaxed);
+                let walks = round_walks + helpers.into_iter().map(|h| {
+                    let (walks, helper_rng) = h.join().unwrap();
+                    helper_rngs.push(helper_rng);
+                    walks
+                }).sum::<usize>();
+                (walks, full)
+            });
+            self.walks += walks;
+            // Also when the limits stopped the round, so that the kept tree fits too.
+            // Leave room for a while before the next eviction.
+            if state.memory().bytes > self.memory_limit {
+                state.evict(pos, self.memory_limit / 4 * 3);
             }
-            state.populate_children(&pos, self.eval);
-            let mut softmax =
-                strategy::SoftMaxStrategy::new(&*state, self.softness);
-            if let Some(choice) = softmax.choose_move(&pos) {
-                let pos1 = pos.make_move(&choice).unwrap();
-                //eprintln!("move={} pos1={}", choice, pos1.to_str());
-                track.push(pos);
-                track_moves.push(choice);
-                pos = pos1
-            } else {
+            if !full {
                 break
             }
         }
-        let player_final = pos.current_player();
-        let ev_final = self.eval.evaluate_position(&pos)/self.eval.saturation();
-        //eprintln!("moves: {:?} player_final: {} ev_final: {}", track_moves, player_final, ev_final);
-        track.push(pos);
-        track.into_iter().rev().for_each(|p| {
-            let ev = if p.current_player() == player_final {ev_final} else {-ev_final};
-            state.update_node(&p, ev)
-        })
+        if self.verbose {
+            let usage = state.memory();
+            eprintln!("MCTS: {} nodes, {:.1} MiB", usage.nodes, usage.bytes as f64 / (1 << 20) as f64);
+        }
+        state
     }
 }
 
-impl<'a, PosT: ag::AbstractGame, EvalT: ag::Evaluator<PosT>> strategy::StrategyEngine<PosT> for MonteCarloTreeSearchStrategy<'a, PosT, EvalT> {
+impl<'a, PosT: ag::AbstractGame + Sync, EvalT: ag::Evaluator<PosT> + Sync> strategy::StrategyEngine<PosT> for MonteCarloTreeSearchStrategy<'a, PosT, EvalT> {
     fn choose_move(&mut self, pos: &PosT) -> Option<String> {
-        let mut state = MCTSState{ nodes: HashMap::new(), phantom_data: PhantomData };
-        state.make_node(pos,None, self.eval);
-        for _ in 1..self.num_tries {
-            self.walk_once(pos, &mut state)
+        self.choose_move_with_limits(pos, &SearchLimits::default())
+    }
+
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        let moves = pos.possible_moves();
+        if moves.len() == 1 {
+            return moves.into_iter().next()  // nothing to think about
         }
-        //state.print_move_tree(pos, 2, 0);
-        state.choose_best_by_reward(pos)
+        let state = self.search(pos, limits, true);
+        let mv = state.choose_best_by_reward(pos);
+        self.tree = Some(state);
+        mv
+    }
+
+    /// Grows the tree for the opponent's move, to be picked up by the next search
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        let state = self.search(pos, limits, false);
+        self.tree = Some(state);
+    }
+
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        let tree = self.tree.as_ref()?;
+        let visits = tree.node(&pos.to_hash())?.stats.visits();
+        tree.dump(pos, None, visits, depth, min_visits, &mut HashSet::new())
+    }
+}
+
+impl<'a, PosT: ag::AbstractGame + Sync, EvalT: ag::Evaluator<PosT> + Sync> AnalysisEngine<PosT> for MonteCarloTreeSearchStrategy<'a, PosT, EvalT> {
+    fn analyze(&mut self, pos: &PosT, num_pv: usize, limits: &SearchLimits) -> Vec<MoveAnalysis> {
+        let state = self.search(pos, limits, false);
+        let max_len = limits.depth.unwrap_or(self.max_depth).max(1) as usize;
+        let lines = state.ranked_children(pos).into_iter()
+            .filter(|m| m.visits > 0 || m.proof() != Proof::Unknown)
+            .take(num_pv)
+            .map(|m| {
+                let child = pos.make_move(&m.mv).unwrap();
+                let mut pv = vec![m.mv.clone()];
+                pv.extend(state.principal_variation(&child, max_len - 1));
+                let score = match m.proof() {
+                    Proof::Lost => 1.0,
+                    Proof::Won => -1.0,
+                    Proof::Unknown => -m.reward / m.visits as f64,
+                };
+                MoveAnalysis {
+                    mv: m.mv,
+                    score,
+                    visits: Some(m.visits),
+                    depth: None,
+                    pv,
+                }
+            })
+            .collect();
+        self.tree = Some(state);
+        lines
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::{abstract_game::{tests as agt, AbstractGame}, strategy::{self, StrategyEngine}};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
 
-    use super::MonteCarloTreeSearchStrategy;
+    use crate::{abstract_game::{tests as agt, AbstractGame}, strategy::{self, SearchLimits, StrategyEngine}};
+    use crate::kids_shogi::{KidsShogiGame, SimpleEvaluator};
+
+    use crate::analysis::AnalysisEngine;
+
+    use super::{EvalPolicy, MCTSState, MonteCarloTreeSearchStrategy, NodeId, Proof, Selection, MIN_MEMORY_LIMIT};
 
     // This is a somewhat probabilistic test but it succesfully solves OneTwoGame
     #[test]
@@ -214,8 +1070,305 @@ pub mod tests {
         let pos = agt::OneTwoGame::from_str("8 0").unwrap();
         let eval = strategy::OneStepEvaluator::<agt::OneTwoGame>::new();
         let mut strat = MonteCarloTreeSearchStrategy::new(
-            &eval, 32, 3.0, 8);
+            &eval, 32, 3.0, 8, 1);
         let mv = strat.choose_move(&pos);
         assert_eq!(mv.unwrap(), "2");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn honours_limits() {
+        let pos = KidsShogiGame::initial();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, usize::MAX, 3.0, 8, 1);
+
+        let limits = SearchLimits::with_movetime(Some(Duration::from_millis(50)));
+        let t0 = Instant::now();
+        assert!(strat.choose_move_with_limits(&pos, &limits).is_some());
+        assert!(t0.elapsed() < Duration::from_secs(5));
+
+        let stopped = SearchLimits::default();
+        stopped.stop.store(true, Ordering::Relaxed);
+        let t0 = Instant::now();
+        assert!(strat.choose_move_with_limits(&pos, &stopped).is_some());
+        assert!(t0.elapsed() < Duration::from_secs(5));
+
+        let few_nodes = SearchLimits { nodes: Some(10), ..Default::default() };
+        assert!(strat.choose_move_with_limits(&pos, &few_nodes).is_some());
+    }
+
+    #[test]
+    fn analysis_ranks_lion_capture_first() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 500, 3.0, 8, 1);
+        let lines = strat.analyze(&pos, 2, &SearchLimits::default());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].mv, "a3a4");
+        assert_eq!(lines[0].pv, vec!["a3a4".to_string()]);  // the game ends there
+        // Proven won by the solver before it is ever walked into
+        assert_eq!(lines[0].score, 1.0);
+        assert!(lines[1].visits.is_some_and(|v| v > 0));
+    }
+
+    #[test]
+    fn same_seed_same_tree() {
+        let pos = KidsShogiGame::initial();
+        let eval = SimpleEvaluator{};
+        let analyze = |seed| MonteCarloTreeSearchStrategy::new(&eval, 300, 3.0, 8, seed)
+            .analyze(&pos, 4, &SearchLimits::default());
+        assert_eq!(analyze(5), analyze(5));
+        assert_ne!(analyze(5), analyze(6));
+    }
+
+    #[test]
+    fn reuses_the_subtree_of_the_reply() {
+        let pos = KidsShogiGame::initial();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 500, 3.0, 8, 1);
+        let after_move = pos.make_move(&strat.choose_move(&pos).unwrap()).unwrap();
+        assert_eq!(strat.carried_visits(), 0);
+        // The reply the tree looked at most
+        let tree = strat.tree.as_ref().unwrap();
+        let (expected, reply) = after_move.possible_moves().into_iter()
+            .map(|mv| after_move.make_move(&mv).unwrap())
+            .filter_map(|p| Some((tree.node(&p.to_hash())?.stats.visits(), p)))
+            .max_by_key(|(visits, _)| *visits)
+            .unwrap();
+        assert!(expected > 0);
+        // Promoting the reply drops the rest of the tree, and no move leads there
+        let mut tree = strat.tree.take().unwrap();
+        assert_eq!(tree.promote(&reply), Some(expected));
+        let kept = node_ids(&tree);
+        assert!(kept.iter().all(|&id| tree.arena.get(id).children.get().into_iter().flatten()
+            .all(|e| e.child().is_none_or(|c| kept.contains(&c)))));
+        strat.tree = Some(tree);
+        strat.choose_move(&reply).unwrap();
+        assert_eq!(strat.carried_visits(), expected);
+
+        // A position the tree never reached starts over
+        strat.choose_move(&KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap()).unwrap();
+        assert_eq!(strat.carried_visits(), 0);
+
+        // Pondering on the opponent's turn carries over into the answer
+        let mut pondering = MonteCarloTreeSearchStrategy::new(&eval, 500, 3.0, 8, 1);
+        pondering.ponder(&after_move, &SearchLimits::default());
+        pondering.choose_move(&reply).unwrap();
+        assert!(pondering.carried_visits() > 0);
+
+        // Without cycles, positions the reply cannot reach are dropped
+        let pos = agt::OneTwoGame::from_str("10 0").unwrap();
+        let eval = strategy::OneStepEvaluator::<agt::OneTwoGame>::new();
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 100, 3.0, 8, 1);
+        strat.choose_move(&pos).unwrap();
+        let mut tree = strat.tree.take().unwrap();
+        let old_size = tree.len();
+        assert!(tree.promote(&agt::OneTwoGame::from_str("8 0").unwrap()).is_some());
+        assert!(tree.len() < old_size);
+        assert!(tree.node(&agt::OneTwoGame::from_str("9 1").unwrap().to_hash()).is_none());
+    }
+
+    #[test]
+    fn parallel_walks_share_the_tree() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let eval = SimpleEvaluator{};
+        let puct = Selection::Puct { c_puct: Selection::DEFAULT_C_PUCT, fpu: Selection::DEFAULT_FPU };
+        for selection in [Selection::SoftMax, puct] {
+            let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 400, 3.0, 8, 1).selection(selection).threads(4);
+            let lines = strat.analyze(&pos, 1, &SearchLimits::default());
+            assert_eq!(lines[0].mv, "a3a4", "{:?}", selection);
+            // Every claimed walk ran once, and every virtual loss was taken back
+            assert_eq!(strat.walks(), 399);
+            let tree = strat.tree.as_ref().unwrap();
+            assert!(node_ids(tree).into_iter().map(|id| tree.arena.get(id))
+                .all(|n| n.stats.virtual_loss.load(Ordering::Relaxed) == 0
+                    && n.children.get().into_iter().flatten()
+                        .all(|e| e.stats.virtual_loss.load(Ordering::Relaxed) == 0)));
+        }
+    }
+
+    #[test]
+    fn puct_solves_reference_positions() {
+        let pos = agt::OneTwoGame::from_str("8 0").unwrap();
+        let eval = strategy::OneStepEvaluator::<agt::OneTwoGame>::new();
+        let policy = EvalPolicy::new(&eval, 3.0);
+        let puct = Selection::Puct { c_puct: Selection::DEFAULT_C_PUCT, fpu: Selection::DEFAULT_FPU };
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 32, 3.0, 8, 1).selection(puct);
+        assert_eq!(strat.choose_move(&pos).unwrap(), "2");
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 32, 3.0, 8, 1).selection(puct).policy(&policy);
+        assert_eq!(strat.choose_move(&pos).unwrap(), "2");
+
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 200, 3.0, 8, 1).selection(puct);
+        let lines = strat.analyze(&pos, 2, &SearchLimits::default());
+        assert_eq!(lines[0].mv, "a3a4");
+        assert_eq!(lines[0].score, 1.0);
+    }
+
+    #[test]
+    fn solver_proves_wins_and_losses() {
+        let eval = strategy::OneStepEvaluator::<agt::OneTwoGame>::new();
+        let lost = agt::OneTwoGame::from_str("3 0").unwrap();
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 200, 3.0, 8, 1);
+        strat.choose_move(&lost).unwrap();
+        let tree = strat.tree.as_ref().unwrap();
+        assert_eq!(tree.proof(&lost), Proof::Lost);
+        assert_eq!(tree.node(&lost.to_hash()).unwrap().proof_depth(), 2);  // either move, then the last stones
+
+        let won = agt::OneTwoGame::from_str("4 0").unwrap();
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 200, 3.0, 8, 1);
+        assert_eq!(strat.choose_move(&won).unwrap(), "1");
+        assert_eq!(strat.tree.as_ref().unwrap().proof(&won), Proof::Won);
+        // Once the root is proven, the remaining walks are not needed
+        assert!(strat.walks() < 199);
+
+        // Never walks into a capture of its own lion, whatever the seed
+        let pos = KidsShogiGame::from_fen("3/1l1/3/1L1 b -").unwrap();
+        let eval = SimpleEvaluator{};
+        for seed in 0..4 {
+            let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 300, 3.0, 8, seed);
+            let mv = strat.choose_move(&pos).unwrap();
+            let reply = pos.make_move(&mv).unwrap();
+            assert!(reply.possible_moves().iter().all(|r| !reply.make_move(r).unwrap().is_lost()), "{}", mv);
+        }
+    }
+
+    fn node_ids<PosT: AbstractGame>(tree: &MCTSState<PosT>) -> HashSet<NodeId> {
+        tree.shards.iter().flat_map(|shard| shard.read().unwrap().values().copied().collect::<Vec<_>>()).collect()
+    }
+
+    // Walks along the moves into each node
+    fn incoming_visits<PosT: AbstractGame>(tree: &MCTSState<PosT>) -> HashMap<NodeId, usize> {
+        let mut incoming = HashMap::new();
+        for id in node_ids(tree) {
+            for edge in tree.arena.get(id).children.get().into_iter().flatten() {
+                *incoming.entry(edge.child().unwrap()).or_insert(0) += edge.stats.visits();
+            }
+        }
+        incoming
+    }
+
+    #[test]
+    fn transposed_visits_add_up() {
+        // Taking 1 then 2 or 2 then 1 reaches the same heap: nearly every node has
+        // several parents
+        let pos = agt::OneTwoGame::from_str("10 0").unwrap();
+        let eval = strategy::OneStepEvaluator::<agt::OneTwoGame>::new();
+        let puct = Selection::Puct { c_puct: Selection::DEFAULT_C_PUCT, fpu: Selection::DEFAULT_FPU };
+        for selection in [Selection::SoftMax, puct] {
+            let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 300, 3.0, 20, 1).selection(selection);
+            strat.analyze(&pos, 1, &SearchLimits::default());
+            let tree = strat.tree.as_ref().unwrap();
+            let incoming = incoming_visits(tree);
+            assert_eq!(tree.node(&pos.to_hash()).unwrap().stats.visits(), strat.walks());
+            // Every visit of a node came along exactly one of the moves into it
+            let root = tree.id(&pos.to_hash()).unwrap();
+            for id in node_ids(tree).into_iter().filter(|&id| id != root) {
+                assert_eq!(tree.arena.get(id).stats.visits(), incoming[&id], "{:?}", selection);
+            }
+            assert_eq!(strat.choose_move(&pos).unwrap(), "1", "{:?}", selection);
+        }
+    }
+
+    #[test]
+    fn cycles_are_draws() {
+        // Lions alone can walk back and forth forever
+        let pos = KidsShogiGame::from_fen("l2/3/3/2L b -").unwrap();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 300, 3.0, 60, 1);
+        let lines = strat.analyze(&pos, 8, &SearchLimits::default());
+        let tree = strat.tree.as_ref().unwrap();
+        // A walk coming back to a position stops there instead of counting it again
+        assert_eq!(tree.node(&pos.to_hash()).unwrap().stats.visits(), strat.walks());
+        assert_eq!(lines.iter().map(|l| l.visits.unwrap()).sum::<usize>(), strat.walks());
+        // ... though the move that closed the cycle is counted
+        assert!(incoming_visits(tree).get(&tree.id(&pos.to_hash()).unwrap()).is_some_and(|&v| v > 0));
+    }
+
+    #[test]
+    fn dumps_the_searched_tree() {
+        let pos = KidsShogiGame::initial();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, 300, 3.0, 8, 1);
+        assert!(strat.dump_tree(&pos, 2, 1).is_none());  // nothing searched yet
+        let mv = strat.choose_move(&pos).unwrap();
+        let tree = strat.dump_tree(&pos, 2, 5).unwrap();
+        assert_eq!(tree.position, pos.to_str());
+        assert_eq!(tree.mv, None);
+        assert_eq!(tree.visits, strat.walks());
+        assert_eq!(tree.children[0].mv.as_ref(), Some(&mv));
+        let below = tree.children.iter().flat_map(|c| &c.children).collect::<Vec<_>>();
+        assert!(!below.is_empty());
+        assert!(below.iter().all(|n| (n.visits >= 5 || n.proof.is_some()) && n.children.is_empty()));
+
+        // However deep, each position is expanded once
+        fn expanded<'a>(node: &'a crate::tree_dump::TreeNode, out: &mut Vec<&'a str>) {
+            if !node.children.is_empty() {
+                out.push(&node.position);
+            }
+            node.children.iter().for_each(|c| expanded(c, out));
+        }
+        let deep = strat.dump_tree(&pos, 1000, 1).unwrap();
+        let mut positions = Vec::new();
+        expanded(&deep, &mut positions);
+        assert_eq!(positions.iter().collect::<HashSet<_>>().len(), positions.len());
+    }
+
+    #[test]
+    fn evicts_least_visited_nodes_to_stay_within_memory() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let eval = SimpleEvaluator{};
+        let mut unbounded = MonteCarloTreeSearchStrategy::new(&eval, 400, 3.0, 8, 1);
+        unbounded.analyze(&pos, 1, &SearchLimits::default());
+        let full = unbounded.memory();
+        assert!(full.nodes > 0 && full.bytes > full.nodes);
+
+        let limit = full.bytes / 4;
+        assert!(limit >= MIN_MEMORY_LIMIT);
+        let mut bounded = MonteCarloTreeSearchStrategy::new(&eval, 400, 3.0, 8, 1).memory_limit(limit);
+        let lines = bounded.analyze(&pos, 3, &SearchLimits::default());
+        assert_eq!(lines[0].mv, "a3a4");
+        // Eviction keeps equal seeds giving equal trees
+        let mut again = MonteCarloTreeSearchStrategy::new(&eval, 400, 3.0, 8, 1).memory_limit(limit);
+        assert_eq!(again.analyze(&pos, 3, &SearchLimits::default()), lines);
+        // Evicted nodes are made again when walks come back, so the search runs to the end
+        assert_eq!(bounded.walks(), 399);
+        let usage = bounded.memory();
+        assert!(usage.nodes < full.nodes);
+        assert!(usage.bytes <= limit, "{:?} over {}", usage, limit);
+
+        // Nodes left behind by eviction are not kept: every node is reachable
+        let tree = bounded.tree.as_ref().unwrap();
+        assert_eq!(tree.reachable(tree.id(&pos.to_hash()).unwrap(), |_| true).iter().filter(|&&r| r).count(), usage.nodes);
+        assert_eq!(usage, tree.memory());
+    }
+
+    #[test]
+    fn a_full_tree_still_honours_limits() {
+        // The smallest tree fills up within a few walks, every round
+        let pos = KidsShogiGame::initial();
+        let eval = SimpleEvaluator{};
+        let mut strat = MonteCarloTreeSearchStrategy::new(&eval, usize::MAX, 3.0, 8, 1).memory_limit(0);
+        let limits = SearchLimits::with_movetime(Some(Duration::from_millis(100)));
+        let t0 = Instant::now();
+        assert!(strat.choose_move_with_limits(&pos, &limits).is_some());
+        assert!(t0.elapsed() < Duration::from_secs(5));
+        assert!(strat.memory().bytes <= MIN_MEMORY_LIMIT);
+
+        let stopped = SearchLimits::default();
+        stopped.stop.store(true, Ordering::Relaxed);
+        assert!(strat.choose_move_with_limits(&pos, &stopped).is_some());
+    }
+
+    #[test]
+    fn puct_beats_softmax() {
+        // Same number of walks; PUCT's are shorter, so this is not even a fair fight
+        let eval = SimpleEvaluator{};
+        let puct = Selection::Puct { c_puct: Selection::DEFAULT_C_PUCT, fpu: Selection::DEFAULT_FPU };
+        let score = crate::skill::match_score::<KidsShogiGame, _>(|player, game| {
+            let strat = MonteCarloTreeSearchStrategy::new(&eval, 150, 3.0, 8, game * 2 + player as u64);
+            if player == 0 { strat.selection(puct) } else { strat }
+        }, 4, 100);
+        assert!(score >= 0.75, "PUCT scored {}", score);
+    }
+}
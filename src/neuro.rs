@@ -11,7 +11,9 @@ use dfdx::{
     tensor::Cpu,
     tensor_ops::Backward,
 };
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 
 use crate::abstract_game::{self as ag, Evaluator};
 use crate::mcts::MonteCarloTreeSearchStrategy;
@@ -65,6 +67,36 @@ impl<PosT: ag::NeuroPosition, const IN: usize> ag::Evaluator<PosT> for NeuroEval
     fn saturation(&self) -> f64 { 1.0 }
 }
 
+/// Network handle that hides the encoding length, so that code generic over
+/// the position type can create, load, save and train models.
+pub trait NeuroModel<PosT: ag::AbstractGame>: ag::Evaluator<PosT> + Sized {
+    fn fresh() -> Self;
+    fn load(path: &str) -> io::Result<Self>;
+    fn save_to(&self, path: &str) -> io::Result<()>;
+    fn train_epoch<EvalT: ag::Evaluator<PosT> + Sync>(
+        &mut self,
+        self_play_eval: &EvalT,
+        params: &TrainParameters,
+        epoch: usize,
+        model_file: &str,
+    ) -> io::Result<()>;
+}
+
+impl<PosT: ag::NeuroPosition, const IN: usize> NeuroModel<PosT> for NeuroEvaluator<PosT, IN> {
+    fn fresh() -> Self { Self::new() }
+    fn load(path: &str) -> io::Result<Self> { load_model(path) }
+    fn save_to(&self, path: &str) -> io::Result<()> { save_model(self, path) }
+    fn train_epoch<EvalT: ag::Evaluator<PosT> + Sync>(
+        &mut self,
+        self_play_eval: &EvalT,
+        params: &TrainParameters,
+        epoch: usize,
+        model_file: &str,
+    ) -> io::Result<()> {
+        train_epoch(self_play_eval, self, params, epoch, model_file)
+    }
+}
+
 // ── TrainParameters ───────────────────────────────────────────────────────────
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
@@ -87,6 +119,12 @@ pub struct TrainParameters {
     pub batch_size: usize,
     /// Training passes over the sampled subset
     pub training_epochs: usize,
+    /// SGD step size of the NNUE trainer (the dense network uses Adam's default)
+    #[serde(default = "default_learning_rate")]
+    pub learning_rate: f64,
+    /// Seed for self-play and sampling; epoch `n` uses `seed + n`. Random when absent
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 impl Default for TrainParameters {
@@ -101,10 +139,16 @@ impl Default for TrainParameters {
             training_subset: 5000,
             batch_size: 64,
             training_epochs: 20,
+            learning_rate: default_learning_rate(),
+            seed: None,
         }
     }
 }
 
+fn default_learning_rate() -> f64 {
+    0.01
+}
+
 // ── Self-play ─────────────────────────────────────────────────────────────────
 
 enum PlayResult {
@@ -114,46 +158,47 @@ enum PlayResult {
 }
 
 /// Play one game using `eval` wrapped in FindWinningMove + MCTS.
-/// Returns (hash, encoding, score) for every position visited;
+/// Returns (hash, position, score) for every position visited;
 /// score = ±decay^(distance_from_end), or 0.0 for draws.
 fn play_game<PosT, EvalT>(
     eval: &EvalT,
     params: &TrainParameters,
-) -> (Vec<(PosT::PositionHash, Vec<f64>, f64)>, PlayResult)
+    seed: u64,
+) -> (Vec<(PosT::PositionHash, PosT, f64)>, PlayResult)
 where
-    PosT: ag::NeuroPosition,
-    EvalT: ag::Evaluator<PosT>,
+    PosT: ag::AbstractGame,
+    EvalT: ag::Evaluator<PosT> + Sync,
 {
     let mcts = MonteCarloTreeSearchStrategy::new(
-        eval, params.mcts_tries, params.mcts_softness, params.mcts_max_depth);
+        eval, params.mcts_tries, params.mcts_softness, params.mcts_max_depth, seed);
     let mut strat = FindWinningMoveStrategy::new(mcts);
 
-    // (hash, encoding, player_at_pos)
-    let mut history: Vec<(PosT::PositionHash, Vec<f64>, i32)> = Vec::new();
+    // (hash, position, player_at_pos)
+    let mut history: Vec<(PosT::PositionHash, PosT, i32)> = Vec::new();
     let mut pos = PosT::initial();
 
     loop {
         if pos.is_lost() {
             let loser = pos.current_player();
             let n = history.len();
-            let scored = history.into_iter().enumerate().map(|(i, (hash, enc, player))| {
+            let scored = history.into_iter().enumerate().map(|(i, (hash, p, player))| {
                 let dist = (n - i) as i32;
                 let sign: f64 = if player == loser { -1.0 } else { 1.0 };
-                (hash, enc, sign * params.score_decay.powi(dist))
+                (hash, p, sign * params.score_decay.powi(dist))
             }).collect();
             return (scored, PlayResult::Win { loser });
         }
         if history.len() >= params.max_game_depth {
             let scored = history.into_iter()
-                .map(|(hash, enc, _)| (hash, enc, 0.0))
+                .map(|(hash, p, _)| (hash, p, 0.0))
                 .collect();
             return (scored, PlayResult::Draw);
         }
-        history.push((pos.to_hash(), pos.encode(), pos.current_player()));
+        history.push((pos.to_hash(), pos.clone(), pos.current_player()));
         let Some(mv) = strat.choose_move(&pos) else {
             // No moves without is_lost — shouldn't happen, treat as draw
             let scored = history.into_iter()
-                .map(|(hash, enc, _)| (hash, enc, 0.0))
+                .map(|(hash, p, _)| (hash, p, 0.0))
                 .collect();
             return (scored, PlayResult::Draw);
         };
@@ -163,38 +208,40 @@ where
 
 // ── Database ──────────────────────────────────────────────────────────────────
 
-struct DbEntry {
-    encoding: Vec<f64>,
+pub(crate) struct DbEntry<PosT> {
+    pub(crate) pos: PosT,
     score_sum: f64,
     count:     u32,
 }
 
-impl DbEntry {
-    fn avg_score(&self) -> f64 { self.score_sum / self.count as f64 }
+impl<PosT> DbEntry<PosT> {
+    pub(crate) fn avg_score(&self) -> f64 { self.score_sum / self.count as f64 }
 }
 
-type Database<H> = HashMap<H, DbEntry>;
+/// Self-play positions by hash, with their average game outcome
+pub(crate) type Database<PosT> = HashMap<<PosT as ag::AbstractGame>::PositionHash, DbEntry<PosT>>;
 
-fn db_insert<H: Eq + std::hash::Hash>(
-    db: &mut Database<H>,
-    hash: H,
-    encoding: Vec<f64>,
+fn db_insert<PosT: ag::AbstractGame>(
+    db: &mut Database<PosT>,
+    hash: PosT::PositionHash,
+    pos: PosT,
     score: f64,
 ) {
-    let entry = db.entry(hash).or_insert(DbEntry { encoding, score_sum: 0.0, count: 0 });
+    let entry = db.entry(hash).or_insert(DbEntry { pos, score_sum: 0.0, count: 0 });
     entry.score_sum += score;
     entry.count += 1;
 }
 
-fn generate_database<PosT, EvalT>(
+pub(crate) fn generate_database<PosT, EvalT>(
     eval: &EvalT,
     params: &TrainParameters,
-) -> Database<PosT::PositionHash>
+    rng: &mut StdRng,
+) -> Database<PosT>
 where
-    PosT: ag::NeuroPosition,
-    EvalT: ag::Evaluator<PosT>,
+    PosT: ag::AbstractGame,
+    EvalT: ag::Evaluator<PosT> + Sync,
 {
-    let mut db: Database<PosT::PositionHash> = HashMap::new();
+    let mut db: Database<PosT> = HashMap::new();
     let mut total_plies = 0usize;
     let mut sente_wins = 0usize; // loser = player 1 (Gote)
     let mut gote_wins  = 0usize; // loser = player 0 (Sente)
@@ -204,7 +251,7 @@ where
         params.games_per_epoch, params.mcts_tries, params.mcts_max_depth);
 
     for g in 0..params.games_per_epoch {
-        let (positions, result) = play_game::<PosT, EvalT>(eval, params);
+        let (positions, result) = play_game::<PosT, EvalT>(eval, params, rng.gen());
         let plies = positions.len();
         total_plies += plies;
 
@@ -214,8 +261,8 @@ where
             PlayResult::Draw             => { draws       += 1; "draw      " }
         };
 
-        for (hash, enc, score) in positions {
-            db_insert(&mut db, hash, enc, score);
+        for (hash, p, score) in positions {
+            db_insert(&mut db, hash, p, score);
         }
 
         println!("    Game {:3}/{}: {:3} plies, {} | DB: {} unique positions",
@@ -237,40 +284,43 @@ where
 // ── Training ──────────────────────────────────────────────────────────────────
 
 fn train_on_database<PosT, const IN: usize>(
-    db: &Database<PosT::PositionHash>,
+    db: &Database<PosT>,
     model: &mut NeuroEvaluator<PosT, IN>,
     params: &TrainParameters,
+    rng: &mut StdRng,
 )
 where
     PosT: ag::NeuroPosition,
 {
-    let mut rng = rand::thread_rng();
-
-    // Sample up to training_subset positions
-    let all_entries: Vec<&DbEntry> = db.values().collect();
+    // Sample up to training_subset positions. Map order differs between runs,
+    // so sort first to make the sample depend on the seed alone.
+    let mut all_entries: Vec<(Vec<f64>, f64)> = db.values()
+        .map(|e| (e.pos.encode(), e.avg_score()))
+        .collect();
+    all_entries.sort_by(|a, b| a.partial_cmp(b).unwrap());
     let n_samples = params.training_subset.min(all_entries.len());
-    let sampled: Vec<&&DbEntry> = all_entries
-        .choose_multiple(&mut rng, n_samples)
+    let sampled: Vec<&(Vec<f64>, f64)> = all_entries
+        .choose_multiple(rng, n_samples)
         .collect();
 
     println!("  Training on {}/{} positions  (batch_size={}, epochs={})",
         n_samples, db.len(), params.batch_size, params.training_epochs);
 
     // Pre-convert to f32 once
-    let mut training_data: Vec<([f32; IN], f32)> = sampled.into_iter().map(|e| {
-        let arr: [f32; IN] = e.encoding.iter()
+    let mut training_data: Vec<([f32; IN], f32)> = sampled.into_iter().map(|(encoding, score)| {
+        let arr: [f32; IN] = encoding.iter()
             .map(|&x| x as f32)
             .collect::<Vec<_>>()
             .try_into()
             .expect("encoding length must equal IN");
-        (arr, e.avg_score() as f32)
+        (arr, *score as f32)
     }).collect();
 
     let mut grads = model.model.alloc_grads();
     let mut opt   = Adam::new(&model.model, Default::default());
 
     for epoch in 0..params.training_epochs {
-        training_data.shuffle(&mut rng);
+        training_data.shuffle(rng);
         let mut loss_sum  = 0f64;
         let mut n_updates = 0usize;
 
@@ -312,14 +362,18 @@ pub fn train_epoch<PosT, EvalT, const IN: usize>(
 ) -> io::Result<()>
 where
     PosT: ag::NeuroPosition,
-    EvalT: ag::Evaluator<PosT>,
+    EvalT: ag::Evaluator<PosT> + Sync,
 {
     println!("\n=== Epoch {} ===", epoch);
+    let mut rng = match params.seed {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(epoch as u64)),
+        None => StdRng::from_entropy(),
+    };
     println!("--- Self-play phase ---");
-    let db = generate_database::<PosT, EvalT>(self_play_eval, params);
+    let db = generate_database::<PosT, EvalT>(self_play_eval, params, &mut rng);
 
     println!("--- Training phase ---");
-    train_on_database::<PosT, IN>(&db, model, params);
+    train_on_database::<PosT, IN>(&db, model, params, &mut rng);
 
     let checkpoint = format!("{}.epoch{}", model_file, epoch);
     model.save(&checkpoint)?;
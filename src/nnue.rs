@@ -0,0 +1,439 @@
+// Efficiently updatable network (NNUE) over sparse position features
+//
+// Each player's accumulator is the first layer's output for the features seen
+// from that player's side; a move only adds and subtracts the columns of the
+// features it changes. The rest of the network is small:
+//   [acc(side to move), acc(opponent)] → clipped ReLU → HIDDEN → clipped ReLU → 1
+// Training runs in floating point; evaluation uses integer weights quantized
+// from the float ones.
+
+use std::io;
+use std::marker::PhantomData;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::abstract_game::{self as ag};
+use crate::neuro::{self, Database, NeuroModel, TrainParameters};
+
+/// Width of each player's accumulator
+pub const ACCUMULATOR: usize = 32;
+/// Width of the hidden layer
+pub const HIDDEN: usize = 16;
+
+// Activations in [0, 1] are stored as integers in [0, QA]; weights after the
+// first layer are scaled by QB.
+const QA: i32 = 127;
+const QB: i32 = 64;
+
+/// Static scores stay strictly inside ±saturation, which is kept for decided positions.
+const MAX_STATIC_FRACTION: f64 = 0.95;
+
+// Seed for the initial weights of `fresh()`, so that training runs can repeat
+const INIT_SEED: u64 = 0;
+
+// ── Float weights ─────────────────────────────────────────────────────────────
+
+/// Trainable weights; this is also the file format (JSON).
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+struct Weights {
+    feature_count: usize,
+    /// `feature_count` rows of `ACCUMULATOR` columns
+    feature_weights: Vec<f64>,
+    feature_bias: Vec<f64>,
+    /// `HIDDEN` rows of `2 * ACCUMULATOR` columns
+    hidden_weights: Vec<f64>,
+    hidden_bias: Vec<f64>,
+    output_weights: Vec<f64>,
+    output_bias: f64,
+}
+
+fn clipped(x: f64) -> f64 {
+    x.clamp(0.0, 1.0)
+}
+
+// Derivative of `clipped` at `x`
+fn clipped_slope(x: f64) -> f64 {
+    if x > 0.0 && x < 1.0 { 1.0 } else { 0.0 }
+}
+
+impl Weights {
+    fn random(feature_count: usize, rng: &mut StdRng) -> Self {
+        let mut uniform = |n: usize, scale: f64| (0..n).map(|_| rng.gen_range(-scale..scale)).collect::<Vec<_>>();
+        let feature_weights = uniform(feature_count * ACCUMULATOR, 0.1);
+        let hidden_weights = uniform(HIDDEN * 2 * ACCUMULATOR, 1.0 / (2.0 * ACCUMULATOR as f64).sqrt());
+        let output_weights = uniform(HIDDEN, 1.0 / (HIDDEN as f64).sqrt());
+        Weights {
+            feature_count,
+            feature_weights,
+            feature_bias: vec![0.1; ACCUMULATOR],
+            hidden_weights,
+            hidden_bias: vec![0.0; HIDDEN],
+            output_weights,
+            output_bias: 0.0,
+        }
+    }
+
+    fn accumulate(&self, features: &[usize]) -> Vec<f64> {
+        let mut acc = self.feature_bias.clone();
+        for &f in features {
+            let column = &self.feature_weights[f * ACCUMULATOR..(f + 1) * ACCUMULATOR];
+            acc.iter_mut().zip(column).for_each(|(a, w)| *a += w);
+        }
+        acc
+    }
+
+    /// Pre-activations of both layers and the output, for `features` of the
+    /// side to move and of the opponent
+    fn forward(&self, features: [&[usize]; 2]) -> ([Vec<f64>; 2], Vec<f64>, f64) {
+        let acc = features.map(|f| self.accumulate(f));
+        let input = acc.iter().flatten().map(|&x| clipped(x)).collect::<Vec<_>>();
+        let hidden = (0..HIDDEN).map(|k| {
+            let row = &self.hidden_weights[k * 2 * ACCUMULATOR..(k + 1) * 2 * ACCUMULATOR];
+            self.hidden_bias[k] + row.iter().zip(&input).map(|(w, x)| w * x).sum::<f64>()
+        }).collect::<Vec<_>>();
+        let output = self.output_bias
+            + self.output_weights.iter().zip(&hidden).map(|(w, h)| w * clipped(*h)).sum::<f64>();
+        (acc, hidden, output)
+    }
+
+    /// One SGD step on the squared error against `target`; returns the error before the step.
+    fn train_step(&mut self, features: [&[usize]; 2], target: f64, learning_rate: f64) -> f64 {
+        let (acc, hidden, output) = self.forward(features);
+        let input = acc.iter().flatten().map(|&x| clipped(x)).collect::<Vec<_>>();
+        let d_output = 2.0 * (output - target);
+
+        let mut d_input = vec![0.0; 2 * ACCUMULATOR];
+        let neurons = self.hidden_weights.chunks_exact_mut(2 * ACCUMULATOR)
+            .zip(&mut self.hidden_bias)
+            .zip(&mut self.output_weights)
+            .zip(&hidden);
+        for (((row, bias), output_weight), &h) in neurons {
+            let d_hidden = d_output * *output_weight * clipped_slope(h);
+            *output_weight -= learning_rate * d_output * clipped(h);
+            if d_hidden == 0.0 {
+                continue;
+            }
+            for ((d, w), x) in d_input.iter_mut().zip(row.iter_mut()).zip(&input) {
+                *d += d_hidden * *w;
+                *w -= learning_rate * d_hidden * x;
+            }
+            *bias -= learning_rate * d_hidden;
+        }
+        self.output_bias -= learning_rate * d_output;
+
+        for (side, side_features) in features.iter().enumerate() {
+            let d_acc = d_input[side * ACCUMULATOR..(side + 1) * ACCUMULATOR].iter().zip(&acc[side])
+                .map(|(d, &a)| d * clipped_slope(a))
+                .collect::<Vec<_>>();
+            self.feature_bias.iter_mut().zip(&d_acc).for_each(|(b, d)| *b -= learning_rate * d);
+            for &f in side_features.iter() {
+                let column = &mut self.feature_weights[f * ACCUMULATOR..(f + 1) * ACCUMULATOR];
+                column.iter_mut().zip(&d_acc).for_each(|(w, d)| *w -= learning_rate * d);
+            }
+        }
+        (output - target).powi(2)
+    }
+}
+
+// ── Integer weights ───────────────────────────────────────────────────────────
+
+/// `Weights` rounded for integer inference: the first layer and its accumulators
+/// in units of 1/QA, the later weights in units of 1/QB.
+struct Quantized {
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    hidden_weights: Vec<i16>,
+    /// Units of 1/(QA*QB)
+    hidden_bias: Vec<i32>,
+    output_weights: Vec<i16>,
+    /// Units of 1/(QA*QB)
+    output_bias: i32,
+}
+
+impl Quantized {
+    fn new(w: &Weights) -> Self {
+        let q16 = |v: &[f64], scale: i32| v.iter()
+            .map(|x| (x * scale as f64).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16)
+            .collect::<Vec<_>>();
+        let q32 = |x: f64| (x * (QA * QB) as f64).round() as i32;
+        Quantized {
+            feature_weights: q16(&w.feature_weights, QA),
+            feature_bias: q16(&w.feature_bias, QA),
+            hidden_weights: q16(&w.hidden_weights, QB),
+            hidden_bias: w.hidden_bias.iter().map(|&x| q32(x)).collect(),
+            output_weights: q16(&w.output_weights, QB),
+            output_bias: q32(w.output_bias),
+        }
+    }
+}
+
+// ── Evaluator ─────────────────────────────────────────────────────────────────
+
+/// First-layer outputs of both players, indexed by player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    sides: [Vec<i32>; 2],
+}
+
+pub struct NnueEvaluator<PosT: ag::SparsePosition> {
+    weights: Weights,
+    quantized: Quantized,
+    _phantom: PhantomData<PosT>,
+}
+
+impl<PosT: ag::SparsePosition> NnueEvaluator<PosT> {
+    pub fn new(seed: u64) -> Self {
+        Self::from_weights(Weights::random(PosT::FEATURE_COUNT, &mut StdRng::seed_from_u64(seed)))
+    }
+
+    fn from_weights(weights: Weights) -> Self {
+        let quantized = Quantized::new(&weights);
+        NnueEvaluator { weights, quantized, _phantom: PhantomData }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let weights: Weights = serde_json::from_reader(io::BufReader::new(file))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if weights.feature_count != PosT::FEATURE_COUNT
+            || weights.feature_weights.len() != PosT::FEATURE_COUNT * ACCUMULATOR
+            || weights.feature_bias.len() != ACCUMULATOR
+            || weights.hidden_weights.len() != HIDDEN * 2 * ACCUMULATOR
+            || weights.hidden_bias.len() != HIDDEN
+            || weights.output_weights.len() != HIDDEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "network shape does not match the game"));
+        }
+        Ok(Self::from_weights(weights))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer(io::BufWriter::new(file), &self.weights)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn accumulate_side(&self, features: &[usize]) -> Vec<i32> {
+        let mut acc = self.quantized.feature_bias.iter().map(|&b| b as i32).collect::<Vec<_>>();
+        for &f in features {
+            self.add_column(&mut acc, f, 1);
+        }
+        acc
+    }
+
+    fn add_column(&self, acc: &mut [i32], feature: usize, sign: i32) {
+        let column = &self.quantized.feature_weights[feature * ACCUMULATOR..(feature + 1) * ACCUMULATOR];
+        acc.iter_mut().zip(column).for_each(|(a, &w)| *a += sign * w as i32);
+    }
+
+    /// Accumulators computed from scratch
+    pub fn refresh(&self, pos: &PosT) -> Accumulator {
+        Accumulator { sides: [0, 1].map(|player| self.accumulate_side(&pos.active_features(player))) }
+    }
+
+    /// Accumulators after `mv`, from those of `pos`; `None` for illegal moves
+    pub fn update(&self, acc: &Accumulator, pos: &PosT, mv: &str) -> Option<Accumulator> {
+        let mut next = acc.clone();
+        for player in [0, 1] {
+            let (removed, added) = pos.feature_changes(mv, player)?;
+            let side = &mut next.sides[player as usize];
+            removed.into_iter().for_each(|f| self.add_column(side, f, -1));
+            added.into_iter().for_each(|f| self.add_column(side, f, 1));
+        }
+        Some(next)
+    }
+
+    /// Score for `player` to move, in integers up to the final scaling
+    pub fn evaluate_accumulator(&self, acc: &Accumulator, player: i32) -> f64 {
+        let q = &self.quantized;
+        let input = acc.sides[player as usize].iter().chain(&acc.sides[1 - player as usize])
+            .map(|&a| a.clamp(0, QA))
+            .collect::<Vec<_>>();
+        let output = (0..HIDDEN).map(|k| {
+            let row = &q.hidden_weights[k * 2 * ACCUMULATOR..(k + 1) * 2 * ACCUMULATOR];
+            let sum = q.hidden_bias[k] + row.iter().zip(&input).map(|(&w, &x)| w as i32 * x).sum::<i32>();
+            (sum / QB).clamp(0, QA) * q.output_weights[k] as i32
+        }).sum::<i32>() + q.output_bias;
+        let limit = MAX_STATIC_FRACTION * ag::Evaluator::<PosT>::saturation(self);
+        (output as f64 / (QA * QB) as f64).clamp(-limit, limit)
+    }
+}
+
+impl<PosT: ag::SparsePosition> ag::Evaluator<PosT> for NnueEvaluator<PosT> {
+    fn evaluate_position(&self, pos: &PosT) -> f64 {
+        if pos.is_lost() {
+            return -self.saturation()
+        }
+        self.evaluate_accumulator(&self.refresh(pos), pos.current_player())
+    }
+
+    fn saturation(&self) -> f64 { 1.0 }
+
+    fn evaluate_children(&self, pos: &PosT, moves: &[String]) -> Vec<f64> {
+        let acc = self.refresh(pos);
+        moves.iter().map(|mv| {
+            let child = pos.make_move(mv).unwrap();
+            if child.is_lost() {
+                return -self.saturation()
+            }
+            let child_acc = self.update(&acc, pos, mv).unwrap();
+            self.evaluate_accumulator(&child_acc, child.current_player())
+        }).collect()
+    }
+}
+
+impl<PosT: ag::SparsePosition> NeuroModel<PosT> for NnueEvaluator<PosT> {
+    fn fresh() -> Self { Self::new(INIT_SEED) }
+    fn load(path: &str) -> io::Result<Self> { NnueEvaluator::load(path) }
+    fn save_to(&self, path: &str) -> io::Result<()> { self.save(path) }
+    fn train_epoch<EvalT: ag::Evaluator<PosT> + Sync>(
+        &mut self,
+        self_play_eval: &EvalT,
+        params: &TrainParameters,
+        epoch: usize,
+        model_file: &str,
+    ) -> io::Result<()> {
+        train_epoch(self_play_eval, self, params, epoch, model_file)
+    }
+}
+
+// ── Training ──────────────────────────────────────────────────────────────────
+
+fn train_on_database<PosT: ag::SparsePosition>(
+    db: &Database<PosT>,
+    model: &mut NnueEvaluator<PosT>,
+    params: &TrainParameters,
+    rng: &mut StdRng,
+) {
+    // Sorted first, so that the sample depends on the seed alone
+    let mut all_entries = db.values()
+        .map(|e| {
+            let player = e.pos.current_player();
+            (e.pos.to_str(), [e.pos.active_features(player), e.pos.active_features(1 - player)], e.avg_score())
+        })
+        .collect::<Vec<_>>();
+    all_entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let n_samples = params.training_subset.min(all_entries.len());
+    let mut training_data = all_entries.choose_multiple(rng, n_samples).collect::<Vec<_>>();
+
+    println!("  Training on {}/{} positions  (learning_rate={}, epochs={})",
+        n_samples, db.len(), params.learning_rate, params.training_epochs);
+
+    for epoch in 0..params.training_epochs {
+        training_data.shuffle(rng);
+        let loss_sum = training_data.iter()
+            .map(|(_, features, score)| model.weights.train_step([&features[0], &features[1]], *score, params.learning_rate))
+            .sum::<f64>();
+        let avg_loss = loss_sum / training_data.len().max(1) as f64;
+        let marker = if epoch == 0 { " <start>" } else if epoch + 1 == params.training_epochs { " <end>" } else { "" };
+        println!("    Epoch {:3}/{}: avg_loss={:.6}{}",
+            epoch + 1, params.training_epochs, avg_loss, marker);
+    }
+    model.quantized = Quantized::new(&model.weights);
+}
+
+/// Run one training epoch like `neuro::train_epoch`: self-play with
+/// `self_play_eval` into a position database, then fit `model` to a sample of it
+/// and save a checkpoint to `{model_file}.epoch{epoch}`.
+pub fn train_epoch<PosT, EvalT>(
+    self_play_eval: &EvalT,
+    model: &mut NnueEvaluator<PosT>,
+    params: &TrainParameters,
+    epoch: usize,
+    model_file: &str,
+) -> io::Result<()>
+where
+    PosT: ag::SparsePosition,
+    EvalT: ag::Evaluator<PosT> + Sync,
+{
+    println!("\n=== Epoch {} (NNUE) ===", epoch);
+    let mut rng = match params.seed {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(epoch as u64)),
+        None => StdRng::from_entropy(),
+    };
+    println!("--- Self-play phase ---");
+    let db = neuro::generate_database::<PosT, EvalT>(self_play_eval, params, &mut rng);
+
+    println!("--- Training phase ---");
+    train_on_database(&db, model, params, &mut rng);
+
+    let checkpoint = format!("{}.epoch{}", model_file, epoch);
+    model.save(&checkpoint)?;
+    println!("  Checkpoint saved → {}", checkpoint);
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::abstract_game::{AbstractGame, Evaluator, SparsePosition};
+    use crate::kids_shogi::KidsShogiGame;
+
+    // Score of `pos` from the float weights being trained
+    fn evaluate_float(eval: &NnueEvaluator<KidsShogiGame>, pos: &KidsShogiGame) -> f64 {
+        let player = pos.current_player();
+        let features = [pos.active_features(player), pos.active_features(1 - player)];
+        eval.weights.forward([&features[0], &features[1]]).2
+    }
+
+    #[test]
+    fn incremental_update_matches_refresh() {
+        let eval = NnueEvaluator::<KidsShogiGame>::new(1);
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut pos = KidsShogiGame::initial();
+        let mut acc = eval.refresh(&pos);
+        for _ in 0..40 {
+            if pos.is_lost() {
+                break;
+            }
+            let mv = pos.possible_moves().choose(&mut rng).unwrap().clone();
+            acc = eval.update(&acc, &pos, &mv).unwrap();
+            pos = pos.make_move(&mv).unwrap();
+            assert_eq!(acc, eval.refresh(&pos), "after {}", mv);
+        }
+    }
+
+    #[test]
+    fn children_scored_incrementally() {
+        let eval = NnueEvaluator::<KidsShogiGame>::new(5);
+        let pos = KidsShogiGame::from_fen("l2/G2/1c1/L2 b C").unwrap();
+        let moves = pos.possible_moves();
+        let expected = moves.iter().map(|mv| eval.evaluate_position(&pos.make_move(mv).unwrap())).collect::<Vec<_>>();
+        assert_eq!(eval.evaluate_children(&pos, &moves), expected);
+        assert!(expected.contains(&-1.0));  // a3a4 takes the lion
+    }
+
+    #[test]
+    fn integer_inference_follows_float_weights() {
+        let eval = NnueEvaluator::<KidsShogiGame>::new(3);
+        let pos = KidsShogiGame::from_fen("gl1/1e1/3/ELG b Cc").unwrap();
+        let float = evaluate_float(&eval, &pos).clamp(-0.95, 0.95);
+        assert!((eval.evaluate_position(&pos) - float).abs() < 0.05);
+        let won = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap().make_move("a3a4").unwrap();
+        assert_eq!(eval.evaluate_position(&won), -1.0);
+    }
+
+    #[test]
+    fn training_fits_targets_and_save_roundtrips() {
+        let mut eval = NnueEvaluator::<KidsShogiGame>::new(4);
+        let winning = KidsShogiGame::from_fen("gl1/1e1/3/ELG b CCc").unwrap();
+        let losing = KidsShogiGame::from_fen("gl1/1e1/3/ELG b Ccc").unwrap();
+        let sample = |pos: &KidsShogiGame| [pos.active_features(pos.current_player()), pos.active_features(1 - pos.current_player())];
+        let (w, l) = (sample(&winning), sample(&losing));
+        for _ in 0..200 {
+            eval.weights.train_step([&w[0], &w[1]], 0.5, 0.01);
+            eval.weights.train_step([&l[0], &l[1]], -0.5, 0.01);
+        }
+        assert!((evaluate_float(&eval, &winning) - 0.5).abs() < 0.05);
+        assert!((evaluate_float(&eval, &losing) + 0.5).abs() < 0.05);
+
+        let path = std::env::temp_dir().join(format!("nnue_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        eval.save(path).unwrap();
+        let loaded = NnueEvaluator::<KidsShogiGame>::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!((evaluate_float(&loaded, &winning) - evaluate_float(&eval, &winning)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,133 @@
+// Thinking on the opponent's time: an engine's `ponder` run on a background thread
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::{SearchLimits, StrategyEngine};
+
+/// Lets `engine` ponder on `pos` while `f` runs (e.g. waiting for the human's
+/// move), then stops it and returns `f`'s result.
+pub fn ponder_while<PosT, E, R>(engine: &mut E, pos: &PosT, f: impl FnOnce() -> R) -> R
+where
+    PosT: ag::AbstractGame + Sync,
+    E: StrategyEngine<PosT> + Send + ?Sized,
+{
+    if pos.is_lost() {
+        return f()
+    }
+    let limits = SearchLimits::default();
+    thread::scope(|scope| {
+        let pondering = scope.spawn(|| engine.ponder(pos, &limits));
+        let result = f();
+        limits.stop.store(true, Ordering::Relaxed);
+        pondering.join().unwrap();
+        result
+    })
+}
+
+/// Owns the engine of one game and lends it to a background thread between
+/// moves. `engine` stops the thread and hands the engine back; dropping the
+/// `Ponderer` stops it too.
+pub struct Ponderer<PosT, E> {
+    engine: Option<E>,
+    pondering: Option<(Arc<AtomicBool>, JoinHandle<E>)>,
+    phantom_pos: std::marker::PhantomData<fn(PosT)>,
+}
+
+impl<PosT, E> Ponderer<PosT, E>
+where
+    PosT: ag::AbstractGame + Send + 'static,
+    E: StrategyEngine<PosT> + Send + 'static,
+{
+    pub fn new(engine: E) -> Self {
+        Ponderer { engine: Some(engine), pondering: None, phantom_pos: std::marker::PhantomData }
+    }
+
+    /// Starts pondering on `pos`, the position the opponent has to answer
+    pub fn start(&mut self, pos: PosT) {
+        let mut engine = self.take_engine();
+        if pos.is_lost() {
+            self.engine = Some(engine);
+            return
+        }
+        let limits = SearchLimits::default();
+        let stop = Arc::clone(&limits.stop);
+        let handle = thread::spawn(move || {
+            engine.ponder(&pos, &limits);
+            engine
+        });
+        self.pondering = Some((stop, handle));
+    }
+
+    /// The engine, after stopping the background thread if it runs
+    pub fn engine(&mut self) -> &mut E {
+        if self.engine.is_none() {
+            self.engine = Some(self.take_engine());
+        }
+        self.engine.as_mut().unwrap()
+    }
+
+    fn take_engine(&mut self) -> E {
+        match self.pondering.take() {
+            Some((stop, handle)) => {
+                stop.store(true, Ordering::Relaxed);
+                handle.join().expect("pondering thread panicked")
+            }
+            None => self.engine.take().expect("engine is either idle or pondering"),
+        }
+    }
+}
+
+impl<PosT, E> Drop for Ponderer<PosT, E> {
+    fn drop(&mut self) {
+        if let Some((stop, handle)) = self.pondering.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::abstract_game::AbstractGame;
+    use crate::kids_shogi::KidsShogiGame;
+
+    // Ponders until stopped and remembers the positions it pondered on
+    #[derive(Default)]
+    struct Patient {
+        pondered: Vec<String>,
+    }
+
+    impl StrategyEngine<KidsShogiGame> for Patient {
+        fn choose_move(&mut self, pos: &KidsShogiGame) -> Option<String> {
+            pos.possible_moves().into_iter().next()
+        }
+        fn ponder(&mut self, pos: &KidsShogiGame, limits: &SearchLimits) {
+            while !limits.is_stopped() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            self.pondered.push(pos.to_str());
+        }
+    }
+
+    #[test]
+    fn stops_when_the_move_arrives() {
+        let mut engine = Patient::default();
+        let pos = KidsShogiGame::initial();
+        assert_eq!(ponder_while(&mut engine, &pos, || 42), 42);
+        assert_eq!(engine.pondered, vec![pos.to_str()]);
+
+        let mut ponderer = Ponderer::new(Patient::default());
+        ponderer.start(pos.clone());
+        assert_eq!(ponderer.engine().pondered, vec![pos.to_str()]);
+        // Finished games are not pondered on, and a dropped ponderer stops its thread
+        ponderer.start(KidsShogiGame::from_fen("3/1L1/3/3 w L").unwrap());
+        assert_eq!(ponderer.engine().pondered.len(), 1);
+        ponderer.start(pos);
+        drop(ponderer);
+    }
+}
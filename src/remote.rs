@@ -0,0 +1,317 @@
+// Engine on another kid_shogi server, asked for moves over its JSON-RPC endpoint
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::games::RegisteredGame;
+use crate::rpc::{BestMoveRequest, BestMoveResponse};
+use crate::strategy::{SearchLimits, StrategyEngine};
+
+/// Extra time the server gets over the movetime for the round trip
+const GRACE: Duration = Duration::from_millis(1000);
+/// Wait before the first retry; doubled for every further one
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub enum RemoteError {
+    /// Connecting, sending or receiving failed; worth retrying unless it timed out
+    Io(io::Error),
+    /// The server answered with this HTTP status
+    Http(u16),
+    /// The server rejected the call
+    Rpc(String),
+    BadReply(String),
+}
+
+impl RemoteError {
+    fn is_transient(&self) -> bool {
+        match self {
+            // The server had its time; asking again would only search the move again
+            RemoteError::Io(e) => !matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock),
+            RemoteError::Http(status) => *status >= 500,
+            RemoteError::Rpc(_) | RemoteError::BadReply(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::Io(e) => write!(f, "connection failed: {}", e),
+            RemoteError::Http(status) => write!(f, "HTTP status {}", status),
+            RemoteError::Rpc(message) => write!(f, "server error: {}", message),
+            RemoteError::BadReply(reply) => write!(f, "unexpected reply: {}", reply),
+        }
+    }
+}
+
+/// `host:port` and path of an `http://host:port/path` URL; the scheme is
+/// optional and the path defaults to `/rpc`.
+fn parse_url(url: &str) -> (String, String) {
+    let rest = url.strip_prefix("http://").unwrap_or(url);
+    match rest.find('/') {
+        Some(i) => (rest[..i].to_string(), rest[i..].to_string()),
+        None => (rest.to_string(), "/rpc".to_string()),
+    }
+}
+
+/// Minimal JSON-RPC over HTTP/1.1 client, one connection per call.
+struct RpcClient {
+    host: String,
+    path: String,
+    next_id: u64,
+}
+
+impl RpcClient {
+    fn new(url: &str) -> Self {
+        let (host, path) = parse_url(url);
+        RpcClient { host, path, next_id: 1 }
+    }
+
+    fn connect(&self, timeout: Duration) -> io::Result<TcpStream> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", self.host));
+        for addr in self.host.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    // `timeout` bounds the whole call: connecting, sending and waiting for the answer
+    fn post(&self, body: &str, timeout: Duration) -> Result<String, RemoteError> {
+        let deadline = Instant::now() + timeout;
+        let left = || Some(deadline.saturating_duration_since(Instant::now()))
+            .filter(|t| !t.is_zero())
+            .ok_or_else(|| RemoteError::Io(io::ErrorKind::TimedOut.into()));
+        let mut stream = self.connect(left()?).map_err(RemoteError::Io)?;
+        stream.set_write_timeout(Some(left()?)).map_err(RemoteError::Io)?;
+        stream.set_read_timeout(Some(left()?)).map_err(RemoteError::Io)?;
+        write!(stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path, self.host, body.len(), body).map_err(RemoteError::Io)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(RemoteError::Io)?;
+        let (head, body) = response.split_once("\r\n\r\n")
+            .ok_or_else(|| RemoteError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete response")))?;
+        let status = head.split(' ').nth(1).and_then(|s| s.parse::<u16>().ok())
+            .ok_or_else(|| RemoteError::BadReply(head.lines().next().unwrap_or("").to_string()))?;
+        if status != 200 {
+            return Err(RemoteError::Http(status))
+        }
+        Ok(body.to_string())
+    }
+
+    fn call(&mut self, method: &str, params: Value, timeout: Duration) -> Result<Value, RemoteError> {
+        let request = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": self.next_id});
+        self.next_id += 1;
+        let body = self.post(&request.to_string(), timeout)?;
+        let mut reply: Value = serde_json::from_str(&body).map_err(|_| RemoteError::BadReply(body.clone()))?;
+        if let Some(error) = reply.get("error") {
+            let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+            return Err(RemoteError::Rpc(message.to_string()))
+        }
+        reply.get_mut("result").map(Value::take).ok_or(RemoteError::BadReply(body))
+    }
+}
+
+/// Asks a kid_shogi server (`--server`) for each move through its `best_move`
+/// RPC, so no game is created there. Failed connections and server errors are
+/// retried with a growing delay, all attempts within one timeout; timeouts are
+/// not retried. If no attempt succeeds the error goes to stderr and no move is
+/// returned.
+pub struct RemoteRpcStrategy<PosT> {
+    client: RpcClient,
+    timeout: Duration,
+    retries: usize,
+    phantom_pos: PhantomData<PosT>,
+}
+
+impl<PosT: RegisteredGame> RemoteRpcStrategy<PosT> {
+    /// `url` of the server's RPC endpoint, e.g. `http://127.0.0.1:8080/rpc`;
+    /// `timeout` bounds moves that have no movetime.
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        RemoteRpcStrategy { client: RpcClient::new(url), timeout, retries: 3, phantom_pos: PhantomData }
+    }
+
+    /// Attempts after the first one fails (3 by default)
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    fn request(&mut self, pos: &PosT, limits: &SearchLimits) -> Result<Option<String>, RemoteError> {
+        let request = BestMoveRequest {
+            position: pos.to_str(),
+            game: Some(PosT::KIND),
+            movetime_ms: limits.movetime.map(|t| t.as_millis() as u64),
+            level: None,
+        };
+        let params = serde_json::to_value(&request).unwrap();
+        let deadline = Instant::now() + limits.movetime.map_or(self.timeout, |t| t + GRACE);
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 0;
+        let result = loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.client.call("best_move", params.clone(), left) {
+                Err(e) if e.is_transient() && attempt < self.retries
+                    && deadline.saturating_duration_since(Instant::now()) > delay => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+        let response: BestMoveResponse = serde_json::from_value(result.clone())
+            .map_err(|_| RemoteError::BadReply(result.to_string()))?;
+        match response.mv {
+            Some(mv) if pos.make_move(&mv).is_none() => Err(RemoteError::BadReply(format!("illegal move {}", mv))),
+            mv => Ok(mv),
+        }
+    }
+}
+
+impl<PosT: RegisteredGame> StrategyEngine<PosT> for RemoteRpcStrategy<PosT> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.choose_move_with_limits(pos, &SearchLimits::default())
+    }
+
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        self.request(pos, limits).unwrap_or_else(|e| {
+            eprintln!("Remote engine at {}{}: {}", self.client.host, self.client.path, e);
+            None
+        })
+    }
+}
+
+/// Connects every game to the same remote server.
+pub struct RemoteRpcFactory {
+    url: String,
+    timeout: Duration,
+    retries: usize,
+}
+
+impl RemoteRpcFactory {
+    pub fn new(url: &str, timeout: Duration, retries: usize) -> Self {
+        RemoteRpcFactory { url: url.to_string(), timeout, retries }
+    }
+}
+
+impl<PosT: RegisteredGame> crate::abstract_game::StrategyFactory<PosT> for RemoteRpcFactory {
+    fn create(&self, _seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(RemoteRpcStrategy::new(&self.url, self.timeout).retries(self.retries))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use crate::abstract_game::AbstractGame;
+    use crate::games::GameKind;
+    use crate::kids_shogi::{self, KidsShogiGame};
+    use crate::rpc;
+
+    fn alphabeta_hosts() -> rpc::GameHosts {
+        static EVAL: kids_shogi::SimpleEvaluator = kids_shogi::SimpleEvaluator {};
+        let mut hosts = rpc::GameHosts::new(GameKind::KidsShogi, None, Some(1));
+        hosts.add(crate::alphabeta::AlphaBetaFactory::new(&EVAL, 3, 1));
+        hosts
+    }
+
+    // Serves `/rpc` with the real handler on a free port; the first `drop_first`
+    // connections are closed without an answer
+    fn serve(hosts: rpc::GameHosts, drop_first: usize) -> String {
+        let io = rpc::create_io_handler(hosts);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rpc", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+                if i < drop_first {
+                    continue;
+                }
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let reply = io.handle_request_sync(std::str::from_utf8(&body).unwrap()).unwrap();
+                write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", reply.len(), reply).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn plays_through_the_server() {
+        let mut remote = RemoteRpcStrategy::<KidsShogiGame>::new(&serve(alphabeta_hosts(), 0), Duration::from_secs(10));
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        assert_eq!(remote.choose_move(&pos), Some("a3a4".to_string()));
+        let initial = KidsShogiGame::initial();
+        let mv = remote.choose_move(&initial).unwrap();
+        assert!(initial.possible_moves().contains(&mv));
+        let over = KidsShogiGame::from_fen("3/1L1/3/3 w L").unwrap();
+        assert_eq!(remote.choose_move(&over), None);
+    }
+
+    #[test]
+    fn retries_dropped_connections() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        let mut remote = RemoteRpcStrategy::<KidsShogiGame>::new(&serve(alphabeta_hosts(), 2), Duration::from_secs(10));
+        assert_eq!(remote.request(&pos, &SearchLimits::default()).unwrap(), Some("a3a4".to_string()));
+
+        let mut impatient = RemoteRpcStrategy::<KidsShogiGame>::new(&serve(alphabeta_hosts(), 1), Duration::from_secs(10)).retries(0);
+        assert!(matches!(impatient.request(&pos, &SearchLimits::default()), Err(RemoteError::Io(_))));
+        // A server that hosts no games answers with an error, which is not retried
+        let no_games = rpc::GameHosts::new(GameKind::KidsShogi, None, None);
+        let mut rejected = RemoteRpcStrategy::<KidsShogiGame>::new(&serve(no_games, 0), Duration::from_secs(10));
+        assert!(matches!(rejected.request(&pos, &SearchLimits::default()), Err(RemoteError::Rpc(_))));
+    }
+
+    #[test]
+    fn timeouts_are_not_retried() {
+        // Accepts connections and never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rpc", listener.local_addr().unwrap());
+        let (connections, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                connections.send(stream.unwrap()).unwrap();
+            }
+        });
+        let pos = KidsShogiGame::initial();
+        let mut remote = RemoteRpcStrategy::<KidsShogiGame>::new(&url, Duration::from_millis(300));
+        let t0 = Instant::now();
+        match remote.request(&pos, &SearchLimits::default()) {
+            Err(RemoteError::Io(e)) => assert!(matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(t0.elapsed() < Duration::from_secs(2));
+        assert!(received.recv_timeout(Duration::from_secs(1)).is_ok());
+        assert!(received.try_recv().is_err(), "the move was asked for again");
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(parse_url("http://127.0.0.1:8080/rpc"), ("127.0.0.1:8080".to_string(), "/rpc".to_string()));
+        assert_eq!(parse_url("localhost:9000"), ("localhost:9000".to_string(), "/rpc".to_string()));
+    }
+}
@@ -1,16 +1,39 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-
-use rand::Rng;
-use jsonrpc_core::{IoHandler, Params, Value, Error};
-
-use crate::abstract_game::{AbstractGame, StrategyFactory};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use jsonrpc_core::{ErrorCode, IoHandler, Params, Value, Error};
+
+use crate::abstract_game::StrategyFactory;
+use crate::analysis::MoveAnalysis;
+use crate::games::{GameKind, RegisteredGame};
+use crate::hint::{self, Hint};
+use crate::ponder::Ponderer;
+use crate::skill::{self, SkillLimitedStrategy};
+use crate::spec::{self, Spec};
+use crate::strategy::{derive_seed, SearchLimits, StrategyEngine};
+use crate::tree_dump::TreeNode;
 
 // ── Request / response types ──────────────────────────────────────────────────
 
 #[derive(serde::Deserialize)]
 struct StartGameRequest {
     player: i32,
+    /// Game to play; the server's `--game` when omitted
+    #[serde(default)]
+    game: Option<GameKind>,
+    /// Thinking time for the server's moves; the server's `--movetime` when omitted
+    #[serde(default)]
+    movetime_ms: Option<u64>,
+    /// Skill level 1-10 of the server's play; full strength when omitted
+    #[serde(default)]
+    level: Option<u8>,
+    /// Strategy spec of the server's play (as for `--spec`), limited to engines
+    /// that need no files, processes or connections; the server's own when omitted
+    #[serde(default)]
+    strategy: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -31,6 +54,9 @@ struct MakeMoveRequest {
     game_id: String,
     #[serde(rename = "move")]
     move_: String,
+    /// Thinking time for the reply; the server's `--movetime` when omitted
+    #[serde(default)]
+    movetime_ms: Option<u64>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -48,29 +74,146 @@ struct MakeMoveResponse {
     game_result: Option<GameResult>,
 }
 
+#[derive(serde::Deserialize)]
+struct AnalyzePositionRequest {
+    position: String,
+    /// Game of `position`; the server's `--game` when omitted
+    #[serde(default)]
+    game: Option<GameKind>,
+    #[serde(default = "default_num_pv")]
+    num_pv: usize,
+    #[serde(default)]
+    movetime_ms: Option<u64>,
+}
+
+fn default_num_pv() -> usize {
+    3
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AnalyzePositionResponse {
+    position: String,
+    /// Best first
+    moves: Vec<MoveAnalysis>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct BestMoveRequest {
+    pub(crate) position: String,
+    /// Game of `position`; the server's `--game` when omitted
+    #[serde(default)]
+    pub(crate) game: Option<GameKind>,
+    #[serde(default)]
+    pub(crate) movetime_ms: Option<u64>,
+    /// Skill level 1-10; full strength when omitted
+    #[serde(default)]
+    pub(crate) level: Option<u8>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct BestMoveResponse {
+    /// None if the game is already over
+    #[serde(rename = "move")]
+    pub(crate) mv: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct GetHintRequest {
+    game_id: String,
+    #[serde(default)]
+    movetime_ms: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GetHintResponse {
+    position: String,
+    hint: Hint,
+}
+
+/// Debugging aid: the server's engine searches `position` and returns its tree
+#[derive(serde::Deserialize)]
+struct DumpTreeRequest {
+    position: String,
+    /// Game of `position`; the server's `--game` when omitted
+    #[serde(default)]
+    game: Option<GameKind>,
+    /// Plies below `position`
+    #[serde(default = "default_dump_depth")]
+    depth: usize,
+    /// Moves tried fewer times are left out
+    #[serde(default = "default_min_visits")]
+    min_visits: usize,
+    #[serde(default)]
+    movetime_ms: Option<u64>,
+}
+
+fn default_dump_depth() -> usize {
+    2
+}
+
+fn default_min_visits() -> usize {
+    1
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DumpTreeResponse {
+    position: String,
+    /// The move the search chose
+    #[serde(rename = "move")]
+    mv: Option<String>,
+    tree: TreeNode,
+    /// `tree` in Graphviz DOT
+    dot: String,
+}
+
+/// Node budget of the mate search behind `get_hint`
+const HINT_MATE_NODES: usize = 20000;
+
+/// Deepest tree `dump_tree` returns
+const MAX_DUMP_DEPTH: usize = 16;
+
+/// Client strategy specs whose factories the server keeps at once
+const MAX_CLIENT_FACTORIES: usize = 64;
+
+// The strategy had no move for a game in progress: an external or remote engine
+// timed out, crashed or could not be reached
+fn no_move_error() -> Error {
+    Error { code: ErrorCode::InternalError, message: "the strategy found no move".to_string(), data: None }
+}
+
 // ── Game registry ─────────────────────────────────────────────────────────────
 
 struct GameEntry {
+    game: GameKind,
     #[allow(dead_code)]
     human_player: i32,
     position: String,
+    level: Option<u8>,
+    // Canonical spec from `start_game`, if the client chose the strategy
+    strategy: Option<String>,
 }
 
 struct GameRegistry {
     games: HashMap<String, GameEntry>,
+    // Source of game ids and engine seeds
+    rng: StdRng,
 }
 
 impl GameRegistry {
-    fn new() -> Self {
-        GameRegistry { games: HashMap::new() }
+    fn new(rng: StdRng) -> Self {
+        GameRegistry { games: HashMap::new(), rng }
     }
 
     fn insert(&mut self, entry: GameEntry) -> String {
-        let id = format!("{:016x}", rand::thread_rng().gen::<u64>());
+        let id = format!("{:016x}", self.rng.gen::<u64>());
         self.games.insert(id.clone(), entry);
         id
     }
 
+    fn next_seed(&mut self) -> u64 {
+        self.rng.gen()
+    }
+
     fn get(&self, id: &str) -> Option<&GameEntry> {
         self.games.get(id)
     }
@@ -82,43 +225,120 @@ impl GameRegistry {
 
 // ── Game server ───────────────────────────────────────────────────────────────
 
-struct GameServer<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> {
-    registry: Mutex<GameRegistry>,
-    strategy_factory: FactoryT,
-    phantom_pos: std::marker::PhantomData<PosT>,
+/// Type-erased view of a `GameServer`, so that servers for different position
+/// types can live in one `GameHosts` map.
+trait GameHost: Send + Sync {
+    fn start_game(&self, request: StartGameRequest, limits: &SearchLimits) -> Result<Value, Error>;
+    fn make_move(&self, request: MakeMoveRequest, limits: &SearchLimits) -> Result<Value, Error>;
+    fn analyze_position(&self, request: AnalyzePositionRequest, limits: &SearchLimits) -> Result<Value, Error>;
+    fn get_hint(&self, request: GetHintRequest, limits: &SearchLimits) -> Result<Value, Error>;
+    fn best_move(&self, request: BestMoveRequest, limits: &SearchLimits) -> Result<Value, Error>;
+    fn dump_tree(&self, request: DumpTreeRequest, limits: &SearchLimits) -> Result<Value, Error>;
+    /// Forget the engine of a game that left the registry
+    fn remove_game(&self, game_id: &str);
 }
 
-// SAFETY: registry is protected by Mutex; factory is Send+Sync; PhantomData
-// holds no PosT data.
-unsafe impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> Send for GameServer<PosT, FactoryT> {}
-unsafe impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> Sync for GameServer<PosT, FactoryT> {}
+type Engine<PosT> = Box<dyn StrategyEngine<PosT> + Send>;
+
+struct GameServer<PosT: RegisteredGame, FactoryT: StrategyFactory<PosT>> {
+    registry: Arc<Mutex<GameRegistry>>,
+    strategy_factory: FactoryT,
+    // With pondering, each game keeps its engine, thinking between the moves
+    ponder: bool,
+    engines: Mutex<HashMap<String, Ponderer<PosT, Engine<PosT>>>>,
+    // Factories for the strategies clients asked for, by canonical spec
+    client_factories: Mutex<HashMap<String, Arc<dyn StrategyFactory<PosT>>>>,
+}
 
-impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> GameServer<PosT, FactoryT> {
-    fn new(strategy_factory: FactoryT) -> Self {
+impl<PosT: RegisteredGame, FactoryT: StrategyFactory<PosT>> GameServer<PosT, FactoryT> {
+    fn new(registry: Arc<Mutex<GameRegistry>>, strategy_factory: FactoryT, ponder: bool) -> Self {
         GameServer {
-            registry: Mutex::new(GameRegistry::new()),
+            registry,
             strategy_factory,
-            phantom_pos: std::marker::PhantomData,
+            ponder,
+            engines: Mutex::new(HashMap::new()),
+            client_factories: Mutex::new(HashMap::new()),
         }
     }
 
-    fn start_game(&self, params: Params) -> Result<Value, Error> {
-        let request: StartGameRequest = params.parse()
-            .map_err(|e| Error::invalid_params(e.message))?;
-        if request.player != 0 && request.player != 1 {
-            return Err(Error::invalid_params("player must be 0 or 1"));
+    fn next_seed(&self) -> u64 {
+        self.registry.lock().unwrap().next_seed()
+    }
+
+    // Factory for a client's strategy spec, built on first use
+    fn client_factory(&self, spec: &str) -> Result<Arc<dyn StrategyFactory<PosT>>, Error> {
+        let mut factories = self.client_factories.lock().unwrap();
+        if let Some(factory) = factories.get(spec) {
+            return Ok(Arc::clone(factory))
+        }
+        // Clients may send any number of specs; games holding a dropped factory keep it
+        if factories.len() >= MAX_CLIENT_FACTORIES {
+            factories.clear();
+        }
+        let factory: Arc<dyn StrategyFactory<PosT>> = Spec::parse(spec)
+            .and_then(|spec| spec::build_for_client(&spec))
+            .map_err(|e| Error::invalid_params(format!("invalid strategy: {}", e)))?
+            .into();
+        factories.insert(spec.to_string(), Arc::clone(&factory));
+        Ok(factory)
+    }
+
+    fn create_strategy(&self, level: Option<u8>, strategy: Option<&str>) -> Result<Engine<PosT>, Error> {
+        let seed = self.next_seed();
+        let strategy = match strategy {
+            Some(spec) => self.client_factory(spec)?.create(derive_seed(seed, 0)),
+            None => self.strategy_factory.create(derive_seed(seed, 0)),
+        };
+        Ok(match level {
+            Some(level) => Box::new(SkillLimitedStrategy::new(level, derive_seed(seed, 1), strategy)),
+            None => strategy,
+        })
+    }
+
+    // The game's pondering engine, or a fresh one
+    fn checkout(&self, game_id: &str, level: Option<u8>, strategy: Option<&str>)
+        -> Result<Ponderer<PosT, Engine<PosT>>, Error>
+    {
+        let kept = self.engines.lock().unwrap().remove(game_id);
+        match kept {
+            Some(engine) => Ok(engine),
+            None => Ok(Ponderer::new(self.create_strategy(level, strategy)?)),
         }
-        let mut strategy = self.strategy_factory.create();
+    }
+
+    // Lets the engine ponder on `pos` until the game's next move, if the server ponders
+    fn checkin(&self, game_id: &str, mut engine: Ponderer<PosT, Engine<PosT>>, pos: &PosT) {
+        if self.ponder {
+            engine.start(pos.clone());
+            self.engines.lock().unwrap().insert(game_id.to_string(), engine);
+        }
+    }
+}
+
+impl<PosT: RegisteredGame, FactoryT: StrategyFactory<PosT>> GameHost for GameServer<PosT, FactoryT> {
+    fn start_game(&self, request: StartGameRequest, limits: &SearchLimits) -> Result<Value, Error> {
+        // Canonical form, so that equal specs share a factory
+        let spec = request.strategy.as_deref().map(Spec::parse).transpose()
+            .map_err(|e| Error::invalid_params(format!("invalid strategy: {}", e)))?
+            .map(|spec| spec.to_string());
+        let mut strategy = Ponderer::new(self.create_strategy(request.level, spec.as_deref())?);
         let (pos, last_move) = if request.player == 0 {
             (PosT::initial(), None)
         } else {
             let initial = PosT::initial();
-            let mv = strategy.choose_move(&initial).unwrap();
-            let new_pos = initial.make_move(&mv).unwrap();
+            let mv = strategy.engine().choose_move_with_limits(&initial, limits).ok_or_else(no_move_error)?;
+            let new_pos = initial.make_move(&mv).ok_or_else(Error::internal_error)?;
             (new_pos, Some(mv))
         };
         let game_id = self.registry.lock().unwrap()
-            .insert(GameEntry { human_player: request.player, position: pos.to_str() });
+            .insert(GameEntry {
+                game: PosT::KIND,
+                human_player: request.player,
+                position: pos.to_str(),
+                level: request.level,
+                strategy: spec,
+            });
+        self.checkin(&game_id, strategy, &pos);
         let response = StartGameResponse {
             game_id,
             position: pos.to_str(),
@@ -128,27 +348,18 @@ impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> GameS
         Ok(serde_json::to_value(&response).unwrap())
     }
 
-    fn remove_game(&self, params: Params) -> Result<Value, Error> {
-        let request: RemoveGameRequest = params.parse()
-            .map_err(|e| Error::invalid_params(e.message))?;
-        self.registry.lock().unwrap().remove(&request.game_id);
-        Ok(Value::Null)
-    }
-
-    fn make_move(&self, params: Params) -> Result<Value, Error> {
-        let request: MakeMoveRequest = params.parse()
-            .map_err(|e| Error::invalid_params(e.message))?;
-        let pos_str = {
+    fn make_move(&self, request: MakeMoveRequest, limits: &SearchLimits) -> Result<Value, Error> {
+        let (pos_str, level, strategy) = {
             let registry = self.registry.lock().unwrap();
             let entry = registry.get(&request.game_id)
                 .ok_or_else(|| Error::invalid_params("unknown game_id"))?;
-            entry.position.clone()
+            (entry.position.clone(), entry.level, entry.strategy.clone())
         };
-        let mut strategy = self.strategy_factory.create();
         let pos = PosT::from_str(&pos_str).expect("registry position must be valid");
         let Some(new_pos) = pos.make_move(&request.move_) else {
             return Err(Error::invalid_params("invalid move"));
         };
+        let mut strategy = self.checkout(&request.game_id, level, strategy.as_deref())?;
         if new_pos.is_lost() {
             self.registry.lock().unwrap().remove(&request.game_id);
             let response = MakeMoveResponse {
@@ -159,7 +370,8 @@ impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> GameS
             };
             return Ok(serde_json::to_value(&response).unwrap());
         }
-        let my_move = strategy.choose_move(&new_pos).unwrap();
+        // The human's move is not recorded, so the client can send it again
+        let my_move = strategy.engine().choose_move_with_limits(&new_pos, limits).ok_or_else(no_move_error)?;
         let Some(my_new_pos) = new_pos.make_move(&my_move) else {
             return Err(Error::internal_error());
         };
@@ -168,6 +380,7 @@ impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> GameS
             Some(GameResult::IWon)
         } else {
             self.registry.lock().unwrap().games.get_mut(&request.game_id).unwrap().position = my_new_pos.to_str();
+            self.checkin(&request.game_id, strategy, &my_new_pos);
             None
         };
         let response = MakeMoveResponse {
@@ -178,14 +391,199 @@ impl<PosT: AbstractGame + Send + 'static, FactoryT: StrategyFactory<PosT>> GameS
         };
         Ok(serde_json::to_value(&response).unwrap())
     }
+
+    fn analyze_position(&self, request: AnalyzePositionRequest, limits: &SearchLimits) -> Result<Value, Error> {
+        let pos = PosT::from_str(&request.position)
+            .ok_or_else(|| Error::invalid_params("invalid position"))?;
+        let mut engine = self.strategy_factory.create_analysis(self.next_seed())
+            .ok_or_else(|| Error::invalid_params("the server's strategy cannot analyze positions"))?;
+        let moves = if pos.is_lost() { Vec::new() } else { engine.analyze(&pos, request.num_pv, limits) };
+        let response = AnalyzePositionResponse { position: pos.to_str(), moves };
+        Ok(serde_json::to_value(&response).unwrap())
+    }
+
+    fn get_hint(&self, request: GetHintRequest, limits: &SearchLimits) -> Result<Value, Error> {
+        let pos_str = self.registry.lock().unwrap().get(&request.game_id)
+            .ok_or_else(|| Error::invalid_params("unknown game_id"))?
+            .position.clone();
+        let pos = PosT::from_str(&pos_str).expect("registry position must be valid");
+        let mut engine = self.strategy_factory.create_analysis(self.next_seed())
+            .ok_or_else(|| Error::invalid_params("the server's strategy cannot analyze positions"))?;
+        let hint = hint::hint(&pos, &mut engine, limits, HINT_MATE_NODES)
+            .ok_or_else(Error::internal_error)?;
+        let response = GetHintResponse { position: pos.to_str(), hint };
+        Ok(serde_json::to_value(&response).unwrap())
+    }
+
+    fn best_move(&self, request: BestMoveRequest, limits: &SearchLimits) -> Result<Value, Error> {
+        let pos = PosT::from_str(&request.position)
+            .ok_or_else(|| Error::invalid_params("invalid position"))?;
+        let mv = if pos.is_lost() {
+            None
+        } else {
+            self.create_strategy(request.level, None)?.choose_move_with_limits(&pos, limits)
+        };
+        Ok(serde_json::to_value(&BestMoveResponse { mv }).unwrap())
+    }
+
+    fn dump_tree(&self, request: DumpTreeRequest, limits: &SearchLimits) -> Result<Value, Error> {
+        let pos = PosT::from_str(&request.position)
+            .ok_or_else(|| Error::invalid_params("invalid position"))?;
+        if pos.is_lost() {
+            return Err(Error::invalid_params("the game is over"));
+        }
+        let mut engine = self.create_strategy(None, None)?;
+        let mv = engine.choose_move_with_limits(&pos, limits);
+        let tree = engine.dump_tree(&pos, request.depth, request.min_visits)
+            .ok_or_else(|| Error::invalid_params("the server's strategy keeps no search tree"))?;
+        let dot = tree.to_dot();
+        let response = DumpTreeResponse { position: pos.to_str(), mv, tree, dot };
+        Ok(serde_json::to_value(&response).unwrap())
+    }
+
+    fn remove_game(&self, game_id: &str) {
+        self.engines.lock().unwrap().remove(game_id);
+    }
 }
 
-pub fn create_io_handler<PosT, FactoryT>(factory: FactoryT) -> IoHandler
-where
-    PosT: AbstractGame + Send + 'static,
-    FactoryT: StrategyFactory<PosT> + 'static,
-{
-    let server = Arc::new(GameServer::new(factory));
+/// All games the RPC server can host, sharing one game registry.
+/// `start_game` picks the game from its `game` field (or `default_game`);
+/// later calls find it through the registry entry. With a `seed`, game ids and
+/// the engines' random choices repeat from run to run.
+pub struct GameHosts {
+    default_game: GameKind,
+    default_movetime: Option<Duration>,
+    registry: Arc<Mutex<GameRegistry>>,
+    hosts: HashMap<GameKind, Box<dyn GameHost>>,
+    ponder: bool,
+}
+
+impl GameHosts {
+    pub fn new(default_game: GameKind, default_movetime: Option<Duration>, seed: Option<u64>) -> Self {
+        let rng = seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        GameHosts {
+            default_game,
+            default_movetime,
+            registry: Arc::new(Mutex::new(GameRegistry::new(rng))),
+            hosts: HashMap::new(),
+            ponder: false,
+        }
+    }
+
+    /// Let the engines of games added from now on think while waiting for the
+    /// client's move
+    pub fn ponder(mut self, ponder: bool) -> Self {
+        self.ponder = ponder;
+        self
+    }
+
+    pub fn add<PosT, FactoryT>(&mut self, factory: FactoryT)
+    where
+        PosT: RegisteredGame,
+        FactoryT: StrategyFactory<PosT> + 'static,
+    {
+        let server = GameServer::new(Arc::clone(&self.registry), factory, self.ponder);
+        self.hosts.insert(PosT::KIND, Box::new(server));
+    }
+
+    fn limits(&self, movetime_ms: Option<u64>) -> SearchLimits {
+        SearchLimits::with_movetime(movetime_ms.map(Duration::from_millis).or(self.default_movetime))
+    }
+
+    fn start_game(&self, params: Params) -> Result<Value, Error> {
+        let request: StartGameRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        if request.player != 0 && request.player != 1 {
+            return Err(Error::invalid_params("player must be 0 or 1"));
+        }
+        if request.level.is_some_and(|level| !skill::is_valid_level(level)) {
+            return Err(Error::invalid_params("level must be between 1 and 10"));
+        }
+        let game = request.game.unwrap_or(self.default_game);
+        let host = self.hosts.get(&game)
+            .ok_or_else(|| Error::invalid_params("game is not served"))?;
+        let limits = self.limits(request.movetime_ms);
+        host.start_game(request, &limits)
+    }
+
+    fn remove_game(&self, params: Params) -> Result<Value, Error> {
+        let request: RemoveGameRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        let game = {
+            let mut registry = self.registry.lock().unwrap();
+            let game = registry.get(&request.game_id).map(|entry| entry.game);
+            registry.remove(&request.game_id);
+            game
+        };
+        if let Some(host) = game.and_then(|game| self.hosts.get(&game)) {
+            host.remove_game(&request.game_id);
+        }
+        Ok(Value::Null)
+    }
+
+    fn make_move(&self, params: Params) -> Result<Value, Error> {
+        let request: MakeMoveRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        let game = self.registry.lock().unwrap().get(&request.game_id)
+            .ok_or_else(|| Error::invalid_params("unknown game_id"))?
+            .game;
+        let host = self.hosts.get(&game)
+            .ok_or_else(|| Error::invalid_params("game is not served"))?;
+        let limits = self.limits(request.movetime_ms);
+        host.make_move(request, &limits)
+    }
+
+    fn analyze_position(&self, params: Params) -> Result<Value, Error> {
+        let request: AnalyzePositionRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        let game = request.game.unwrap_or(self.default_game);
+        let host = self.hosts.get(&game)
+            .ok_or_else(|| Error::invalid_params("game is not served"))?;
+        let limits = self.limits(request.movetime_ms);
+        host.analyze_position(request, &limits)
+    }
+
+    fn get_hint(&self, params: Params) -> Result<Value, Error> {
+        let request: GetHintRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        let game = self.registry.lock().unwrap().get(&request.game_id)
+            .ok_or_else(|| Error::invalid_params("unknown game_id"))?
+            .game;
+        let host = self.hosts.get(&game)
+            .ok_or_else(|| Error::invalid_params("game is not served"))?;
+        let limits = self.limits(request.movetime_ms);
+        host.get_hint(request, &limits)
+    }
+
+    fn best_move(&self, params: Params) -> Result<Value, Error> {
+        let request: BestMoveRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        if request.level.is_some_and(|level| !skill::is_valid_level(level)) {
+            return Err(Error::invalid_params("level must be between 1 and 10"));
+        }
+        let game = request.game.unwrap_or(self.default_game);
+        let host = self.hosts.get(&game)
+            .ok_or_else(|| Error::invalid_params("game is not served"))?;
+        let limits = self.limits(request.movetime_ms);
+        host.best_move(request, &limits)
+    }
+
+    fn dump_tree(&self, params: Params) -> Result<Value, Error> {
+        let request: DumpTreeRequest = params.parse()
+            .map_err(|e| Error::invalid_params(e.message))?;
+        if request.depth > MAX_DUMP_DEPTH {
+            return Err(Error::invalid_params(format!("depth must be at most {}", MAX_DUMP_DEPTH)));
+        }
+        let game = request.game.unwrap_or(self.default_game);
+        let host = self.hosts.get(&game)
+            .ok_or_else(|| Error::invalid_params("game is not served"))?;
+        let limits = self.limits(request.movetime_ms);
+        host.dump_tree(request, &limits)
+    }
+}
+
+pub fn create_io_handler(hosts: GameHosts) -> IoHandler {
+    let server = Arc::new(hosts);
     let mut io = IoHandler::default();
     let s1 = Arc::clone(&server);
     io.add_sync_method("start_game", move |params| s1.start_game(params));
@@ -193,6 +591,14 @@ where
     io.add_sync_method("make_move", move |params| s2.make_move(params));
     let s3 = Arc::clone(&server);
     io.add_sync_method("remove_game", move |params| s3.remove_game(params));
+    let s4 = Arc::clone(&server);
+    io.add_sync_method("analyze_position", move |params| s4.analyze_position(params));
+    let s5 = Arc::clone(&server);
+    io.add_sync_method("get_hint", move |params| s5.get_hint(params));
+    let s6 = Arc::clone(&server);
+    io.add_sync_method("best_move", move |params| s6.best_move(params));
+    let s7 = Arc::clone(&server);
+    io.add_sync_method("dump_tree", move |params| s7.dump_tree(params));
     io
 }
 
@@ -205,7 +611,10 @@ use crate::{kids_shogi, mcts::MctsFactory};
 
 fn test_io() -> IoHandler {
     static EVAL: kids_shogi::SimpleEvaluator = kids_shogi::SimpleEvaluator {};
-    create_io_handler(MctsFactory::new(&EVAL, 1000, 3.0, 8))
+    // Seeded so that the server's replies do not vary between runs
+    let mut hosts = GameHosts::new(GameKind::KidsShogi, None, Some(1));
+    hosts.add(MctsFactory::new(&EVAL, 1000, 3.0, 8));
+    create_io_handler(hosts)
 }
 
 #[test]
@@ -227,8 +636,9 @@ fn start_game() {
     let value1 = serde_json::from_str::<Value>(&response1).unwrap();
     let resp1: StartGameResponse = serde_json::from_value(
         value1.get("result").unwrap().clone()).unwrap();
-    assert_eq!(resp1.position, "gle/1C1/3/ELG w C");
-    assert_eq!(resp1.last_move, Some("b2b3".to_string()));
+    // The seeded server always opens the same way
+    assert_eq!(resp1.position, "gle/1c1/1CG/EL1 w -");
+    assert_eq!(resp1.last_move, Some("c1c2".to_string()));
     assert_eq!(resp1.game_id.len(), 16);
 }
 
@@ -280,6 +690,37 @@ fn invalid_move_rejected() {
     assert!(move_val.get("error").is_some());
 }
 
+// Never finds a move, like an external engine that keeps crashing
+struct GivesUp;
+
+impl StrategyEngine<kids_shogi::KidsShogiGame> for GivesUp {
+    fn choose_move(&mut self, _pos: &kids_shogi::KidsShogiGame) -> Option<String> {
+        None
+    }
+}
+
+impl StrategyFactory<kids_shogi::KidsShogiGame> for GivesUp {
+    fn create(&self, _seed: u64) -> Box<dyn StrategyEngine<kids_shogi::KidsShogiGame> + Send> {
+        Box::new(GivesUp)
+    }
+}
+
+#[test]
+fn strategy_without_a_move_is_an_error() {
+    let mut hosts = GameHosts::new(GameKind::KidsShogi, None, Some(1));
+    hosts.add(GivesUp);
+    let io = create_io_handler(hosts);
+    let call = |request: &str| serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    let opening = call(r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":1}, "id":1}"#);
+    assert_eq!(opening["error"]["message"], "the strategy found no move");
+
+    let start = call(r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":0}, "id":2}"#);
+    let game_id = start["result"]["game_id"].as_str().unwrap();
+    let reply = call(&format!(
+        r#"{{"jsonrpc": "2.0", "method":"make_move", "params":{{"game_id":"{game_id}", "move":"b2b3"}}, "id":3}}"#));
+    assert_eq!(reply["error"]["message"], "the strategy found no move");
+}
+
 #[test]
 fn unknown_game_id_rejected() {
     let io = test_io();
@@ -289,4 +730,191 @@ fn unknown_game_id_rejected() {
     assert!(value.get("error").is_some());
 }
 
+#[test]
+fn start_game_by_name() {
+    let io = test_io();
+    let request = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":0, "game":"kids_shogi"}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    let resp: StartGameResponse = serde_json::from_value(
+        value.get("result").unwrap().clone()).unwrap();
+    assert_eq!(resp.position, "gle/1c1/1C1/ELG b -");
+
+    let bad_request = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":0, "game":"chess"}, "id":2}"#;
+    let bad_value = serde_json::from_str::<Value>(&io.handle_request_sync(bad_request).unwrap()).unwrap();
+    assert!(bad_value.get("error").is_some());
+}
+
+#[test]
+fn seeded_servers_repeat_themselves() {
+    let request = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":1}, "id":1}"#;
+    let first = test_io().handle_request_sync(request).unwrap();
+    let second = test_io().handle_request_sync(request).unwrap();
+    assert_eq!(first, second);  // same game id and same opening move
+}
+
+#[test]
+fn make_move_with_movetime() {
+    static EVAL: kids_shogi::SimpleEvaluator = kids_shogi::SimpleEvaluator {};
+    let mut hosts = GameHosts::new(GameKind::KidsShogi, None, None);
+    hosts.add(crate::alphabeta::AlphaBetaFactory::new(&EVAL, 64, 1));
+    let io = create_io_handler(hosts);
+
+    let start_req = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":1, "movetime_ms":50}, "id":1}"#;
+    let start_val = serde_json::from_str::<Value>(&io.handle_request_sync(start_req).unwrap()).unwrap();
+    let start_resp: StartGameResponse = serde_json::from_value(
+        start_val.get("result").unwrap().clone()).unwrap();
+    let game_id = &start_resp.game_id;
+
+    let t0 = std::time::Instant::now();
+    let move_req = format!(
+        r#"{{"jsonrpc": "2.0", "method":"make_move", "params":{{"game_id":"{game_id}", "move":"{}", "movetime_ms":50}}, "id":2}}"#,
+        start_resp.possible_moves[0]);
+    let move_val = serde_json::from_str::<Value>(&io.handle_request_sync(&move_req).unwrap()).unwrap();
+    assert!(move_val.get("error").is_none(), "unexpected error: {move_val}");
+    assert!(t0.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn analyze_position() {
+    let io = test_io();
+    let request = r#"{"jsonrpc": "2.0", "method":"analyze_position", "params":{"position":"l2/G2/3/L2 b -", "num_pv":2}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    let resp: AnalyzePositionResponse = serde_json::from_value(
+        value.get("result").unwrap().clone()).unwrap();
+    assert_eq!(resp.moves.len(), 2);
+    assert_eq!(resp.moves[0].mv, "a3a4");  // takes the lion
+    assert_eq!(resp.moves[0].pv[0], "a3a4");
+    assert!(resp.moves[0].visits.is_some());
+
+    let bad_request = r#"{"jsonrpc": "2.0", "method":"analyze_position", "params":{"position":"nonsense"}, "id":2}"#;
+    let bad_value = serde_json::from_str::<Value>(&io.handle_request_sync(bad_request).unwrap()).unwrap();
+    assert!(bad_value.get("error").is_some());
+}
+
+#[test]
+fn get_hint() {
+    let io = test_io();
+    let start_req = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":0}, "id":1}"#;
+    let start_resp: StartGameResponse = serde_json::from_value(
+        serde_json::from_str::<Value>(&io.handle_request_sync(start_req).unwrap())
+            .unwrap().get("result").unwrap().clone()).unwrap();
+    let game_id = &start_resp.game_id;
+
+    let hint_req = format!(
+        r#"{{"jsonrpc": "2.0", "method":"get_hint", "params":{{"game_id":"{game_id}"}}, "id":2}}"#);
+    let hint_val = serde_json::from_str::<Value>(&io.handle_request_sync(&hint_req).unwrap()).unwrap();
+    assert!(hint_val.get("error").is_none(), "unexpected error: {hint_val}");
+    let resp: GetHintResponse = serde_json::from_value(hint_val.get("result").unwrap().clone()).unwrap();
+    assert_eq!(resp.position, start_resp.position);
+    assert!(start_resp.possible_moves.contains(&resp.hint.mv));
+    assert!(!resp.hint.reasons.is_empty());
+
+    let bad_request = r#"{"jsonrpc": "2.0", "method":"get_hint", "params":{"game_id":"deadbeefdeadbeef"}, "id":3}"#;
+    let bad_value = serde_json::from_str::<Value>(&io.handle_request_sync(bad_request).unwrap()).unwrap();
+    assert!(bad_value.get("error").is_some());
+}
+
+#[test]
+fn best_move() {
+    let io = test_io();
+    let request = r#"{"jsonrpc": "2.0", "method":"best_move", "params":{"position":"l2/G2/3/L2 b -"}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    let resp: BestMoveResponse = serde_json::from_value(value.get("result").unwrap().clone()).unwrap();
+    assert_eq!(resp.mv, Some("a3a4".to_string()));
+
+    let over = r#"{"jsonrpc": "2.0", "method":"best_move", "params":{"position":"3/1L1/3/3 w L"}, "id":2}"#;
+    let over_value = serde_json::from_str::<Value>(&io.handle_request_sync(over).unwrap()).unwrap();
+    assert_eq!(over_value["result"], serde_json::json!({"move": null}));
+
+    let bad_request = r#"{"jsonrpc": "2.0", "method":"best_move", "params":{"position":"l2/G2/3/L2 b -", "level":0}, "id":3}"#;
+    let bad_value = serde_json::from_str::<Value>(&io.handle_request_sync(bad_request).unwrap()).unwrap();
+    assert!(bad_value.get("error").is_some());
+}
+
+#[test]
+fn dump_tree() {
+    let io = test_io();
+    let request = r#"{"jsonrpc": "2.0", "method":"dump_tree", "params":{"position":"l2/G2/3/L2 b -", "depth":1}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    assert!(value.get("error").is_none(), "unexpected error: {value}");
+    let resp: DumpTreeResponse = serde_json::from_value(value.get("result").unwrap().clone()).unwrap();
+    assert_eq!(resp.mv, Some("a3a4".to_string()));
+    assert_eq!(resp.tree.position, "l2/G2/3/L2 b -");
+    assert!(resp.tree.visits > 0);
+    assert!(resp.tree.children.iter().all(|c| c.children.is_empty() && (c.visits >= 1 || c.proof.is_some())));
+    assert!(resp.dot.starts_with("digraph"));
+
+    let deep = r#"{"jsonrpc": "2.0", "method":"dump_tree", "params":{"position":"l2/G2/3/L2 b -", "depth":17}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(deep).unwrap()).unwrap();
+    assert!(value.get("error").is_some());
+
+    // Alpha-beta keeps no tree
+    static EVAL: kids_shogi::SimpleEvaluator = kids_shogi::SimpleEvaluator {};
+    let mut hosts = GameHosts::new(GameKind::KidsShogi, None, Some(1));
+    hosts.add(crate::alphabeta::AlphaBetaFactory::new(&EVAL, 2, 1));
+    let value = serde_json::from_str::<Value>(&create_io_handler(hosts).handle_request_sync(request).unwrap()).unwrap();
+    assert!(value.get("error").is_some());
+}
+
+#[test]
+fn pondering_keeps_engines_between_moves() {
+    static EVAL: kids_shogi::SimpleEvaluator = kids_shogi::SimpleEvaluator {};
+    let registry = Arc::new(Mutex::new(GameRegistry::new(StdRng::seed_from_u64(1))));
+    let server = GameServer::<kids_shogi::KidsShogiGame, _>::new(
+        registry, crate::alphabeta::AlphaBetaFactory::new(&EVAL, 4, 1), true);
+    let start: StartGameResponse = serde_json::from_value(
+        server.start_game(StartGameRequest { player: 1, game: None, movetime_ms: None, level: None, strategy: None }, &SearchLimits::default())
+            .unwrap()).unwrap();
+    assert!(server.engines.lock().unwrap().contains_key(&start.game_id));
+    let request = MakeMoveRequest { game_id: start.game_id.clone(), move_: start.possible_moves[0].clone(), movetime_ms: None };
+    let reply: MakeMoveResponse = serde_json::from_value(server.make_move(request, &SearchLimits::default()).unwrap()).unwrap();
+    assert!(reply.last_move.is_some());
+    assert_eq!(server.engines.lock().unwrap().len(), 1);
+    server.remove_game(&start.game_id);
+    assert!(server.engines.lock().unwrap().is_empty());
+}
+
+#[test]
+fn start_game_with_strategy() {
+    let io = test_io();
+    let request = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":1, "strategy":"alphabeta( depth=2 )"}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    assert!(value.get("error").is_none(), "unexpected error: {value}");
+    let resp: StartGameResponse = serde_json::from_value(value.get("result").unwrap().clone()).unwrap();
+    let move_req = format!(
+        r#"{{"jsonrpc": "2.0", "method":"make_move", "params":{{"game_id":"{}", "move":"{}"}}, "id":2}}"#,
+        resp.game_id, resp.possible_moves[0]);
+    let move_val = serde_json::from_str::<Value>(&io.handle_request_sync(&move_req).unwrap()).unwrap();
+    assert!(move_val.get("error").is_none(), "unexpected error: {move_val}");
+
+    // Clients cannot make the server run programs or read files
+    for strategy in ["minimax", "external(command=rm)", "mcts(eval=neuro:ks.model)", "mcts("] {
+        let bad_request = format!(
+            r#"{{"jsonrpc": "2.0", "method":"start_game", "params":{{"player":0, "strategy":"{strategy}"}}, "id":3}}"#);
+        let bad_value = serde_json::from_str::<Value>(&io.handle_request_sync(&bad_request).unwrap()).unwrap();
+        assert!(bad_value["error"]["message"].as_str().unwrap().starts_with("invalid strategy"), "{bad_value}");
+    }
+}
+
+#[test]
+fn start_game_with_level() {
+    let io = test_io();
+    let request = r#"{"jsonrpc": "2.0", "method":"start_game", "params":{"player":0, "level":3}, "id":1}"#;
+    let value = serde_json::from_str::<Value>(&io.handle_request_sync(request).unwrap()).unwrap();
+    let resp: StartGameResponse = serde_json::from_value(
+        value.get("result").unwrap().clone()).unwrap();
+    let game_id = &resp.game_id;
+    let move_req = format!(
+        r#"{{"jsonrpc": "2.0", "method":"make_move", "params":{{"game_id":"{game_id}", "move":"b2b3"}}, "id":2}}"#);
+    let move_val = serde_json::from_str::<Value>(&io.handle_request_sync(&move_req).unwrap()).unwrap();
+    assert!(move_val.get("error").is_none(), "unexpected error: {move_val}");
+
+    for bad_level in [0, 11] {
+        let bad_request = format!(
+            r#"{{"jsonrpc": "2.0", "method":"start_game", "params":{{"player":0, "level":{bad_level}}}, "id":3}}"#);
+        let bad_value = serde_json::from_str::<Value>(&io.handle_request_sync(&bad_request).unwrap()).unwrap();
+        assert!(bad_value.get("error").is_some());
+    }
+}
+
 }
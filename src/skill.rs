@@ -0,0 +1,259 @@
+// Weakened play for beginners: skill levels with deliberate mistakes
+
+use std::marker::PhantomData;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::{derive_seed, SearchLimits, StrategyEngine};
+use crate::tree_dump::TreeNode;
+
+pub const MIN_LEVEL: u8 = 1;
+pub const MAX_LEVEL: u8 = 10;
+
+/// Chance that a move is a blunder, by level (index 0 is level 1). Checked with
+/// `--calibrate-skill`: each level should score clearly above the one below it.
+const BLUNDER_RATE: [f64; MAX_LEVEL as usize] = [0.7, 0.55, 0.42, 0.36, 0.22, 0.15, 0.1, 0.05, 0.02, 0.0];
+/// From this level on, a win in one move is never missed
+const SEES_WINS_FROM: u8 = 4;
+/// From this level on, blunders avoid moves that let the opponent win at once
+const SAFE_BLUNDERS_FROM: u8 = 7;
+
+pub fn is_valid_level(level: u8) -> bool {
+    (MIN_LEVEL..=MAX_LEVEL).contains(&level)
+}
+
+fn wins_at_once<PosT: ag::AbstractGame>(pos: &PosT, mv: &str) -> bool {
+    pos.make_move(mv).is_some_and(|p| p.is_lost())
+}
+
+/// Plays `followup`'s move, except that with the level's blunder rate it plays a
+/// random move instead. Low levels may also overlook a win in one; higher levels
+/// never do, and their blunders do not hand the opponent a win in one.
+pub struct SkillLimitedStrategy<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> {
+    level: u8,
+    followup: F,
+    rng: StdRng,
+    pos_type: PhantomData<PosT>,
+}
+
+impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> SkillLimitedStrategy<PosT, F> {
+    /// `level` is clamped to `MIN_LEVEL..=MAX_LEVEL`
+    pub fn new(level: u8, seed: u64, followup: F) -> Self {
+        SkillLimitedStrategy {
+            level: level.clamp(MIN_LEVEL, MAX_LEVEL),
+            followup,
+            rng: StdRng::seed_from_u64(seed),
+            pos_type: PhantomData,
+        }
+    }
+
+    fn blunder_rate(&self) -> f64 {
+        BLUNDER_RATE[(self.level - MIN_LEVEL) as usize]
+    }
+
+    // The move to play instead of asking `followup`, if any
+    fn own_move(&mut self, pos: &PosT) -> Option<String> {
+        let moves = pos.possible_moves();
+        if self.level >= SEES_WINS_FROM {
+            if let Some(mv) = moves.iter().find(|mv| wins_at_once(pos, mv)) {
+                return Some(mv.clone())
+            }
+        }
+        if !self.rng.gen_bool(self.blunder_rate()) {
+            return None
+        }
+        let safe = moves.iter()
+            .filter(|mv| {
+                let next = pos.make_move(mv).unwrap();
+                !next.possible_moves().iter().any(|reply| wins_at_once(&next, reply))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let candidates = if self.level >= SAFE_BLUNDERS_FROM && !safe.is_empty() { &safe } else { &moves };
+        candidates.choose(&mut self.rng).cloned()
+    }
+}
+
+impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> StrategyEngine<PosT> for SkillLimitedStrategy<PosT, F> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.own_move(pos).or_else(|| self.followup.choose_move(pos))
+    }
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        self.own_move(pos).or_else(|| self.followup.choose_move_with_limits(pos, limits))
+    }
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        self.followup.ponder(pos, limits)
+    }
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        self.followup.dump_tree(pos, depth, min_visits)
+    }
+}
+
+/// Wraps the strategies of another factory in a `SkillLimitedStrategy`.
+pub struct SkillFactory<F> {
+    level: u8,
+    inner: F,
+}
+
+impl<F> SkillFactory<F> {
+    pub fn new(level: u8, inner: F) -> Self {
+        SkillFactory { level, inner }
+    }
+}
+
+impl<PosT: ag::AbstractGame + Send + 'static, F: ag::StrategyFactory<PosT>> ag::StrategyFactory<PosT> for SkillFactory<F> {
+    fn create(&self, seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(SkillLimitedStrategy::new(self.level, derive_seed(seed, 0), self.inner.create(derive_seed(seed, 1))))
+    }
+    /// Analysis is left at full strength
+    fn create_analysis(&self, seed: u64) -> Option<Box<dyn crate::analysis::AnalysisEngine<PosT> + Send>> {
+        self.inner.create_analysis(seed)
+    }
+}
+
+/// Play one game from the initial position; `engines[0]` is Sente. Returns the
+/// winner, or `None` if the game reached `max_plies`. An engine that finds no
+/// move (an external engine that crashed or timed out, say) loses.
+pub fn play_game<PosT: ag::AbstractGame>(
+    engines: [&mut dyn StrategyEngine<PosT>; 2], max_plies: usize,
+) -> Option<i32> {
+    let mut pos = PosT::initial();
+    for _ in 0..max_plies {
+        if pos.is_lost() {
+            return Some(1 - pos.current_player())
+        }
+        let Some(mv) = engines[pos.current_player() as usize].choose_move(&pos) else {
+            return Some(1 - pos.current_player())
+        };
+        pos = pos.make_move(&mv).expect("engine must play legal moves");
+    }
+    pos.is_lost().then(|| 1 - pos.current_player())
+}
+
+/// Score of one player against another over `games` games with colours
+/// alternating, the first player taking Sente in the first game (1 per win, 0.5
+/// per draw, divided by `games`). `make_engine(player, game)` makes a fresh engine
+/// for every game, player 0 being the first.
+pub fn match_score<PosT: ag::AbstractGame, S: StrategyEngine<PosT>>(
+    mut make_engine: impl FnMut(usize, u64) -> S, games: usize, max_plies: usize,
+) -> f64 {
+    let mut points = 0.0;
+    for game in 0..games {
+        let mut first = make_engine(0, game as u64);
+        let mut second = make_engine(1, game as u64);
+        let first_side = (game % 2) as i32;
+        let engines: [&mut dyn StrategyEngine<PosT>; 2] = if first_side == 0 {
+            [&mut first, &mut second]
+        } else {
+            [&mut second, &mut first]
+        };
+        points += match play_game(engines, max_plies) {
+            Some(winner) if winner == first_side => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+    }
+    points / games.max(1) as f64
+}
+
+/// Match every level against the next one, `games` games per pair with colours
+/// alternating. Returns, for each level from 2 up, its score against the level
+/// below (1 per win, 0.5 per draw, divided by `games`).
+pub fn calibrate<PosT: ag::AbstractGame, S: StrategyEngine<PosT>>(
+    mut make_engine: impl FnMut(u8, u64) -> S, games: usize, max_plies: usize,
+) -> Vec<(u8, f64)> {
+    (MIN_LEVEL + 1..=MAX_LEVEL).map(|level| {
+        let score = match_score(|player, game| {
+            let seed = (level as u64) << 32 | game;
+            make_engine(level - player as u8, seed ^ player as u64)
+        }, games, max_plies);
+        (level, score)
+    }).collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::AbstractGame;
+    use crate::alphabeta::AlphaBetaStrategy;
+    use crate::kids_shogi::{KidsShogiGame, SimpleEvaluator};
+
+    use super::*;
+
+    #[test]
+    fn top_level_is_the_followup() {
+        let eval = SimpleEvaluator{};
+        let pos = KidsShogiGame::initial();
+        let mut plain = AlphaBetaStrategy::new(&eval, 3, 1);
+        let mut skilled = SkillLimitedStrategy::new(MAX_LEVEL, 5, AlphaBetaStrategy::new(&eval, 3, 1));
+        assert_eq!(skilled.choose_move(&pos), plain.choose_move(&pos));
+    }
+
+    #[test]
+    fn wins_in_one_depend_on_level() {
+        let eval = SimpleEvaluator{};
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        // Level 4 and up never miss the lion capture, even when blundering
+        for seed in 0..20 {
+            let mut strat = SkillLimitedStrategy::new(SEES_WINS_FROM, seed, AlphaBetaStrategy::new(&eval, 1, 1));
+            assert_eq!(strat.choose_move(&pos).unwrap(), "a3a4");
+        }
+        // Level 1 misses it sometimes
+        let misses = (0..40).filter(|&seed| {
+            let mut strat = SkillLimitedStrategy::new(MIN_LEVEL, seed, AlphaBetaStrategy::new(&eval, 1, 1));
+            strat.choose_move(&pos).unwrap() != "a3a4"
+        }).count();
+        assert!(misses > 0 && misses < 40, "misses {}", misses);
+    }
+
+    #[test]
+    fn safe_blunders_keep_the_lion() {
+        // Gote's lion attacks b2; a careless move lets it walk to the last rank or
+        // capture the sente lion. Safe blunders never allow a win in one.
+        let eval = SimpleEvaluator{};
+        let pos = KidsShogiGame::from_fen("3/1l1/3/1L1 b -").unwrap();
+        for seed in 0..30 {
+            let mut strat = SkillLimitedStrategy::new(SAFE_BLUNDERS_FROM, seed, AlphaBetaStrategy::new(&eval, 2, 1));
+            let mv = strat.choose_move(&pos).unwrap();
+            let next = pos.make_move(&mv).unwrap();
+            assert!(!next.possible_moves().iter().any(|r| wins_at_once(&next, r)), "seed {} move {}", seed, mv);
+        }
+    }
+
+    // Never finds a move, like an external engine that keeps crashing
+    struct GivesUp;
+
+    impl StrategyEngine<KidsShogiGame> for GivesUp {
+        fn choose_move(&mut self, _pos: &KidsShogiGame) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    fn failing_to_move_loses() {
+        let eval = SimpleEvaluator{};
+        let score = match_score::<KidsShogiGame, Box<dyn StrategyEngine<KidsShogiGame>>>(|player, _| {
+            if player == 0 { Box::new(AlphaBetaStrategy::new(&eval, 1, 1)) } else { Box::new(GivesUp) }
+        }, 2, 100);
+        assert_eq!(score, 1.0);
+    }
+
+    #[test]
+    fn strongest_beats_weakest() {
+        let eval = SimpleEvaluator{};
+        let mut points = 0;
+        for game in 0..8 {
+            let mut strong = SkillLimitedStrategy::new(MAX_LEVEL, game, AlphaBetaStrategy::new(&eval, 2, 1));
+            let mut weak = SkillLimitedStrategy::new(MIN_LEVEL, game, AlphaBetaStrategy::new(&eval, 2, 1));
+            let strong_side = (game % 2) as i32;
+            let engines: [&mut dyn StrategyEngine<KidsShogiGame>; 2] =
+                if strong_side == 0 { [&mut strong, &mut weak] } else { [&mut weak, &mut strong] };
+            if play_game(engines, 100) == Some(strong_side) {
+                points += 1;
+            }
+        }
+        assert!(points >= 6, "level 10 won only {} of 8", points);
+    }
+}
@@ -0,0 +1,538 @@
+// Engine specs: a small language naming a strategy and its parameters, e.g.
+// `book(file=ks.book,then=alphabeta(depth=8,eval=nnue:ks.nnue))`, and the
+// registry that turns them into strategy factories
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::abstract_game::{Evaluator, StrategyFactory};
+use crate::games::RegisteredGame;
+use crate::neuro::NeuroModel;
+use crate::{alphabeta, book, dfpn, ensemble, external, mcts, nnue, remote, skill, strategy};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecError(String);
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+fn error(message: impl Into<String>) -> SpecError {
+    SpecError(message.into())
+}
+
+/// `name` or `name(key=value,...)`, where every value is again a spec: a plain
+/// word or quoted string is a spec without parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    pub name: String,
+    pub params: Vec<(String, Spec)>,
+}
+
+impl Spec {
+    pub fn word(name: impl ToString) -> Self {
+        Spec { name: name.to_string(), params: Vec::new() }
+    }
+
+    pub fn with(self, key: &str, value: impl ToString) -> Self {
+        self.with_spec(key, Spec::word(value))
+    }
+
+    pub fn with_spec(mut self, key: &str, value: Spec) -> Self {
+        self.params.push((key.to_string(), value));
+        self
+    }
+
+    pub fn parse(text: &str) -> Result<Spec, SpecError> {
+        let mut parser = Parser { text, chars: text.char_indices().collect(), at: 0 };
+        let spec = parser.spec()?;
+        parser.skip_spaces();
+        match parser.peek() {
+            None => Ok(spec),
+            Some(_) => Err(parser.unexpected("end of spec")),
+        }
+    }
+}
+
+impl FromStr for Spec {
+    type Err = SpecError;
+    fn from_str(text: &str) -> Result<Spec, SpecError> {
+        Spec::parse(text)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"(),=\"".contains(c)
+}
+
+/// Canonical form, which parses back to the same spec
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.name.is_empty() && self.name.chars().all(is_word_char) {
+            f.write_str(&self.name)?;
+        } else {
+            write!(f, "\"{}\"", self.name.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        if !self.params.is_empty() {
+            let params = self.params.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>();
+            write!(f, "({})", params.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'t> {
+    text: &'t str,
+    chars: Vec<(usize, char)>,
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).map(|&(_, c)| c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> SpecError {
+        match self.chars.get(self.at) {
+            Some(&(offset, _)) => error(format!("expected {} at {:?} in {:?}", expected, &self.text[offset..], self.text)),
+            None if self.text.is_empty() => error("empty spec"),
+            None => error(format!("expected {} at the end of {:?}", expected, self.text)),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, SpecError> {
+        self.skip_spaces();
+        if self.peek() == Some('"') {
+            self.at += 1;
+            let mut name = String::new();
+            loop {
+                match self.peek() {
+                    Some('"') => break,
+                    Some('\\') if self.chars.get(self.at + 1).is_some() => {
+                        name.push(self.chars[self.at + 1].1);
+                        self.at += 2;
+                    }
+                    Some(c) => {
+                        name.push(c);
+                        self.at += 1;
+                    }
+                    None => return Err(self.unexpected("closing quote")),
+                }
+            }
+            self.at += 1;
+            return Ok(name)
+        }
+        let start = self.at;
+        while self.peek().is_some_and(is_word_char) {
+            self.at += 1;
+        }
+        if self.at == start {
+            return Err(self.unexpected("a name"))
+        }
+        Ok(self.chars[start..self.at].iter().map(|&(_, c)| c).collect())
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SpecError> {
+        self.skip_spaces();
+        if self.peek() != Some(c) {
+            return Err(self.unexpected(&format!("'{}'", c)))
+        }
+        self.at += 1;
+        Ok(())
+    }
+
+    fn spec(&mut self) -> Result<Spec, SpecError> {
+        let mut spec = Spec::word(self.name()?);
+        self.skip_spaces();
+        if self.peek() != Some('(') {
+            return Ok(spec)
+        }
+        self.at += 1;
+        self.skip_spaces();
+        if self.peek() == Some(')') {
+            self.at += 1;
+            return Ok(spec)
+        }
+        loop {
+            let key = self.name()?;
+            self.expect('=')?;
+            spec.params.push((key, self.spec()?));
+            self.skip_spaces();
+            match self.peek() {
+                Some(',') => self.at += 1,
+                Some(')') => {
+                    self.at += 1;
+                    return Ok(spec)
+                }
+                _ => return Err(self.unexpected("',' or ')'")),
+            }
+        }
+    }
+}
+
+// ── Registry ──────────────────────────────────────────────────────────────────
+
+/// Where a spec comes from. Specs sent by RPC clients may only name engines
+/// that run inside the server on built-in evaluators: no files, processes or
+/// connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Local,
+    Client,
+}
+
+// The most a client's spec gets, whatever it asks for
+const CLIENT_MAX_THREADS: usize = 4;
+const CLIENT_MAX_TRIES: usize = 100_000;
+const CLIENT_MAX_DEPTH: i32 = 64;
+const CLIENT_MAX_MEMORY_MIB: usize = 256;
+const CLIENT_MAX_NODES: usize = 2_000_000;
+const CLIENT_MAX_MEMBERS: usize = 4;
+const CLIENT_MAX_NESTING: usize = 4;
+
+type Built<G> = Result<Box<dyn StrategyFactory<G>>, SpecError>;
+
+/// The parameters of one spec, taken one by one by the engine being built
+struct Params<'s> {
+    spec: &'s Spec,
+    taken: Vec<bool>,
+    origin: Origin,
+}
+
+impl<'s> Params<'s> {
+    fn new(spec: &'s Spec, origin: Origin) -> Self {
+        Params { spec, taken: vec![false; spec.params.len()], origin }
+    }
+
+    // The next value of `key` not taken yet
+    fn take(&mut self, key: &str) -> Option<&'s Spec> {
+        let i = (0..self.spec.params.len()).find(|&i| !self.taken[i] && self.spec.params[i].0 == key)?;
+        self.taken[i] = true;
+        Some(&self.spec.params[i].1)
+    }
+
+    fn word(&mut self, key: &str) -> Result<Option<&'s str>, SpecError> {
+        match self.take(key) {
+            Some(value) if !value.params.is_empty() =>
+                Err(error(format!("{}: {} takes a plain value, not {}", self.spec.name, key, value))),
+            value => Ok(value.map(|v| v.name.as_str())),
+        }
+    }
+
+    fn required(&mut self, key: &str) -> Result<&'s str, SpecError> {
+        self.word(key)?.ok_or_else(|| error(format!("{}: {} is required", self.spec.name, key)))
+    }
+
+    fn number<T: FromStr>(&mut self, key: &str, default: T) -> Result<T, SpecError> {
+        match self.word(key)? {
+            Some(text) => text.parse().map_err(|_| error(format!("{}: invalid {} {:?}", self.spec.name, key, text))),
+            None => Ok(default),
+        }
+    }
+
+    // Like `number`, but at least `min`, and capped at `client_max` for clients
+    fn bounded<T: FromStr + PartialOrd + std::fmt::Display>(&mut self, key: &str, default: T, min: T, client_max: T)
+        -> Result<T, SpecError>
+    {
+        let value = self.number(key, default)?;
+        if value < min {
+            return Err(error(format!("{}: {} must be at least {}", self.spec.name, key, min)))
+        }
+        Ok(if self.origin == Origin::Client && value > client_max { client_max } else { value })
+    }
+
+    fn strategy<G: RegisteredGame>(&mut self, key: &str) -> Built<G> {
+        let spec = self.take(key).ok_or_else(|| error(format!("{}: {} is required", self.spec.name, key)))?;
+        build_from(spec, self.origin)
+    }
+
+    fn eval<G: RegisteredGame>(&mut self) -> Result<&'static DynEvaluator<G>, SpecError> {
+        load_eval(self.word("eval")?.unwrap_or("simple"), self.origin)
+            .map_err(|e| error(format!("{}: {}", self.spec.name, e)))
+    }
+
+    fn local_only(&self) -> Result<(), SpecError> {
+        match self.origin {
+            Origin::Local => Ok(()),
+            Origin::Client => Err(error(format!("{} is not available to clients", self.spec.name))),
+        }
+    }
+
+    // A parameter nobody asked for
+    fn unknown(&self) -> Option<&'s str> {
+        let spec = self.spec;
+        spec.params.iter().zip(&self.taken).find(|(_, &taken)| !taken).map(|((key, _), _)| key.as_str())
+    }
+}
+
+/// Any evaluator behind one type, so that factories can be built without
+/// knowing which evaluator a spec names.
+pub struct DynEvaluator<PosT>(Box<dyn Evaluator<PosT> + Send + Sync>);
+
+impl<PosT: crate::abstract_game::AbstractGame> Evaluator<PosT> for DynEvaluator<PosT> {
+    fn evaluate_position(&self, pos: &PosT) -> f64 {
+        self.0.evaluate_position(pos)
+    }
+    fn saturation(&self) -> f64 {
+        self.0.saturation()
+    }
+    fn evaluate_children(&self, pos: &PosT, moves: &[String]) -> Vec<f64> {
+        self.0.evaluate_children(pos, moves)
+    }
+}
+
+// What `leak_once` leaked, by type and key
+type Leaked = HashMap<(TypeId, String), &'static (dyn Any + Send + Sync)>;
+
+static LEAKED: Mutex<Option<Leaked>> = Mutex::new(None);
+
+// `make()` leaked for factories to hold by `'static` reference, once per `key`, so
+// that building the same spec again (as clients may, over and over) leaks nothing more
+fn leak_once<T: Any + Send + Sync>(key: String, make: impl FnOnce() -> T) -> &'static T {
+    let mut leaked = LEAKED.lock().unwrap();
+    let value = *leaked.get_or_insert_with(HashMap::new).entry((TypeId::of::<T>(), key))
+        .or_insert_with(|| Box::leak(Box::new(make())));
+    (value as &dyn Any).downcast_ref().expect("leaked under its own type")
+}
+
+/// Evaluator named by an `eval` value: `simple` (the game's default), `weights`
+/// (default feature weights), `weights:FILE`, `neuro:FILE` or `nnue:FILE`.
+/// Factories hold evaluators by `'static` reference, so they are leaked; those
+/// without a file only once.
+fn load_eval<G: RegisteredGame>(text: &str, origin: Origin) -> Result<&'static DynEvaluator<G>, SpecError> {
+    let cannot_load = |e: std::io::Error| error(format!("cannot load {}: {}", text, e));
+    let (kind, path) = match text.split_once(':') {
+        Some((kind, path)) => (kind, Some(path)),
+        None => (text, None),
+    };
+    if path.is_some() && origin == Origin::Client {
+        return Err(error(format!("eval {} is not available to clients", text)))
+    }
+    let eval: Box<dyn Evaluator<G> + Send + Sync> = match (kind, path) {
+        ("simple", None) => return Ok(leak_once(text.to_string(), || DynEvaluator(Box::new(G::DefaultEval::default())))),
+        ("weights", None) => return Ok(leak_once(text.to_string(), || DynEvaluator(Box::new(G::FeatureEval::default())))),
+        ("weights", Some(path)) => Box::new(G::load_feature_eval(path).map_err(cannot_load)?),
+        ("neuro", Some(path)) => Box::new(G::NeuroEval::load(path).map_err(cannot_load)?),
+        ("nnue", Some(path)) => Box::new(nnue::NnueEvaluator::<G>::load(path).map_err(cannot_load)?),
+        _ => return Err(error(format!("unknown eval {:?} (simple, weights[:FILE], neuro:FILE, nnue:FILE)", text))),
+    };
+    Ok(Box::leak(Box::new(DynEvaluator(eval))))
+}
+
+/// A strategy the registry can build, with its parameters and their defaults
+struct Engine<G> {
+    name: &'static str,
+    usage: &'static str,
+    build: fn(&mut Params) -> Built<G>,
+}
+
+fn engines<G: RegisteredGame>() -> Vec<Engine<G>> {
+    vec![
+        Engine { name: "mcts", usage: "mcts(tries=1000,softness=3,depth=8,threads=1,memory=512,eval=simple,select=softmax|puct,c_puct=1,fpu=0,policy=uniform|eval,verbose=false)", build: |p| {
+            let eval = p.eval::<G>()?;
+            let mut softness: f64 = p.number("softness", 3.0)?;
+            if p.origin == Origin::Client {
+                // Each softness leaks a policy below, so clients get a few
+                softness = (softness.clamp(0.0, 100.0) * 10.0).round() / 10.0;
+            }
+            let memory = p.bounded::<usize>("memory", mcts::DEFAULT_MEMORY_LIMIT >> 20, 0, CLIENT_MAX_MEMORY_MIB)?;
+            let max_memory = mcts::MAX_MEMORY_LIMIT >> 20;
+            if !(1..=max_memory).contains(&memory) {
+                return Err(error(format!("mcts: memory must be between 1 and {} MiB", max_memory)))
+            }
+            let factory = mcts::MctsFactory::new(eval, p.bounded("tries", 1000, 1, CLIENT_MAX_TRIES)?, softness,
+                    p.bounded("depth", 8, 1, CLIENT_MAX_DEPTH)?)
+                .threads(p.bounded("threads", 1, 1, CLIENT_MAX_THREADS)?)
+                .memory_limit(memory << 20);
+            let selection = match p.word("select")?.unwrap_or("softmax") {
+                "softmax" => mcts::Selection::SoftMax,
+                "puct" => mcts::Selection::Puct {
+                    c_puct: p.number("c_puct", mcts::Selection::DEFAULT_C_PUCT)?,
+                    fpu: p.number("fpu", mcts::Selection::DEFAULT_FPU)?,
+                },
+                other => return Err(error(format!("mcts: unknown select {:?} (softmax, puct)", other))),
+            };
+            let factory = match p.word("policy")?.unwrap_or("uniform") {
+                "uniform" => factory,
+                "eval" => factory.policy(leak_once(format!("{:p} {}", eval, softness), || mcts::EvalPolicy::new(eval, softness))),
+                other => return Err(error(format!("mcts: unknown policy {:?} (uniform, eval)", other))),
+            };
+            Ok(Box::new(factory.selection(selection).verbose(p.number("verbose", false)?)))
+        }},
+        Engine { name: "alphabeta", usage: "alphabeta(depth=10,threads=1,eval=simple)", build: |p| {
+            Ok(Box::new(alphabeta::AlphaBetaFactory::new(p.eval::<G>()?, p.bounded("depth", 10, 1, CLIENT_MAX_DEPTH)?,
+                p.bounded("threads", 1, 1, CLIENT_MAX_THREADS)?)))
+        }},
+        Engine { name: "random", usage: "random", build: |_| Ok(Box::new(strategy::RandomMoveFactory)) },
+        Engine { name: "ensemble", usage: "ensemble(member=SPEC,member=SPEC,...,combine=average|vote,verbose=false)", build: |p| {
+            let combine = match p.word("combine")? {
+                Some(text) => <ensemble::Combine as clap::ValueEnum>::from_str(text, true)
+                    .map_err(|_| error(format!("ensemble: unknown combine {:?}", text)))?,
+                None => ensemble::Combine::default(),
+            };
+            let mut factory = ensemble::EnsembleFactory::new(combine).verbose(p.number("verbose", false)?);
+            let mut members = 0;
+            while let Some(member) = p.take("member") {
+                members += 1;
+                if p.origin == Origin::Client && members > CLIENT_MAX_MEMBERS {
+                    return Err(error(format!("ensemble: clients may combine at most {} members", CLIENT_MAX_MEMBERS)))
+                }
+                factory = factory.add(build_from::<G>(member, p.origin)?, 1.0);
+            }
+            Ok(Box::new(factory))
+        }},
+        Engine { name: "external", usage: "external(command=\"PROGRAM ARGS\",timeout=10000)", build: |p| {
+            p.local_only()?;
+            let command = p.required("command")?.split_whitespace().map(String::from).collect();
+            Ok(Box::new(external::ExternalEngineFactory::new(command, Duration::from_millis(p.number("timeout", 10000)?))))
+        }},
+        Engine { name: "remote", usage: "remote(url=http://HOST:PORT/rpc,timeout=10000,retries=3)", build: |p| {
+            p.local_only()?;
+            let url = p.required("url")?;
+            Ok(Box::new(remote::RemoteRpcFactory::new(url, Duration::from_millis(p.number("timeout", 10000)?), p.number("retries", 3)?)))
+        }},
+        Engine { name: "book", usage: "book(file=FILE,then=SPEC)", build: |p| {
+            p.local_only()?;
+            let path = p.required("file")?;
+            let book = book::OpeningBook::load(path).map_err(|e| error(format!("cannot load {}: {}", path, e)))?;
+            Ok(Box::new(book::BookFactory::new(Arc::new(book), p.strategy::<G>("then")?)))
+        }},
+        Engine { name: "mate", usage: "mate(nodes=200000,then=SPEC)", build: |p| {
+            Ok(Box::new(dfpn::DfPnFactory::new(p.bounded("nodes", 200000, 0, CLIENT_MAX_NODES)?, p.strategy::<G>("then")?)))
+        }},
+        Engine { name: "skill", usage: "skill(level=1..10,then=SPEC)", build: |p| {
+            let level = p.number("level", 0)?;
+            if !skill::is_valid_level(level) {
+                return Err(error(format!("skill: level must be between {} and {}", skill::MIN_LEVEL, skill::MAX_LEVEL)))
+            }
+            Ok(Box::new(skill::SkillFactory::new(level, p.strategy::<G>("then")?)))
+        }},
+    ]
+}
+
+fn build_from<G: RegisteredGame>(spec: &Spec, origin: Origin) -> Built<G> {
+    let engines = engines::<G>();
+    let Some(engine) = engines.iter().find(|e| e.name == spec.name) else {
+        let names = engines.iter().map(|e| e.name).collect::<Vec<_>>();
+        return Err(error(format!("unknown strategy {:?} (known: {})", spec.name, names.join(", "))))
+    };
+    let mut params = Params::new(spec, origin);
+    let factory = (engine.build)(&mut params)?;
+    match params.unknown() {
+        Some(key) => Err(error(format!("{}: unknown parameter {} (usage: {})", spec.name, key, engine.usage))),
+        None => Ok(factory),
+    }
+}
+
+/// Factory for the strategy `spec` names, for any of its uses on this machine
+pub fn build<G: RegisteredGame>(spec: &Spec) -> Built<G> {
+    build_from(spec, Origin::Local)
+}
+
+// Specs within specs, counting plain values: `mcts(tries=5)` has 2 levels
+fn nesting(spec: &Spec) -> usize {
+    1 + spec.params.iter().map(|(_, value)| nesting(value)).max().unwrap_or(0)
+}
+
+/// Like `build`, but only with what `Origin::Client` allows, and no bigger than
+/// the `CLIENT_MAX_*` bounds
+pub fn build_for_client<G: RegisteredGame>(spec: &Spec) -> Built<G> {
+    if nesting(spec) > CLIENT_MAX_NESTING {
+        return Err(error(format!("clients may nest specs at most {} levels deep", CLIENT_MAX_NESTING)))
+    }
+    build_from(spec, Origin::Client)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::kids_shogi::KidsShogiGame;
+
+    #[test]
+    fn parses_and_prints() {
+        let spec = Spec::parse(" book( file = x.book , then=alphabeta(depth=8,eval=neuro:ks.model)) ").unwrap();
+        assert_eq!(spec, Spec::word("book").with("file", "x.book")
+            .with_spec("then", Spec::word("alphabeta").with("depth", 8).with("eval", "neuro:ks.model")));
+        assert_eq!(spec.to_string(), "book(file=x.book,then=alphabeta(depth=8,eval=neuro:ks.model))");
+        let quoted = Spec::parse(r#"external(command="sh -c \"echo b2b3\"")"#).unwrap();
+        assert_eq!(quoted.params[0].1.name, r#"sh -c "echo b2b3""#);
+        assert_eq!(Spec::parse(&quoted.to_string()).unwrap(), quoted);
+        assert_eq!(Spec::parse("random()").unwrap(), Spec::word("random"));
+
+        for bad in ["", "mcts(", "mcts(tries)", "mcts(tries=1", "mcts) x", "a(b=\"c)"] {
+            assert!(Spec::parse(bad).is_err(), "{:?}", bad);
+        }
+        assert_eq!(Spec::parse("mcts(tries 5)").unwrap_err().to_string(), r#"expected '=' at "5)" in "mcts(tries 5)""#);
+    }
+
+    #[test]
+    fn builds_engines() {
+        let pos = KidsShogiGame::from_fen("l2/G2/3/L2 b -").unwrap();
+        for text in ["alphabeta(depth=2)", "mcts(tries=50,eval=weights)", "mcts(tries=50,threads=2,memory=64,select=puct,c_puct=0.5,policy=eval,verbose=false)", "mate(nodes=1000,then=random)",
+                     "ensemble(member=alphabeta(depth=1),member=random,combine=vote)", "skill(level=10,then=alphabeta(depth=2))"] {
+            let factory = build::<KidsShogiGame>(&Spec::parse(text).unwrap()).unwrap();
+            assert_eq!(factory.create(1).choose_move(&pos), Some("a3a4".to_string()), "{}", text);
+        }
+        let analysis = build::<KidsShogiGame>(&Spec::parse("mate(then=alphabeta(depth=2))").unwrap()).unwrap();
+        assert!(analysis.create_analysis(1).is_some());
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        let message = |text: &str| build::<KidsShogiGame>(&Spec::parse(text).unwrap()).err().unwrap().to_string();
+        assert!(message("minimax").starts_with(r#"unknown strategy "minimax" (known: mcts, alphabeta"#));
+        assert_eq!(message("alphabeta(dept=3)"), "alphabeta: unknown parameter dept (usage: alphabeta(depth=10,threads=1,eval=simple))");
+        assert_eq!(message("mcts(tries=many)"), r#"mcts: invalid tries "many""#);
+        assert_eq!(message("skill(level=11,then=random)"), "skill: level must be between 1 and 10");
+        assert!(message("mcts(memory=0)").starts_with("mcts: memory must be between 1 and "));
+        assert!(message("mcts(memory=1000000000)").starts_with("mcts: memory must be between 1 and "));
+        assert_eq!(message("book(then=random)"), "book: file is required");
+        assert!(message("mcts(eval=neuro:/nonexistent.model)").starts_with("mcts: cannot load neuro:/nonexistent.model"));
+        assert_eq!(message("mcts(depth=-1)"), "mcts: depth must be at least 1");
+        assert_eq!(message("mcts(tries=0)"), "mcts: tries must be at least 1");
+        assert_eq!(message("alphabeta(depth=0)"), "alphabeta: depth must be at least 1");
+        assert_eq!(message("mcts(select=ucb)"), r#"mcts: unknown select "ucb" (softmax, puct)"#);
+        assert!(message("mcts(fpu=0.5)").starts_with("mcts: unknown parameter fpu"));
+        assert_eq!(message("mcts(eval=magic)"), r#"mcts: unknown eval "magic" (simple, weights[:FILE], neuro:FILE, nnue:FILE)"#);
+
+        let client = |text: &str| build_for_client::<KidsShogiGame>(&Spec::parse(text).unwrap()).err().map(|e| e.to_string());
+        assert_eq!(client("alphabeta(depth=3)"), None);
+        assert_eq!(client("mcts(depth=-1)").unwrap(), "mcts: depth must be at least 1");
+        assert_eq!(client("mate(then=external(command=sh))").unwrap(), "external is not available to clients");
+        assert_eq!(client("mcts(eval=nnue:x.nnue)").unwrap(), "mcts: eval nnue:x.nnue is not available to clients");
+        assert_eq!(client("ensemble(member=random,member=random,member=random,member=random,member=random)").unwrap(),
+            "ensemble: clients may combine at most 4 members");
+        assert_eq!(client("skill(level=1,then=mate(then=ensemble(member=alphabeta(depth=1))))").unwrap(),
+            "clients may nest specs at most 4 levels deep");
+    }
+
+    #[test]
+    fn bounds_client_specs() {
+        let spec = Spec::parse("mcts(threads=100000,tries=5)").unwrap();
+        let mut params = Params::new(&spec, Origin::Client);
+        assert_eq!(params.bounded("threads", 1, 1, CLIENT_MAX_THREADS), Ok(CLIENT_MAX_THREADS));
+        assert_eq!(params.bounded("tries", 1000, 1, CLIENT_MAX_TRIES), Ok(5));
+        let mut params = Params::new(&spec, Origin::Local);
+        assert_eq!(params.bounded("threads", 1, 1, CLIENT_MAX_THREADS), Ok(100000));
+
+        // Building a spec again leaks no new evaluator
+        let simple = |origin| load_eval::<KidsShogiGame>("simple", origin).unwrap() as *const _;
+        assert_eq!(simple(Origin::Client), simple(Origin::Client));
+        assert_eq!(simple(Origin::Client), simple(Origin::Local));
+        assert!(build_for_client::<KidsShogiGame>(&Spec::parse("mcts(threads=100000,memory=100000,policy=eval)").unwrap()).is_ok());
+    }
+}
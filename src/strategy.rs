@@ -1,14 +1,91 @@
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 // Basic strategy engine
 use super::abstract_game as ag;
+use crate::tree_dump::TreeNode;
 
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
 use rand::distributions::WeightedIndex;
 
+/// Budget for a single move search. Unset limits leave the engine's own settings
+/// (e.g. `num_tries`, `max_depth`) in charge; `stop` can be raised from another
+/// thread to make the engine return its current best move as soon as possible.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub movetime: Option<Duration>,
+    pub nodes: Option<usize>,
+    pub depth: Option<i32>,
+    pub stop: Arc<AtomicBool>,
+}
+
+impl SearchLimits {
+    pub fn with_movetime(movetime: Option<Duration>) -> Self {
+        SearchLimits { movetime, ..Default::default() }
+    }
+
+    /// True if a node or time budget was given, i.e. the engine should search
+    /// until it runs out rather than to its configured size.
+    pub fn is_budgeted(&self) -> bool {
+        self.movetime.is_some() || self.nodes.is_some()
+    }
+
+    pub fn deadline(&self, start: Instant) -> Option<Instant> {
+        self.movetime.map(|t| start + t)
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
 pub trait StrategyEngine<PosT: ag::AbstractGame> {
     fn choose_move(&mut self, pos: &PosT) -> Option<String>;
+
+    /// Like `choose_move`, but within the given budget. Engines without a notion
+    /// of budget ignore it.
+    fn choose_move_with_limits(&mut self, pos: &PosT, _limits: &SearchLimits) -> Option<String> {
+        self.choose_move(pos)
+    }
+
+    /// Think about `pos`, the position the opponent has to answer, until `limits`
+    /// run out or `limits.stop` is raised, keeping whatever helps the next
+    /// `choose_move` (e.g. a transposition table). Engines that keep nothing
+    /// between moves return at once.
+    fn ponder(&mut self, _pos: &PosT, _limits: &SearchLimits) {}
+
+    /// The tree the engine searched below `pos`, `depth` plies deep, leaving out moves
+    /// tried fewer than `min_visits` times; `None` for engines that keep no tree.
+    fn dump_tree(&self, _pos: &PosT, _depth: usize, _min_visits: usize) -> Option<TreeNode> {
+        None
+    }
+}
+
+impl<PosT: ag::AbstractGame, S: StrategyEngine<PosT> + ?Sized> StrategyEngine<PosT> for Box<S> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        (**self).choose_move(pos)
+    }
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        (**self).choose_move_with_limits(pos, limits)
+    }
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        (**self).ponder(pos, limits)
+    }
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        (**self).dump_tree(pos, depth, min_visits)
+    }
+}
+
+/// Independent seed number `stream` derived from `seed` (SplitMix64 finaliser), for
+/// handing one seed on to several engines.
+pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
 }
 
 #[derive(Clone)]
@@ -16,6 +93,21 @@ pub struct RandomMoveStrategy {
     rng : StdRng,
 }
 
+impl RandomMoveStrategy {
+    pub fn new(seed: u64) -> Self {
+        RandomMoveStrategy { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+/// Creates a `RandomMoveStrategy` for each game.
+pub struct RandomMoveFactory;
+
+impl<PosT: ag::AbstractGame + Send + 'static> ag::StrategyFactory<PosT> for RandomMoveFactory {
+    fn create(&self, seed: u64) -> Box<dyn StrategyEngine<PosT> + Send> {
+        Box::new(RandomMoveStrategy::new(seed))
+    }
+}
+
 impl<PosT: ag::AbstractGame> StrategyEngine<PosT> for RandomMoveStrategy {
     fn choose_move(&mut self, pos: &PosT) -> Option<String> {
         let moves = pos.possible_moves();
@@ -38,18 +130,55 @@ impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> FindWinningMoveStrategy<Po
     pub fn new(f: F) -> Self {
         FindWinningMoveStrategy { followup: f, pos_type: PhantomData }
     }
-}
 
-impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> StrategyEngine<PosT> for FindWinningMoveStrategy<PosT, F> {
-    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+    fn winning_move(pos: &PosT) -> Option<String> {
         let moves = pos.possible_moves();
         let n = moves.iter().position(
             |mv| pos.make_move(mv).and_then(|pos1| Some(pos1.is_lost())).unwrap_or(false));
-        if n.is_none() {
-            self.followup.choose_move(pos)
-        } else {
-            Some(moves[n.unwrap()].clone())
-        }
+        n.map(|n| moves[n].clone())
+    }
+}
+
+impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> StrategyEngine<PosT> for FindWinningMoveStrategy<PosT, F> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        Self::winning_move(pos).or_else(|| self.followup.choose_move(pos))
+    }
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        Self::winning_move(pos).or_else(|| self.followup.choose_move_with_limits(pos, limits))
+    }
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        self.followup.ponder(pos, limits)
+    }
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        self.followup.dump_tree(pos, depth, min_visits)
+    }
+}
+
+/// Gives `inner` a fixed time per move when it is asked through `choose_move`,
+/// e.g. in matches that play every engine the same way.
+pub struct MoveTimeStrategy<F> {
+    inner: F,
+    movetime: Duration,
+}
+
+impl<F> MoveTimeStrategy<F> {
+    pub fn new(inner: F, movetime: Duration) -> Self {
+        MoveTimeStrategy { inner, movetime }
+    }
+}
+
+impl<PosT: ag::AbstractGame, F: StrategyEngine<PosT>> StrategyEngine<PosT> for MoveTimeStrategy<F> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.inner.choose_move_with_limits(pos, &SearchLimits::with_movetime(Some(self.movetime)))
+    }
+    fn choose_move_with_limits(&mut self, pos: &PosT, limits: &SearchLimits) -> Option<String> {
+        self.inner.choose_move_with_limits(pos, limits)
+    }
+    fn ponder(&mut self, pos: &PosT, limits: &SearchLimits) {
+        self.inner.ponder(pos, limits)
+    }
+    fn dump_tree(&self, pos: &PosT, depth: usize, min_visits: usize) -> Option<TreeNode> {
+        self.inner.dump_tree(pos, depth, min_visits)
     }
 }
 
@@ -81,31 +210,32 @@ impl<PosT: ag::AbstractGame> ag::Evaluator<PosT> for OneStepEvaluator<PosT> {
     }
 }
 
-pub struct SoftMaxStrategy<'a, PosT: ag::AbstractGame, Eval: ag::Evaluator<PosT>> {
+/// Picks moves at random with probabilities growing exponentially with their
+/// evaluation. `rng` may be owned or a `&mut` borrowed from the caller.
+pub struct SoftMaxStrategy<'a, PosT: ag::AbstractGame, Eval: ag::Evaluator<PosT>, R: Rng = StdRng> {
     eval: &'a Eval,
     softness: f64,  // "softness" coefficient - how much we trust the evaluator
-    rng: StdRng,
+    rng: R,
     pos_type: PhantomData<PosT>,
 }
 
-impl<'a, PosT: ag::AbstractGame, E: ag::Evaluator<PosT>> SoftMaxStrategy<'a, PosT, E> {
-    pub fn new(e: &'a E,softness: f64) -> Self {
+impl<'a, PosT: ag::AbstractGame, E: ag::Evaluator<PosT>, R: Rng> SoftMaxStrategy<'a, PosT, E, R> {
+    pub fn new(e: &'a E, softness: f64, rng: R) -> Self {
         SoftMaxStrategy{
             eval: e,
             softness: softness,
-            rng: StdRng::from_entropy(),
+            rng,
             pos_type: PhantomData
         }
     }
 }
 
-impl<'a, PosT: ag::AbstractGame, E: ag::Evaluator<PosT>> StrategyEngine<PosT> for SoftMaxStrategy<'a, PosT, E> {
-    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
-        let moves = pos.possible_moves();
+impl<'a, PosT: ag::AbstractGame, E: ag::Evaluator<PosT>, R: Rng> SoftMaxStrategy<'a, PosT, E, R> {
+    /// Like `choose_move`, but samples only among `moves`, legal moves of `pos`
+    pub fn choose_from(&mut self, pos: &PosT, moves: Vec<String>) -> Option<String> {
         if moves.is_empty() { return None }
-        let values = moves.iter().map(
-            |mv| -self.eval.evaluate_position(&pos.make_move(mv).unwrap()))
-            .map(|v| (v*self.softness).exp())
+        let values = self.eval.evaluate_children(pos, &moves).into_iter()
+            .map(|v| (-v*self.softness).exp())
             .collect::<Vec<f64>>();
         let sum = values.iter().fold(0.0, |acc,x| acc+x);
         let weights = values.iter().map(|v| ((v * 1000000.0)/sum) as i32).collect::<Vec<_>>();
@@ -116,6 +246,12 @@ impl<'a, PosT: ag::AbstractGame, E: ag::Evaluator<PosT>> StrategyEngine<PosT> fo
     }
 }
 
+impl<'a, PosT: ag::AbstractGame, E: ag::Evaluator<PosT>, R: Rng> StrategyEngine<PosT> for SoftMaxStrategy<'a, PosT, E, R> {
+    fn choose_move(&mut self, pos: &PosT) -> Option<String> {
+        self.choose_from(pos, pos.possible_moves())
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::abstract_game::{tests as agt, Evaluator};
@@ -158,6 +294,21 @@ pub mod tests {
         assert_eq!(strategy.choose_move(&g2).unwrap(), "2");
     }
 
+    #[test]
+    fn soft_max_strategy_is_reproducible() {
+        let eval = OneStepEvaluator::<agt::OneTwoGame>::new();
+        let g = agt::OneTwoGame::from_str("7 0").unwrap();
+        let moves = |seed| {
+            let mut strategy = SoftMaxStrategy::new(&eval, 0.1, StdRng::seed_from_u64(seed));
+            (0..20).map(|_| strategy.choose_move(&g).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(moves(7), moves(7));
+        // Borrowing the caller's rng draws from the same stream
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut borrowed = SoftMaxStrategy::new(&eval, 0.1, &mut rng);
+        assert_eq!((0..20).map(|_| borrowed.choose_move(&g).unwrap()).collect::<Vec<_>>(), moves(7));
+    }
+
     #[test]
     fn one_step_evaluator() {
         let eval = OneStepEvaluator::<agt::OneTwoGame>::new();
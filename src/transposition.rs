@@ -0,0 +1,161 @@
+// Lock-free transposition table shared between search threads
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    Lower,  // fail high: score >= beta
+    Upper,  // fail low: score <= alpha
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TTEntry {
+    pub depth: i32,
+    pub score: f64,
+    pub bound: Bound,
+    /// `move_fingerprint` of the best move, 0 if unknown
+    pub best_move: u16,
+}
+
+// Data word layout (LSB first):
+//   bits  0..32 : score as f32
+//   bits 32..40 : depth (saturated to 255)
+//   bits 40..42 : bound (0 = empty slot, 1 = exact, 2 = lower, 3 = upper)
+//   bits 42..48 : generation
+//   bits 48..64 : best move fingerprint
+fn pack(entry: &TTEntry, generation: u8) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1u64,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    (entry.score as f32).to_bits() as u64
+        | (entry.depth.clamp(0, 255) as u64) << 32
+        | bound << 40
+        | ((generation & 0x3f) as u64) << 42
+        | (entry.best_move as u64) << 48
+}
+
+fn unpack(data: u64) -> Option<(TTEntry, u8)> {
+    let bound = match (data >> 40) & 0x3 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let entry = TTEntry {
+        score: f32::from_bits(data as u32) as f64,
+        depth: ((data >> 32) & 0xff) as i32,
+        bound,
+        best_move: (data >> 48) as u16,
+    };
+    Some((entry, ((data >> 42) & 0x3f) as u8))
+}
+
+/// 64-bit table key for any position hash type.
+pub fn table_key<H: Hash>(h: &H) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    h.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Compact non-zero identifier of a move, stored instead of the move string.
+pub fn move_fingerprint(mv: &str) -> u16 {
+    let fp = table_key(&mv) as u16;
+    if fp == 0 { 1 } else { fp }
+}
+
+/// Fixed-size table of (key ^ data, data) pairs. Readers check that the two words
+/// agree with the probed key, so a slot torn by a concurrent writer reads as a miss
+/// instead of returning another position's data; no locks are needed.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// `size` is rounded up to a power of two
+    pub fn new(size: usize) -> Self {
+        let size = size.next_power_of_two();
+        TranspositionTable {
+            slots: (0..size).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// Start a new search: entries from older searches become preferred victims.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn slot(&self, key: u64) -> &[AtomicU64; 2] {
+        &self.slots[(key as usize) & (self.slots.len() - 1)]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let slot = self.slot(key);
+        let data = slot[1].load(Ordering::Relaxed);
+        if slot[0].load(Ordering::Relaxed) ^ data != key {
+            return None
+        }
+        unpack(data).map(|(entry, _)| entry)
+    }
+
+    pub fn store(&self, key: u64, entry: TTEntry) {
+        let slot = self.slot(key);
+        let generation = self.generation.load(Ordering::Relaxed) & 0x3f;
+        let old_data = slot[1].load(Ordering::Relaxed);
+        let same_key = slot[0].load(Ordering::Relaxed) ^ old_data == key;
+        if let Some((old, old_generation)) = unpack(old_data) {
+            // Keep deeper results of the current search for other positions
+            if !same_key && old_generation == generation && old.depth > entry.depth {
+                return
+            }
+        }
+        let data = pack(&entry, generation);
+        slot[0].store(key ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1000);
+        let entry = TTEntry { depth: 5, score: -0.25, bound: Bound::Lower, best_move: move_fingerprint("b2b3") };
+        assert!(tt.probe(42).is_none());
+        tt.store(42, entry);
+        assert_eq!(tt.probe(42), Some(entry));
+        // Same slot, different key
+        assert!(tt.probe(42 + 1024).is_none());
+    }
+
+    #[test]
+    fn replacement_prefers_depth() {
+        let tt = TranspositionTable::new(16);
+        let deep = TTEntry { depth: 8, score: 1.0, bound: Bound::Exact, best_move: 0 };
+        let shallow = TTEntry { depth: 2, score: 0.5, bound: Bound::Exact, best_move: 0 };
+        tt.store(3, deep);
+        tt.store(3 + 16, shallow);
+        assert_eq!(tt.probe(3), Some(deep));
+        // Entries from an older search are always replaced
+        tt.new_search();
+        tt.store(3 + 16, shallow);
+        assert_eq!(tt.probe(3 + 16), Some(shallow));
+        assert!(tt.probe(3).is_none());
+    }
+
+    #[test]
+    fn mate_scores_survive_packing() {
+        let tt = TranspositionTable::new(16);
+        let entry = TTEntry { depth: 1, score: 997.0, bound: Bound::Upper, best_move: 7 };
+        tt.store(5, entry);
+        assert_eq!(tt.probe(5).unwrap().score, 997.0);
+    }
+}
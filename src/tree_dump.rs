@@ -0,0 +1,145 @@
+// Search trees written out for debugging: JSON for scripts, Graphviz DOT for eyes
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::abstract_game as ag;
+use crate::strategy::StrategyEngine;
+
+/// Proven result of a position, for the player to move there
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Won,
+    Lost,
+}
+
+/// A position of an engine's search tree, with the moves searched from it.
+/// Rewards and evaluations are in -1..1, for the player to move in `position`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct TreeNode {
+    /// Move leading here from the parent; none at the root
+    #[serde(rename = "move", skip_serializing_if = "Option::is_none", default)]
+    pub mv: Option<String>,
+    /// As the game writes positions (FEN for kids' shogi)
+    pub position: String,
+    /// Walks along `mv`, or through the root at the root
+    pub visits: usize,
+    /// Average reward of every walk through the position, whichever way it came
+    pub reward: f64,
+    pub evaluation: f64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proof: Option<Outcome>,
+    /// Best first; none where the position was already expanded elsewhere in the dump
+    #[serde(default)]
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Graphviz digraph of the tree, positions as boxes and moves as edges
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tree {\n    node [shape=box, fontname=\"monospace\"];\n");
+        let mut next_id = 0;
+        self.write_dot(&mut dot, &mut next_id);
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Writes this node and its subtree; returns this node's id
+    fn write_dot(&self, dot: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let proof = match self.proof {
+            Some(Outcome::Won) => "\\nproven won",
+            Some(Outcome::Lost) => "\\nproven lost",
+            None => "",
+        };
+        dot.push_str(&format!("    n{} [label=\"{}\\nvisits {}  reward {:.3}\\neval {:.3}{}\"];\n",
+            id, self.position.replace('"', "\\\""), self.visits, self.reward, self.evaluation, proof));
+        for child in &self.children {
+            let child_id = child.write_dot(dot, next_id);
+            dot.push_str(&format!("    n{} -> n{} [label=\"{}\"];\n",
+                id, child_id, child.mv.as_deref().unwrap_or_default()));
+        }
+        id
+    }
+}
+
+/// Writes the engine's tree after each of its moves into a directory (`--dump-tree`)
+pub struct TreeDumper {
+    dir: PathBuf,
+    depth: usize,
+    min_visits: usize,
+}
+
+impl TreeDumper {
+    pub fn new(dir: &Path, depth: usize, min_visits: usize) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(TreeDumper { dir: dir.to_path_buf(), depth, min_visits })
+    }
+
+    /// Writes the tree `engine` searched for `pos` to `ply-NNN.json` and `ply-NNN.dot`.
+    /// Returns false, writing nothing, if the engine keeps no tree for `pos`.
+    pub fn dump<PosT: ag::AbstractGame>(
+        &self, engine: &(impl StrategyEngine<PosT> + ?Sized), pos: &PosT, ply: usize,
+    ) -> io::Result<bool> {
+        let Some(tree) = engine.dump_tree(pos, self.depth, self.min_visits) else {
+            return Ok(false)
+        };
+        let path = self.dir.join(format!("ply-{:03}", ply));
+        std::fs::write(path.with_extension("json"), serde_json::to_string_pretty(&tree)?)?;
+        std::fs::write(path.with_extension("dot"), tree.to_dot())?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn leaf(mv: &str, visits: usize) -> TreeNode {
+        TreeNode { mv: Some(mv.to_string()), position: "3/3/3/3 w -".to_string(), visits,
+            reward: -0.5, evaluation: 0.25, proof: None, children: vec![] }
+    }
+
+    #[test]
+    fn dot_has_a_box_per_position_and_an_edge_per_move() {
+        let root = TreeNode { mv: None, position: "gle/1c1/1C1/ELG b -".to_string(), visits: 10,
+            reward: 0.5, evaluation: 0.0, proof: Some(Outcome::Won),
+            children: vec![leaf("b2b3", 7), leaf("a1a2", 3)] };
+        let dot = root.to_dot();
+        assert!(dot.starts_with("digraph tree {"));
+        assert!(dot.contains("n0 [label=\"gle/1c1/1C1/ELG b -\\nvisits 10  reward 0.500\\neval 0.000\\nproven won\"]"));
+        assert!(dot.contains("n0 -> n1 [label=\"b2b3\"]"));
+        assert!(dot.contains("n0 -> n2 [label=\"a1a2\"]"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn dumper_writes_json_and_dot() {
+        use crate::abstract_game::{tests::OneTwoGame, AbstractGame};
+        use crate::strategy::{OneStepEvaluator, RandomMoveStrategy};
+        let dir = std::env::temp_dir().join(format!("tree_dump_test_{}", std::process::id()));
+        let dumper = TreeDumper::new(&dir, 1, 1).unwrap();
+        let pos = OneTwoGame::from_str("5 0").unwrap();
+        let eval = OneStepEvaluator::<OneTwoGame>::new();
+        let mut mcts = crate::mcts::MonteCarloTreeSearchStrategy::new(&eval, 50, 3.0, 8, 1);
+        mcts.choose_move(&pos).unwrap();
+        assert!(dumper.dump(&mcts, &pos, 3).unwrap());
+        let json = std::fs::read_to_string(dir.join("ply-003.json")).unwrap();
+        assert_eq!(serde_json::from_str::<TreeNode>(&json).unwrap().position, "5 0");
+        assert!(std::fs::read_to_string(dir.join("ply-003.dot")).unwrap().starts_with("digraph"));
+        assert!(!dumper.dump(&RandomMoveStrategy::new(1), &pos, 4).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let root = TreeNode { mv: None, position: "gle/1c1/1C1/ELG b -".to_string(), visits: 1,
+            reward: 0.0, evaluation: 0.0, proof: None, children: vec![leaf("b2b3", 1)] };
+        let json = serde_json::to_value(&root).unwrap();
+        assert!(json.get("move").is_none());
+        assert_eq!(json["children"][0]["move"], "b2b3");
+        assert_eq!(serde_json::from_value::<TreeNode>(json).unwrap(), root);
+    }
+}
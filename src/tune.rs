@@ -0,0 +1,226 @@
+// Texel-style tuning: fit evaluator weights to game results by local search
+//
+// Each training position carries the result of its game from the side to move's
+// point of view (1 win, 0.5 draw, 0 loss). The predicted result is
+// sigmoid(k * evaluate_position / saturation), and the error is the mean logistic
+// loss over all positions. `k` is fitted once to the starting weights; then every
+// weight is nudged up or down by a step while that lowers the error, halving the
+// step whenever no weight moves.
+
+use crate::abstract_game::{self as ag};
+use crate::strategy::StrategyEngine;
+
+/// Evaluator whose weights the tuner may change.
+pub trait Tunable<PosT: ag::AbstractGame>: ag::Evaluator<PosT> {
+    fn param_names(&self) -> Vec<String>;
+    fn params(&self) -> Vec<f64>;
+    fn set_params(&mut self, params: &[f64]);
+}
+
+pub struct TuneParameters {
+    /// Passes over all weights
+    pub max_iterations: usize,
+    pub initial_step: f64,
+    /// Stop once the step has been halved below this
+    pub min_step: f64,
+}
+
+impl Default for TuneParameters {
+    fn default() -> Self {
+        TuneParameters { max_iterations: 100, initial_step: 0.5, min_step: 0.01 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneReport {
+    /// Fitted sigmoid scale
+    pub k: f64,
+    pub error_before: f64,
+    pub error_after: f64,
+    pub iterations: usize,
+}
+
+fn result_of(token: &str) -> Option<Option<i32>> {
+    match token {
+        "1-0" => Some(Some(0)),
+        "0-1" => Some(Some(1)),
+        "1/2-1/2" => Some(None),
+        _ => None,
+    }
+}
+
+// Result for `player` of a game won by `winner` (`None` for a draw)
+fn score_for(player: i32, winner: Option<i32>) -> f64 {
+    match winner {
+        Some(w) if w == player => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    }
+}
+
+/// Every position of a game played from the initial position, scored for its side
+/// to move. Decided positions are left out: their evaluation does not depend on
+/// the weights. Returns `None` if the game contains an illegal move.
+pub fn game_positions<PosT: ag::AbstractGame>(moves: &[String], winner: Option<i32>) -> Option<Vec<(PosT, f64)>> {
+    let mut pos = PosT::initial();
+    let mut positions = Vec::new();
+    for mv in moves {
+        positions.push((pos.clone(), score_for(pos.current_player(), winner)));
+        pos = pos.make_move(mv)?;
+    }
+    positions.retain(|(p, _)| !p.is_lost());
+    Some(positions)
+}
+
+/// Parse training positions, one per line, in either of two forms:
+///   `<position> <result>` - a position (`AbstractGame::from_str`) and its game's result
+///   `<moves...> <result>` - a whole game from the initial position, as for `--import-games`
+/// Results are "1-0", "0-1" or "1/2-1/2". Lines without a result, with an illegal
+/// move or an unreadable position are skipped.
+pub fn parse_positions<PosT: ag::AbstractGame>(text: &str) -> Vec<(PosT, f64)> {
+    text.lines().flat_map(|line| {
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let Some((last, rest)) = tokens.split_last() else { return Vec::new() };
+        let Some(winner) = result_of(last) else { return Vec::new() };
+        if let Some(pos) = PosT::from_str(&rest.join(" ")) {
+            return if pos.is_lost() { Vec::new() } else { vec![(pos.clone(), score_for(pos.current_player(), winner))] }
+        }
+        let moves = rest.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        game_positions(&moves, winner).unwrap_or_default()
+    }).collect()
+}
+
+/// Play `games` games of `strat` against itself and collect their positions.
+/// Games longer than `max_plies` are scored as draws.
+pub fn self_play_positions<PosT: ag::AbstractGame, S: StrategyEngine<PosT> + ?Sized>(
+    strat: &mut S, games: usize, max_plies: usize,
+) -> Vec<(PosT, f64)> {
+    (0..games).flat_map(|_| {
+        let mut pos = PosT::initial();
+        let mut moves = Vec::new();
+        while !pos.is_lost() && moves.len() < max_plies {
+            let Some(mv) = strat.choose_move(&pos) else { break };
+            pos = pos.make_move(&mv).expect("strategy must play legal moves");
+            moves.push(mv);
+        }
+        let winner = pos.is_lost().then(|| 1 - pos.current_player());
+        game_positions(&moves, winner).unwrap_or_default()
+    }).collect()
+}
+
+/// Mean logistic loss of the evaluator's predictions.
+pub fn error<PosT: ag::AbstractGame, E: ag::Evaluator<PosT>>(eval: &E, positions: &[(PosT, f64)], k: f64) -> f64 {
+    const EPS: f64 = 1e-9;
+    let total = positions.iter().fold(0.0, |sum, (pos, result)| {
+        let x = k * eval.evaluate_position(pos) / eval.saturation();
+        let p = (1.0 / (1.0 + (-x).exp())).clamp(EPS, 1.0 - EPS);
+        sum - (result * p.ln() + (1.0 - result) * (1.0 - p).ln())
+    });
+    total / positions.len().max(1) as f64
+}
+
+/// The sigmoid scale that fits the current weights best, from a log-spaced grid.
+pub fn fit_k<PosT: ag::AbstractGame, E: ag::Evaluator<PosT>>(eval: &E, positions: &[(PosT, f64)]) -> f64 {
+    (-8..=32).map(|i| 2f64.powf(i as f64 / 4.0))
+        .map(|k| (k, error(eval, positions, k)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(1.0, |(k, _)| k)
+}
+
+/// Tune `eval` in place; `progress` is called after every pass with the pass
+/// number, the error and the current step.
+pub fn tune<PosT: ag::AbstractGame, E: Tunable<PosT>>(
+    eval: &mut E, positions: &[(PosT, f64)], params: &TuneParameters, mut progress: impl FnMut(usize, f64, f64),
+) -> TuneReport {
+    let k = fit_k(eval, positions);
+    let error_before = error(eval, positions, k);
+    let mut best = error_before;
+    let mut weights = eval.params();
+    let mut step = params.initial_step;
+    let mut iterations = 0;
+    while iterations < params.max_iterations && step >= params.min_step {
+        iterations += 1;
+        let mut improved = false;
+        for i in 0..weights.len() {
+            for delta in [step, -step] {
+                let mut candidate = weights.clone();
+                candidate[i] += delta;
+                eval.set_params(&candidate);
+                let e = error(eval, positions, k);
+                if e < best {
+                    best = e;
+                    weights = candidate;
+                    improved = true;
+                    break;
+                }
+            }
+            eval.set_params(&weights);
+        }
+        progress(iterations, best, step);
+        if !improved {
+            step /= 2.0;
+        }
+    }
+    TuneReport { k, error_before, error_after: best, iterations }
+}
+
+/// One line per weight: its name, the value in `before` and the current one.
+pub fn format_weights<PosT: ag::AbstractGame, E: Tunable<PosT>>(before: &[f64], eval: &E) -> String {
+    eval.param_names().iter().zip(before).zip(eval.params())
+        .map(|((name, old), new)| format!("  {:<16} {:>8.3} -> {:>8.3}\n", name, old, new))
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::abstract_game::{AbstractGame, Evaluator};
+    use crate::kids_shogi::{FeatureEvaluator, KidsShogiGame};
+
+    use super::*;
+
+    #[test]
+    fn parses_positions_and_games() {
+        let text = "gle/1c1/1C1/ELG b - 1-0\n\
+                    b2b3 c4b3 1/2-1/2\n\
+                    b2b3 b2b3 0-1\n\
+                    gle/1c1/1C1/ELG w - no result\n";
+        let positions = parse_positions::<KidsShogiGame>(text);
+        // One position, then the two positions of the legal game; the illegal game is skipped
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[0].1, 1.0);
+        assert_eq!(positions[0].0.to_str(), "gle/1c1/1C1/ELG b -");
+        assert!(positions[1..].iter().all(|(_, r)| *r == 0.5));
+        // Results are from the side to move's point of view
+        let won_by_sente = game_positions::<KidsShogiGame>(&["b2b3".to_string()], Some(0)).unwrap();
+        assert_eq!(won_by_sente.len(), 1);
+        assert_eq!(won_by_sente[0].1, 1.0);
+        let lost_by_gote = parse_positions::<KidsShogiGame>("gle/1C1/3/ELG w C 1-0");
+        assert_eq!(lost_by_gote[0].1, 0.0);
+    }
+
+    #[test]
+    fn tuning_learns_material() {
+        // The side with the extra chick in hand always wins; with the chick weights
+        // zeroed the tuner has to rediscover their sign.
+        let mut positions = Vec::new();
+        for fen in ["gle/1c1/1C1/ELG b C", "gle/1c1/1C1/ELG w c", "gle/1c1/1C1/ELG b c", "gle/1c1/1C1/ELG w C"] {
+            let pos = KidsShogiGame::from_fen(fen).unwrap();
+            let ahead = FeatureEvaluator::default().evaluate_position(&pos) > 0.0;
+            positions.push((pos, if ahead { 1.0 } else { 0.0 }));
+        }
+        let mut eval = FeatureEvaluator::default();
+        eval.weights[4] = 0.0;
+        let report = tune(&mut eval, &positions, &TuneParameters::default(), |_, _, _| {});
+        assert!(report.error_after < report.error_before, "{:?}", report);
+        assert!(eval.weights[4] > 0.0);
+        assert_eq!(error(&eval, &positions, report.k), report.error_after);
+    }
+
+    #[test]
+    fn self_play_collects_positions() {
+        let mut strat = crate::strategy::RandomMoveStrategy::new(3);
+        let positions = self_play_positions::<KidsShogiGame, _>(&mut strat, 2, 20);
+        assert!(!positions.is_empty());
+        assert!(positions.iter().all(|(p, r)| !p.is_lost() && [0.0, 0.5, 1.0].contains(r)));
+    }
+}
@@ -472,7 +472,10 @@ async function startGame(playerChoice) {
   state.busy = true;
   setStatus('Starting game…');
   try {
-    const res = await rpcCall('start_game', { player: playerChoice });
+    const params = { player: playerChoice };
+    const level = $('level-select').value;
+    if (level) params.level = Number(level);  // empty means full strength
+    const res = await rpcCall('start_game', params);
     state.humanPlayer = playerChoice === 0 ? 'sente' : 'gote';
     state.gameId = res.game_id;
     state.lastAiMove = res.last_move ?? null;